@@ -1,6 +1,12 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tonic_build::compile_protos("../api/proto/scheduler/agent.proto")?;
-    tonic_build::compile_protos("../api/proto/agent/actions.proto")?;
+    // The scheduler's agent.proto imports actions.proto, so both are compiled from the common root.
+    tonic_build::configure().compile(
+        &[
+            "../api/proto/scheduler/agent.proto",
+            "../api/proto/agent/actions.proto",
+        ],
+        &["../api/proto"],
+    )?;
 
     Ok(())
 }
@@ -14,7 +14,7 @@ use crate::{
     container::{
         create_exec, inspect_exec, launch_container, remove_container, start_exec, stop_container,
     },
-    proto::{ActionResponseStream, ActionResult},
+    proto::{ActionResponseStream, ActionResult, ActionStatus},
 };
 
 pub async fn launch_action(
@@ -23,26 +23,30 @@ pub async fn launch_action(
     log_input: Arc<Mutex<UnboundedSender<Result<ActionResponseStream, Status>>>>,
     action_id: Arc<Mutex<u32>>,
     repo_url: String,
+    env: Vec<String>,
+    container_slot: Arc<Mutex<Option<String>>>,
 ) -> Result<(), Status> {
     let _ = log_input.lock().unwrap().send(Ok(ActionResponseStream {
         log: "Launching action".to_string(),
         action_id: *action_id.lock().unwrap(),
         result: Some(ActionResult {
-            completion: 1,
+            completion: ActionStatus::Scheduled.into(),
             exit_code: None,
         }),
     }));
 
-    let container_id: String = match launch_container(&image_name).await {
+    let container_id: String = match launch_container(&image_name, &env).await {
         Ok(id) => id,
         Err(e) => return Err(Status::aborted(format!("Launching error: {}", e))),
     };
+    // Let the action be cancelled from now on, by cleaning up its container.
+    *container_slot.lock().unwrap() = Some(container_id.clone());
 
     let _ = log_input.lock().unwrap().send(Ok(ActionResponseStream {
         log: format!("Container launched using image: {}", image_name),
         action_id: *action_id.lock().unwrap(),
         result: Some(ActionResult {
-            completion: 1,
+            completion: ActionStatus::Scheduled.into(),
             exit_code: None,
         }),
     }));
@@ -53,7 +57,7 @@ pub async fn launch_action(
         log: format!("Repository {} cloned", repo_name),
         action_id: *action_id.lock().unwrap(),
         result: Some(ActionResult {
-            completion: 1,
+            completion: ActionStatus::Scheduled.into(),
             exit_code: None,
         }),
     }));
@@ -70,17 +74,18 @@ pub async fn launch_action(
             Some(absolute_path),
         )
         .await?;
-        match wait_for_command(exec_id, &container_id).await {
-            Ok(_) => info!("Command completed"),
-            Err(e) => {
+        match wait_for_command(exec_id).await? {
+            0 => info!("Command completed"),
+            exit_code => {
+                // The action fails with its step: the next ones are not run.
+                info!("Step exited with an error: {}", exit_code);
                 let _ = log_input.lock().unwrap().send(Ok(ActionResponseStream {
-                    log: format!("Error happened: {}", e),
+                    log: format!("Step exited with code {}", exit_code),
                     action_id: *action_id.lock().unwrap(),
-                    result: Some(ActionResult {
-                        completion: 3,
-                        exit_code: Some(1),
-                    }),
+                    result: Some(failed_step_result(exit_code)),
                 }));
+                clean_action(container_id.as_str()).await?;
+                return Ok(());
             }
         }
     }
@@ -91,7 +96,7 @@ pub async fn launch_action(
             log: "Action completed".to_string(),
             action_id: *action_id.lock().unwrap(),
             result: Some(ActionResult {
-                completion: 3,
+                completion: ActionStatus::Completed.into(),
                 exit_code: None,
             }),
         }))
@@ -107,7 +112,10 @@ pub async fn setup_repository(repo_url: String, container_id: &str) -> Result<St
         Err(_) => return Err(Status::aborted("Error happened when creating exec")),
     };
     let _ = start_exec(&exec_id).await;
-    wait_for_command(exec_id, &container_id.to_string()).await?;
+    if wait_for_command(exec_id).await? != 0 {
+        clean_action(container_id).await?;
+        return Err(Status::aborted("Error happened when cloning repository"));
+    }
     let repo_name = match get_repo_name(&repo_url) {
         Some(repo_name) => Ok(repo_name),
         None => Err(Status::aborted("Error happened when getting repo name")),
@@ -130,7 +138,7 @@ pub async fn start_command(
         log: command.clone(),
         action_id: *action_id.lock().unwrap(),
         result: Some(ActionResult {
-            completion: 2,
+            completion: ActionStatus::Running.into(),
             exit_code: None,
         }),
     }));
@@ -149,7 +157,7 @@ pub async fn start_command(
                 log: container_log_output.to_string(),
                 action_id: *action_id.lock().unwrap(),
                 result: Some(ActionResult {
-                    completion: 2,
+                    completion: ActionStatus::Running.into(),
                     exit_code: None,
                 }),
             }));
@@ -159,27 +167,20 @@ pub async fn start_command(
     Ok(exec_id)
 }
 
-pub async fn wait_for_command(exec_id: String, container_id: &String) -> Result<(), Status> {
+/// Wait for a step to exit, and return its exit code.
+pub async fn wait_for_command(exec_id: String) -> Result<i64, Status> {
     loop {
         let exec_state = match inspect_exec(&exec_id).await {
             Ok(exec_state) => exec_state,
             Err(_) => return Err(Status::aborted("Error happened checking state of a step")),
         };
-        match exec_state.exit_code {
-            Some(0) => {
-                break;
-            }
-            Some(exit_code) => {
-                info!("Step exited with an error: {}", exit_code);
-                clean_action(container_id).await?;
-                return Err(Status::aborted("Step exited with an error"));
-            }
-            None => {}
+        if let Some(exit_code) = exec_state.exit_code {
+            return Ok(exit_code);
         }
         match exec_state.running {
             Some(true) => {}
             Some(false) => {
-                break;
+                return Ok(0);
             }
             None => {
                 return Err(Status::aborted("Error happened checking state of a step"));
@@ -187,7 +188,15 @@ pub async fn wait_for_command(exec_id: String, container_id: &String) -> Result<
         }
         sleep(Duration::from_secs(1)).await;
     }
-    Ok(())
+}
+
+/// The result of an action whose step exited with an error, carrying the step's exit code for retries to match on.
+pub fn failed_step_result(exit_code: i64) -> ActionResult {
+    ActionResult {
+        completion: ActionStatus::Error.into(),
+        // Exit codes are at most 255, anything else is reported as a plain failure.
+        exit_code: Some(i32::try_from(exit_code).unwrap_or(1)),
+    }
 }
 
 pub async fn clean_action(container_id: &str) -> Result<(), Status> {
@@ -211,3 +220,19 @@ fn get_repo_name(github_url: &str) -> Option<String> {
     let segments: Vec<&str> = url.path_segments()?.collect();
     segments.last().map(|s| s.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failed_step_keeps_its_exit_code() {
+        // A retry policy such as `on: [28]` matches the code the step exited with, and only it.
+        let retried = failed_step_result(28);
+        assert_eq!(retried.exit_code, Some(28));
+        assert_eq!(retried.completion(), ActionStatus::Error);
+        let not_retried = failed_step_result(3);
+        assert_eq!(not_retried.exit_code, Some(3));
+        assert_eq!(failed_step_result(i64::MAX).exit_code, Some(1));
+    }
+}
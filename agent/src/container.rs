@@ -8,10 +8,13 @@ use tracing::info;
 
 use crate::dockerLocal;
 
-pub async fn launch_container(image_name: &str) -> Result<String, bollard::errors::Error> {
+pub async fn launch_container(
+    image_name: &str,
+    env: &[String],
+) -> Result<String, bollard::errors::Error> {
     create_image(image_name).await?;
     info!("Image {} created", image_name);
-    let config = create_config(image_name);
+    let config = create_config(image_name, env);
     info!("Config created");
     let ContainerCreateResponse { id, warnings: _ } = create_container(config).await?;
     info!("Container created");
@@ -36,10 +39,12 @@ pub async fn create_image(
         .await;
 }
 
-pub fn create_config(image_name: &str) -> bollard::container::Config<&str> {
+/// The environment variables, as KEY=VALUE, are set on the container so that every command of the action gets them.
+pub fn create_config<'a>(image_name: &'a str, env: &'a [String]) -> bollard::container::Config<&'a str> {
     return Config {
         entrypoint: Some(vec!["/bin/sh"]),
         image: Some(image_name),
+        env: Some(env.iter().map(String::as_str).collect()),
         attach_stdin: Some(true),
         attach_stdout: Some(true),
         attach_stderr: Some(true),
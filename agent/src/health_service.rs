@@ -5,6 +5,9 @@ use tokio_stream::wrappers::UnboundedReceiverStream;
 use tonic::Request;
 use tracing::{error, info};
 
+/// Health is sent at least this often, even without any significant change, so that the scheduler knows the agent is alive.
+const HEARTBEAT_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_secs(10);
+
 use crate::proto::agent_client::AgentClient;
 use crate::proto::{Health, HealthStatus};
 
@@ -24,14 +27,18 @@ pub(crate) async fn report_health(
     };
 
     let mut system = System::new_all();
+    let mut last_sent = tokio::time::Instant::now();
     tokio::spawn(async move {
         loop {
             // Fetch current usage
             let current_health = get_current_health_status(&mut system, agent_id);
 
-            // Check if the change is significant
-            if has_significant_change(&previous_usage.health, &current_health.health, 5.0) {
+            // Check if the change is significant, or if the heartbeat is due
+            if has_significant_change(&previous_usage.health, &current_health.health, 5.0)
+                || last_sent.elapsed() >= HEARTBEAT_INTERVAL
+            {
                 previous_usage = current_health;
+                last_sent = tokio::time::Instant::now();
                 let _ = tx.send(current_health);
                 info!("Health status sent: {:?}", current_health);
                 info!("Health status sent: {:?}", current_health);
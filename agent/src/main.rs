@@ -1,10 +1,11 @@
 use bollard::Docker;
 use clap::Parser;
 use lazy_static::lazy_static;
-use registering_service::register_agent;
+use registering_service::{register_agent, unregister_agent};
 use server::ActionsLauncher;
 use std::error::Error;
 use std::sync::Mutex;
+use tokio::sync::{mpsc, oneshot};
 use tonic::transport::Server;
 use tracing::info;
 mod action;
@@ -12,12 +13,21 @@ mod container;
 mod health_service;
 mod registering_service;
 pub mod server;
+mod work_service;
 use crate::health_service::report_health;
 use crate::proto::action_service_server::ActionServiceServer;
+use crate::work_service::work;
 use tracing::error;
 mod proto {
-    tonic::include_proto!("scheduler");
-    tonic::include_proto!("actions");
+    // The scheduler package refers to the actions package as its sibling module.
+    pub mod actions {
+        tonic::include_proto!("actions");
+    }
+    pub mod scheduler {
+        tonic::include_proto!("scheduler");
+    }
+    pub use actions::*;
+    pub use scheduler::*;
 }
 
 lazy_static! {
@@ -39,6 +49,26 @@ struct Args {
     /// The port of the agent to listen on
     #[clap(long, default_value = "9001")]
     port: u32,
+
+    /// A label advertised to the scheduler, that actions can target with `runs_on`. Can be repeated
+    #[clap(long = "label")]
+    labels: Vec<String>,
+
+    /// Get actions through a stream opened to the scheduler, rather than listening for the scheduler to dial the agent. For agents behind a NAT or a firewall
+    #[clap(long)]
+    pull: bool,
+
+    /// Run a single action, then unregister and exit with the action's exit status. For autoscaled runners
+    #[clap(long)]
+    ephemeral: bool,
+}
+
+/// Wait for the exit status of the action of an ephemeral agent. Never returns for other agents.
+async fn action_finished(finished_rx: Option<mpsc::UnboundedReceiver<i32>>) -> i32 {
+    match finished_rx {
+        Some(mut finished_rx) => finished_rx.recv().await.unwrap_or(1),
+        None => std::future::pending().await,
+    }
 }
 
 #[tokio::main]
@@ -48,7 +78,16 @@ async fn main() -> Result<(), Box<dyn Error>> {
     dockerLocal.ping().await?;
     info!("Connecting to scheduler at {}", args.shost);
 
-    let (mut client, id) = match register_agent(&args.shost, &args.ahost, args.port).await {
+    let (mut client, id) = match register_agent(
+        &args.shost,
+        &args.ahost,
+        args.port,
+        args.labels,
+        args.pull,
+        args.ephemeral,
+    )
+    .await
+    {
         Ok(res) => {
             info!("Connection succeeded");
             info!("Connection succeeded");
@@ -59,6 +98,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
             return Err(err);
         }
     };
+    let mut work_client = client.clone();
+    let mut unregister_client = client.clone();
     tokio::spawn(async move {
         loop {
             let _ = report_health(&mut client, id).await;
@@ -67,13 +108,48 @@ async fn main() -> Result<(), Box<dyn Error>> {
     });
 
     info!("Agent id: {}", id);
-    info!("Starting server...");
-    let addr = format!("0.0.0.0:{}", args.port).parse()?;
-    info!("Starting server on {}", addr);
 
-    let actions = ActionsLauncher::default();
-    let server = ActionServiceServer::new(actions);
-    Server::builder().add_service(server).serve(addr).await?;
+    // An ephemeral agent stops once its action is done.
+    let mut actions = ActionsLauncher::default();
+    let mut finished_rx = None;
+    if args.ephemeral {
+        info!("Ephemeral agent, running a single action");
+        let (finished_tx, rx) = mpsc::unbounded_channel();
+        actions = actions.reporting_to(finished_tx);
+        finished_rx = Some(rx);
+    }
+
+    let exit_status = if args.pull {
+        info!("Pulling actions from the scheduler");
+        tokio::select! {
+            result = work(&mut work_client, id, actions) => {
+                result?;
+                None
+            }
+            exit_status = action_finished(finished_rx) => Some(exit_status),
+        }
+    } else {
+        info!("Starting server...");
+        let addr = format!("0.0.0.0:{}", args.port).parse()?;
+        info!("Starting server on {}", addr);
+
+        // The server shuts down gracefully, once the action's logs are all sent.
+        let (exit_status_tx, exit_status_rx) = oneshot::channel();
+        let server = ActionServiceServer::new(actions);
+        Server::builder()
+            .add_service(server)
+            .serve_with_shutdown(addr, async move {
+                let _ = exit_status_tx.send(action_finished(finished_rx).await);
+            })
+            .await?;
+        exit_status_rx.await.ok()
+    };
+
+    if let Some(exit_status) = exit_status {
+        unregister_agent(&mut unregister_client, id).await?;
+        info!("Action done, exiting with status {}", exit_status);
+        std::process::exit(exit_status);
+    }
 
     Ok(())
 }
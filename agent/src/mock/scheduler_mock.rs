@@ -1,26 +1,65 @@
 // This file is a simple mock for testing purpose
-// In order to not break the build it is commented out
-// As you can see the service is missing some implementation for the moment
 
 use proto::{
     agent_server::{Agent, AgentServer},
-    RegisterAgentResponse,
+    Empty, RegisterAgentResponse,
 };
+use tokio_stream::StreamExt;
 use tonic::{transport::Server, Response};
 mod proto {
-    tonic::include_proto!("scheduler");
+    pub mod actions {
+        tonic::include_proto!("actions");
+    }
+    pub mod scheduler {
+        tonic::include_proto!("scheduler");
+    }
+    pub use scheduler::*;
 }
 #[derive(Debug, Default)]
 struct RegistrationService {}
 
 #[tonic::async_trait]
 impl Agent for RegistrationService {
+    type WorkStream = tokio_stream::Empty<Result<proto::WorkResponse, tonic::Status>>;
+
     async fn register_agent(
         &self,
-        _request: tonic::Request<proto::Health>,
+        request: tonic::Request<proto::RegisterAgentRequest>,
     ) -> Result<tonic::Response<proto::RegisterAgentResponse>, tonic::Status> {
+        let request = request.into_inner();
+        println!(
+            "Agent registered with labels {:?} and capabilities {:?}",
+            request.labels, request.capabilities
+        );
         Ok(Response::new(RegisterAgentResponse { id: 1 }))
     }
+
+    async fn report_health_status(
+        &self,
+        request: tonic::Request<tonic::Streaming<proto::HealthStatus>>,
+    ) -> Result<tonic::Response<proto::Empty>, tonic::Status> {
+        let mut stream = request.into_inner();
+        while let Some(status) = stream.next().await {
+            println!("Health status: {:?}", status?);
+        }
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn unregister_agent(
+        &self,
+        request: tonic::Request<proto::UnregisterAgentRequest>,
+    ) -> Result<tonic::Response<proto::Empty>, tonic::Status> {
+        println!("Agent {} unregistered", request.into_inner().id);
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn work(
+        &self,
+        _request: tonic::Request<tonic::Streaming<proto::WorkRequest>>,
+    ) -> Result<tonic::Response<Self::WorkStream>, tonic::Status> {
+        println!("Agent opened its work stream, no action to give");
+        Ok(Response::new(tokio_stream::empty()))
+    }
 }
 
 #[tokio::main]
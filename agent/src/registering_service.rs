@@ -1,4 +1,7 @@
-use crate::proto::{agent_client, Health, Hostname, RegisterAgentRequest, RegisterAgentResponse};
+use crate::proto::{
+    agent_client, Capabilities, Health, Hostname, RegisterAgentRequest, RegisterAgentResponse,
+    UnregisterAgentRequest,
+};
 use sysinfo::System;
 use tonic::transport::Channel;
 use tracing::info;
@@ -7,6 +10,9 @@ pub async fn register_agent(
     scheduler_url: &String,
     agent_host: &String,
     port: u32,
+    labels: Vec<String>,
+    pull: bool,
+    ephemeral: bool,
 ) -> Result<(agent_client::AgentClient<Channel>, u32), Box<dyn std::error::Error>> {
     let mut cli: agent_client::AgentClient<tonic::transport::Channel> =
         match agent_client::AgentClient::connect(scheduler_url.to_string()).await {
@@ -26,9 +32,16 @@ pub async fn register_agent(
         host: agent_host.to_string(),
         port,
     };
+    let capabilities = detect_capabilities();
+    info!("Labels: {:?}, capabilities: {:?}", labels, capabilities);
+    // In pull mode, the scheduler never dials the agent.
     let req = RegisterAgentRequest {
         health: Some(health),
-        hostname: Some(host),
+        hostname: (!pull).then_some(host),
+        labels,
+        capabilities: Some(capabilities),
+        pull,
+        ephemeral,
     };
     let request = tonic::Request::new(req);
     let response: RegisterAgentResponse = cli.register_agent(request).await?.into_inner();
@@ -37,3 +50,25 @@ pub async fn register_agent(
 
     Ok((cli, response.id))
 }
+
+/// Leave the scheduler's pool, once an ephemeral agent is done with its action.
+pub async fn unregister_agent(
+    client: &mut agent_client::AgentClient<Channel>,
+    id: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    client
+        .unregister_agent(tonic::Request::new(UnregisterAgentRequest { id }))
+        .await?;
+    info!("Agent {} unregistered", id);
+    Ok(())
+}
+
+/// Detect what this agent is able to run: its architecture, its OS, and the runners it supports.
+/// Actions are only run in Docker containers for now.
+fn detect_capabilities() -> Capabilities {
+    Capabilities {
+        arch: std::env::consts::ARCH.to_string(),
+        os: std::env::consts::OS.to_string(),
+        runner_types: vec![String::from("docker")],
+    }
+}
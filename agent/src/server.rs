@@ -1,27 +1,46 @@
-use crate::action::launch_action;
-use crate::proto::{action_service_server::ActionService, ActionRequest, ActionResponseStream};
+use crate::action::{clean_action, launch_action};
+use crate::proto::{
+    action_service_server::ActionService, ActionRequest, ActionResponseStream,
+    CancelActionRequest, CancelActionResponse,
+};
 use futures_util::Stream;
+use std::collections::HashMap;
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc::{self};
+use tokio::task::AbortHandle;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use tonic::{async_trait, Request, Response, Status};
+use tracing::info;
 
-#[derive(Default)]
-pub struct ActionsLauncher {}
+/// An action being run by the agent, that can be cancelled.
+struct RunningAction {
+    task: AbortHandle,
+    container_id: Arc<Mutex<Option<String>>>, // Set once the action's container is launched
+    exit_status: Arc<Mutex<i32>>, // 0 until a command fails, the action fails to launch or is cancelled
+}
 
-#[async_trait]
-impl ActionService for ActionsLauncher {
-    type ExecutionActionStream =
-        Pin<Box<dyn Stream<Item = Result<ActionResponseStream, Status>> + Send>>;
+/// Launches the actions, whether they come from the scheduler dialing the agent or down the agent's work stream.
+#[derive(Default, Clone)]
+pub struct ActionsLauncher {
+    running: Arc<Mutex<HashMap<u32, RunningAction>>>,
+    finished: Option<mpsc::UnboundedSender<i32>>, // Where the exit status of each action goes once it is done
+}
 
-    async fn execution_action(
+impl ActionsLauncher {
+    /// Report the exit status of each action once it is done and its logs are all sent, e.g. for an ephemeral agent to exit with it.
+    pub(crate) fn reporting_to(mut self, finished: mpsc::UnboundedSender<i32>) -> Self {
+        self.finished = Some(finished);
+        self
+    }
+
+    /// Launch an action, and return the receiver of its logs. The receiver ends once the action is done.
+    pub(crate) fn launch(
         &self,
-        request: Request<ActionRequest>,
-    ) -> Result<Response<Self::ExecutionActionStream>, Status> {
+        mut request_body: ActionRequest,
+    ) -> Result<mpsc::UnboundedReceiver<Result<ActionResponseStream, Status>>, Status> {
         let (log_input, log_ouput) =
             mpsc::unbounded_channel::<Result<ActionResponseStream, Status>>();
-        let mut request_body = request.into_inner();
         let context = match request_body.context {
             Some(context) => context,
             None => return Err(Status::invalid_argument("Context is missing")),
@@ -31,23 +50,181 @@ impl ActionService for ActionsLauncher {
             None => return Err(Status::invalid_argument("Container image is missing")),
         };
 
+        let id = request_body.action_id;
+        let exit_status = Arc::new(Mutex::new(0));
+        // To report the exit status, the logs go through a forwarder watching the exit codes.
+        let log_input = match &self.finished {
+            Some(finished) => {
+                let (forwarded_input, forwarded_output) = mpsc::unbounded_channel();
+                tokio::spawn(forward_logs(
+                    forwarded_output,
+                    log_input,
+                    exit_status.clone(),
+                    finished.clone(),
+                ));
+                forwarded_input
+            }
+            None => log_input,
+        };
         let log_input = Arc::new(Mutex::new(log_input));
         let action_id = Arc::new(Mutex::new(request_body.action_id));
-        tokio::spawn(async move {
-            let _ = launch_action(
-                container_image,
-                &mut request_body.commands,
-                log_input.clone(),
-                action_id.clone(),
-                request_body.repo_url,
-            )
-            .await
-            .map_err(|e| Status::aborted(format!("Launching error {}", e)));
+        let container_id = Arc::new(Mutex::new(None));
+
+        // The action is registered before its task can finish and unregister it.
+        let mut running = self.running.lock().unwrap();
+        let running_actions = self.running.clone();
+        let task = tokio::spawn({
+            let container_id = container_id.clone();
+            let exit_status = exit_status.clone();
+            async move {
+                let result = launch_action(
+                    container_image,
+                    &mut request_body.commands,
+                    log_input.clone(),
+                    action_id.clone(),
+                    request_body.repo_url,
+                    request_body.env,
+                    container_id,
+                )
+                .await
+                .map_err(|e| Status::aborted(format!("Launching error {}", e)));
+                if result.is_err() {
+                    let mut exit_status = exit_status.lock().unwrap();
+                    if *exit_status == 0 {
+                        *exit_status = 1;
+                    }
+                }
+                running_actions.lock().unwrap().remove(&id);
+            }
         });
+        running.insert(
+            id,
+            RunningAction {
+                task: task.abort_handle(),
+                container_id,
+                exit_status,
+            },
+        );
+        drop(running);
+
+        Ok(log_ouput)
+    }
+
+    /// Cancel a running action, stopping it where it is.
+    pub(crate) async fn cancel(&self, action_id: u32) -> Result<(), Status> {
+        let running = self.running.lock().unwrap().remove(&action_id);
+        let running = match running {
+            Some(running) => running,
+            None => return Err(Status::not_found(format!("Action {} is not running", action_id))),
+        };
+
+        // Stop the action where it is, then get rid of its container if it was launched.
+        *running.exit_status.lock().unwrap() = 1;
+        running.task.abort();
+        let container_id = running.container_id.lock().unwrap().take();
+        if let Some(container_id) = container_id {
+            clean_action(&container_id).await?;
+        }
+        info!("Action {} cancelled", action_id);
+
+        Ok(())
+    }
+}
+
+/// Pass the logs of an action on, keeping the exit code of its last failed command, then report its exit status once its logs end.
+async fn forward_logs(
+    mut logs: mpsc::UnboundedReceiver<Result<ActionResponseStream, Status>>,
+    log_output: mpsc::UnboundedSender<Result<ActionResponseStream, Status>>,
+    exit_status: Arc<Mutex<i32>>,
+    finished: mpsc::UnboundedSender<i32>,
+) {
+    while let Some(log) = logs.recv().await {
+        let exit_code = log
+            .as_ref()
+            .ok()
+            .and_then(|response| response.result.as_ref())
+            .and_then(|result| result.exit_code);
+        if let Some(exit_code) = exit_code.filter(|exit_code| *exit_code != 0) {
+            *exit_status.lock().unwrap() = exit_code;
+        }
+        let _ = log_output.send(log);
+    }
+    drop(log_output);
+    let exit_status = *exit_status.lock().unwrap();
+    let _ = finished.send(exit_status);
+}
+
+#[async_trait]
+impl ActionService for ActionsLauncher {
+    type ExecutionActionStream =
+        Pin<Box<dyn Stream<Item = Result<ActionResponseStream, Status>> + Send>>;
+
+    async fn execution_action(
+        &self,
+        request: Request<ActionRequest>,
+    ) -> Result<Response<Self::ExecutionActionStream>, Status> {
+        let log_ouput = self.launch(request.into_inner())?;
 
         let stream = UnboundedReceiverStream::new(log_ouput);
         Ok(Response::new(
             Box::pin(stream) as Self::ExecutionActionStream
         ))
     }
+
+    async fn cancel_action(
+        &self,
+        request: Request<CancelActionRequest>,
+    ) -> Result<Response<CancelActionResponse>, Status> {
+        self.cancel(request.into_inner().action_id).await?;
+
+        Ok(Response::new(CancelActionResponse {}))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::failed_step_result;
+    use crate::proto::{ActionResult, ActionStatus};
+
+    fn response(result: ActionResult) -> ActionResponseStream {
+        ActionResponseStream {
+            log: String::new(),
+            action_id: 1,
+            result: Some(result),
+        }
+    }
+
+    /// Forward the given logs, and return the exit status reported once they end.
+    async fn exit_status_of(logs: Vec<ActionResponseStream>) -> i32 {
+        let (log_input, forwarded) = mpsc::unbounded_channel();
+        let (log_output, _received) = mpsc::unbounded_channel();
+        let (finished, mut finished_rx) = mpsc::unbounded_channel();
+        for log in logs {
+            log_input.send(Ok(log)).unwrap();
+        }
+        drop(log_input);
+        forward_logs(forwarded, log_output, Arc::new(Mutex::new(0)), finished).await;
+        finished_rx.recv().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_exit_status_is_the_step_exit_code() {
+        let running = ActionResult {
+            completion: ActionStatus::Running.into(),
+            exit_code: None,
+        };
+        let completed = ActionResult {
+            completion: ActionStatus::Completed.into(),
+            exit_code: None,
+        };
+        assert_eq!(
+            exit_status_of(vec![response(running), response(failed_step_result(28))]).await,
+            28
+        );
+        assert_eq!(
+            exit_status_of(vec![response(running), response(completed)]).await,
+            0
+        );
+    }
 }
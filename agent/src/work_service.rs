@@ -0,0 +1,91 @@
+use crate::proto::agent_client::AgentClient;
+use crate::proto::work_request::Message;
+use crate::proto::work_response::Work;
+use crate::proto::{LaunchResult, WorkRequest};
+use crate::server::ActionsLauncher;
+use std::error::Error;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tonic::transport::Channel;
+use tonic::Request;
+use tracing::{error, info, warn};
+
+/// Open the work stream to the scheduler, run the actions coming down it and send whether each one started, then its logs, back up it.
+/// Returns once the scheduler closes the stream.
+pub(crate) async fn work(
+    client: &mut AgentClient<Channel>,
+    agent_id: u32,
+    launcher: ActionsLauncher,
+) -> Result<(), Box<dyn Error>> {
+    let (request_tx, request_rx) = mpsc::unbounded_channel();
+    // The first message tells the scheduler which agent this is.
+    request_tx.send(WorkRequest {
+        message: Some(Message::AgentId(agent_id)),
+    })?;
+    let mut work_stream = client
+        .work(Request::new(UnboundedReceiverStream::new(request_rx)))
+        .await?
+        .into_inner();
+    info!("Work stream opened, waiting for actions");
+
+    while let Some(work) = work_stream.message().await? {
+        match work.work {
+            Some(Work::Action(action)) => {
+                let action_id = action.action_id;
+                info!("Action {} received", action_id);
+                let request_tx = request_tx.clone();
+                // The scheduler gives an action that cannot start to another agent.
+                let mut logs = match launcher.launch(action) {
+                    Ok(logs) => {
+                        let _ = request_tx.send(launched(action_id, None));
+                        logs
+                    }
+                    Err(status) => {
+                        error!("Failed to launch action {}: {}", action_id, status);
+                        let _ = request_tx
+                            .send(launched(action_id, Some(status.message().to_string())));
+                        continue;
+                    }
+                };
+                // The logs go up the stream, then the scheduler is told that the action is done.
+                tokio::spawn(async move {
+                    while let Some(log) = logs.recv().await {
+                        match log {
+                            Ok(response) => {
+                                let _ = request_tx.send(WorkRequest {
+                                    message: Some(Message::Response(response)),
+                                });
+                            }
+                            Err(status) => {
+                                warn!("Action {} failed: {}", action_id, status);
+                                break;
+                            }
+                        }
+                    }
+                    let _ = request_tx.send(WorkRequest {
+                        message: Some(Message::FinishedActionId(action_id)),
+                    });
+                });
+            }
+            Some(Work::Cancel(cancel)) => {
+                let launcher = launcher.clone();
+                tokio::spawn(async move {
+                    if let Err(status) = launcher.cancel(cancel.action_id).await {
+                        warn!("Failed to cancel action {}: {}", cancel.action_id, status);
+                    }
+                });
+            }
+            None => warn!("Received an empty work message"),
+        }
+    }
+
+    info!("Work stream closed by the scheduler");
+    Ok(())
+}
+
+/// Tell the scheduler whether an action started, or why it could not.
+fn launched(action_id: u32, error: Option<String>) -> WorkRequest {
+    WorkRequest {
+        message: Some(Message::Launched(LaunchResult { action_id, error })),
+    }
+}
@@ -0,0 +1,2 @@
+ALTER TABLE
+    "actions" ADD COLUMN "runs_on" TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,14 @@
+UPDATE
+    "actions" SET "status" = 'ACTION_STATUS_' || UPPER("status")
+    WHERE "status" IN ('Pending', 'Scheduled', 'Running', 'Completed', 'Error');
+ALTER TABLE
+    "actions" ADD CONSTRAINT "actions_status_check" CHECK("status" IN (
+        'ACTION_STATUS_PENDING',
+        'ACTION_STATUS_SCHEDULED',
+        'ACTION_STATUS_RUNNING',
+        'ACTION_STATUS_COMPLETED',
+        'ACTION_STATUS_ERROR',
+        'ACTION_STATUS_CANCELLED',
+        'ACTION_STATUS_TIMED_OUT',
+        'ACTION_STATUS_SKIPPED'
+    ));
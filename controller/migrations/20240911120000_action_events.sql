@@ -0,0 +1,18 @@
+CREATE TABLE "action_events"(
+    "id" BIGSERIAL NOT NULL,
+    "action_id" BIGINT NOT NULL,
+    "status" VARCHAR(255) NOT NULL,
+    "source" VARCHAR(255) NOT NULL,
+    "created_at" TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+ALTER TABLE
+    "action_events" ADD PRIMARY KEY("id");
+ALTER TABLE
+    "action_events" ADD CONSTRAINT "action_events_action_id_foreign" FOREIGN KEY("action_id") REFERENCES "actions"("id") ON DELETE CASCADE ON UPDATE CASCADE;
+ALTER TABLE
+    "action_events" ADD CONSTRAINT "action_events_source_check" CHECK("source" IN ('controller', 'scheduler', 'agent'));
+CREATE INDEX "action_events_action_id_index" ON "action_events"("action_id", "created_at");
+ALTER TABLE
+    "actions" ADD COLUMN "started_at" TIMESTAMPTZ NULL;
+ALTER TABLE
+    "actions" ADD COLUMN "finished_at" TIMESTAMPTZ NULL;
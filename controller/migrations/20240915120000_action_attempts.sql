@@ -0,0 +1,13 @@
+ALTER TABLE
+    "actions" ADD COLUMN "attempt" INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE
+    "actions" ADD COLUMN "max_attempts" INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE
+    "actions" ADD COLUMN "retry_backoff" INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE
+    "actions" ADD COLUMN "retry_on" INTEGER[] NOT NULL DEFAULT '{}';
+ALTER TABLE
+    "logs" ADD COLUMN "attempt" INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE
+    "action_events" ADD COLUMN "attempt" INTEGER NOT NULL DEFAULT 1;
+CREATE INDEX "logs_action_id_index" ON "logs"("action_id", "attempt");
@@ -0,0 +1,4 @@
+ALTER TABLE
+    "pipelines" ADD COLUMN "manifest" TEXT;
+ALTER TABLE
+    "pipelines" ADD COLUMN "manifest_hash" TEXT;
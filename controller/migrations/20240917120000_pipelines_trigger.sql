@@ -0,0 +1,10 @@
+ALTER TABLE
+    "pipelines" ADD COLUMN "commit_sha" TEXT;
+ALTER TABLE
+    "pipelines" ADD COLUMN "ref" TEXT;
+ALTER TABLE
+    "pipelines" ADD COLUMN "event" TEXT;
+ALTER TABLE
+    "pipelines" ADD COLUMN "trigger" TEXT;
+ALTER TABLE
+    "pipelines" ADD COLUMN "requester" TEXT;
@@ -0,0 +1,10 @@
+ALTER TABLE
+    "pipelines" ADD COLUMN "created_at" TIMESTAMPTZ NOT NULL DEFAULT NOW();
+CREATE INDEX "pipelines_created_at_index" ON "pipelines"("created_at", "id");
+CREATE INDEX "pipelines_name_index" ON "pipelines"("name", "id");
+CREATE INDEX "pipelines_repository_url_index" ON "pipelines"("repository_url", "created_at", "id");
+CREATE INDEX "pipelines_commit_sha_index" ON "pipelines"("commit_sha" text_pattern_ops);
+CREATE INDEX "actions_pipeline_id_index" ON "actions"("pipeline_id", "status");
+CREATE INDEX "commands_action_id_index" ON "commands"("action_id");
+DROP INDEX "logs_action_id_index";
+CREATE INDEX "logs_action_id_index" ON "logs"("action_id", "attempt", "id");
@@ -0,0 +1,2 @@
+ALTER TABLE
+    "actions" ADD COLUMN "priority" INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,43 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::error;
+
+use crate::action::action_service::ActionService;
+
+/// Number of logs of a page, when not given.
+const DEFAULT_LOGS_PAGE_SIZE: i64 = 100;
+const MAX_LOGS_PAGE_SIZE: i64 = 1000;
+
+#[derive(Deserialize)]
+struct ActionByIDPath {
+    id: i64,
+}
+
+#[derive(Deserialize)]
+struct ActionLogsQueryParams {
+    offset: Option<i64>,
+    limit: Option<i64>,
+}
+
+/// The logs of an action, a page at a time: the next page starts at the `next_offset` of the current one.
+#[get("/actions/{id}/logs")]
+pub async fn get_action_logs(
+    path: web::Path<ActionByIDPath>,
+    query: web::Query<ActionLogsQueryParams>,
+    action_service: web::Data<Arc<ActionService>>,
+) -> impl Responder {
+    let offset = query.offset.unwrap_or(0).max(0);
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_LOGS_PAGE_SIZE)
+        .clamp(1, MAX_LOGS_PAGE_SIZE);
+    match action_service.find_logs(path.id, offset, limit).await {
+        Ok(Some(page)) => HttpResponse::Ok().json(page),
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            error!("Error while fetching logs of action {}: {:?}", path.id, e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
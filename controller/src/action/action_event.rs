@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+use crate::grpc_scheduler::ResponseSource;
+
+/// Component an action status change comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventSource {
+    Controller,
+    Scheduler,
+    Agent,
+}
+
+impl EventSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventSource::Controller => "controller",
+            EventSource::Scheduler => "scheduler",
+            EventSource::Agent => "agent",
+        }
+    }
+}
+
+impl From<ResponseSource> for EventSource {
+    fn from(source: ResponseSource) -> Self {
+        match source {
+            ResponseSource::Scheduler => EventSource::Scheduler,
+            ResponseSource::Agent => EventSource::Agent,
+        }
+    }
+}
+
+/// A status change of an action, as shown in the timeline of its pipeline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionEvent {
+    pub action_id: i64,
+    pub action_name: String,
+    pub status: String,
+    pub source: String,
+    pub attempt: i32,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+pub struct ActionEventRepository {
+    pool: Arc<PgPool>,
+}
+
+impl ActionEventRepository {
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create(
+        &self,
+        action_id: i64,
+        status: &str,
+        source: EventSource,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"INSERT INTO action_events (action_id, status, source) VALUES ($1, $2, $3)"#,
+            action_id,
+            status,
+            source.as_str()
+        )
+        .execute(self.pool.as_ref())
+        .await?;
+
+        Ok(())
+    }
+
+    /// Every status change of the actions of a pipeline, oldest first.
+    pub async fn find_by_pipeline_id(
+        &self,
+        pipeline_id: i64,
+    ) -> Result<Vec<ActionEvent>, sqlx::Error> {
+        sqlx::query_as!(
+            ActionEvent,
+            r#"SELECT e.action_id, a.name as action_name, e.status, e.source, e.attempt, e.created_at
+        FROM action_events e
+                 JOIN actions a on a.id = e.action_id
+        WHERE a.pipeline_id = $1
+        ORDER BY e.created_at, e.id"#,
+            pipeline_id
+        )
+        .fetch_all(self.pool.as_ref())
+        .await
+    }
+}
@@ -1,9 +1,12 @@
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use std::sync::Arc;
+use time::OffsetDateTime;
 
 use crate::{
-    action::action_service::ActionDTO, grpc_scheduler::ActionStatus, parser::pipe_parser::Type,
+    action::{action_event::EventSource, action_service::ActionDTO},
+    grpc_scheduler::ActionStatus,
+    parser::pipe_parser::{RetryPolicy, Type},
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,6 +20,39 @@ pub struct Action {
     pub logs: Option<Vec<String>>,
     r#type: Type,
     status: String,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub runs_on: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub needs: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub env: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub condition: Option<String>,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    /// Higher priority actions of a repository are dispatched first by the scheduler.
+    #[serde(default)]
+    pub priority: i32,
+    /// The current attempt at running the action, from 1.
+    #[serde(default)]
+    pub attempt: i32,
+    /// The logs of each attempt, oldest first.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attempts: Option<Vec<ActionAttempt>>,
+    #[serde(with = "time::serde::rfc3339::option", default)]
+    pub started_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option", default)]
+    pub finished_at: Option<OffsetDateTime>,
+    /// Time between the start and the end of the action, in milliseconds. None until it has both.
+    #[serde(default)]
+    pub duration: Option<i64>,
+}
+
+/// The logs of one attempt at running an action.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionAttempt {
+    pub attempt: i32,
+    pub logs: Vec<String>,
 }
 
 #[derive(Debug)]
@@ -25,6 +61,7 @@ pub enum ActionCreationError {
 }
 
 impl Action {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         id: i64,
         pipeline_id: i64,
@@ -33,6 +70,10 @@ impl Action {
         commands: Vec<String>,
         r#type: Type,
         status: String,
+        runs_on: Vec<String>,
+        needs: Vec<String>,
+        env: Vec<String>,
+        condition: Option<String>,
     ) -> Result<Self, ActionCreationError> {
         let status = ActionStatus::from_str_name(status.as_str());
         if status.is_none() {
@@ -48,8 +89,50 @@ impl Action {
             logs: None,
             r#type,
             commands,
+            runs_on,
+            needs,
+            env,
+            condition,
+            retry: RetryPolicy::default(),
+            priority: 0,
+            attempt: 1,
+            attempts: None,
+            started_at: None,
+            finished_at: None,
+            duration: None,
         });
     }
+
+    pub fn with_timing(
+        mut self,
+        started_at: Option<OffsetDateTime>,
+        finished_at: Option<OffsetDateTime>,
+    ) -> Self {
+        self.started_at = started_at;
+        self.finished_at = finished_at;
+        self.duration = match (started_at, finished_at) {
+            (Some(started_at), Some(finished_at)) => {
+                Some((finished_at - started_at).whole_milliseconds() as i64)
+            }
+            _ => None,
+        };
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy, attempt: i32) -> Self {
+        self.retry = retry;
+        self.attempt = attempt;
+        self
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn status(&self) -> &str {
+        &self.status
+    }
 }
 
 pub struct ActionRepository {
@@ -61,6 +144,7 @@ impl ActionRepository {
         Self { pool }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn create(
         &self,
         pipeline_id: i64,
@@ -68,42 +152,115 @@ impl ActionRepository {
         container_uri: &String,
         r#type: &Type,
         status: &String,
+        runs_on: &[String],
+        needs: &[String],
+        env: &[String],
+        condition: Option<&str>,
+        retry: &RetryPolicy,
+        priority: i32,
     ) -> Result<ActionDTO, sqlx::Error> {
         // create a nex action in psql
         sqlx::query_as!(
             ActionDTO,
-            r#"INSERT INTO actions (pipeline_id, name, container_uri, type, status) VALUES ($1, $2, $3, $4, $5) RETURNING *"#,
+            r#"INSERT INTO actions (pipeline_id, name, container_uri, type, status, runs_on, needs, env, condition, max_attempts, retry_backoff, retry_on, priority) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13) RETURNING id, pipeline_id, name, container_uri, type, status, runs_on, needs, env, condition, attempt, max_attempts, retry_backoff, retry_on, priority"#,
             pipeline_id,
             name,
             container_uri,
             &r#type.to_string(),
-            status
+            status,
+            runs_on,
+            needs,
+            env,
+            condition,
+            retry.max_attempts as i32,
+            retry.backoff as i32,
+            &retry.on,
+            priority
         )
         .fetch_one(self.pool.as_ref())
         .await
     }
 
-    #[allow(dead_code)]
     pub async fn find_by_id(&self, id: i64) -> Result<ActionDTO, sqlx::Error> {
         sqlx::query_as!(
             ActionDTO,
-            r#"SELECT * FROM actions WHERE id = $1 ORDER BY id"#,
+            r#"SELECT id, pipeline_id, name, container_uri, type, status, runs_on, needs, env, condition, attempt, max_attempts, retry_backoff, retry_on, priority FROM actions WHERE id = $1 ORDER BY id"#,
             id
         )
         .fetch_one(&*self.pool)
         .await
     }
 
-    pub async fn alter_status(&self, status: &str, id: i64) -> Result<(), sqlx::Error> {
-        sqlx::query!(
-            r#"UPDATE actions SET status = $1 WHERE id = $2"#,
+    /// Set the status of an action, only if it is currently in one of the `from` statuses, and record the change.
+    /// The action starts on its first `started` status, and finishes on a `finished` one.
+    /// Return whether the action was updated.
+    pub async fn alter_status(
+        &self,
+        status: &str,
+        from: &[String],
+        started: bool,
+        finished: bool,
+        source: EventSource,
+        id: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"WITH updated AS (
+            UPDATE actions
+            SET status = $1,
+                started_at = CASE WHEN $4 THEN COALESCE(started_at, NOW()) ELSE started_at END,
+                finished_at = CASE WHEN $5 THEN NOW() ELSE finished_at END
+            WHERE id = $2 AND status = ANY($3) AND status <> $1
+            RETURNING id, attempt
+        )
+        INSERT INTO action_events (action_id, status, source, attempt) SELECT id, $1, $6, attempt FROM updated"#,
             status,
-            id
+            id,
+            from,
+            started,
+            finished,
+            source.as_str()
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Start the given attempt at running an action, only if it is currently in one of the `from` statuses, and record the change.
+    /// The action goes back to pending, and is not finished anymore. Return whether the action was updated.
+    pub async fn restart(
+        &self,
+        attempt: i32,
+        from: &[String],
+        id: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let pending = ActionStatus::Pending.as_str_name();
+        let result = sqlx::query!(
+            r#"WITH updated AS (
+            UPDATE actions
+            SET status = $1, attempt = $2
+            WHERE id = $3 AND status = ANY($4)
+            RETURNING id, attempt
+        )
+        INSERT INTO action_events (action_id, status, source, attempt) SELECT id, $1, $5, attempt FROM updated"#,
+            pending,
+            attempt,
+            id,
+            from,
+            EventSource::Controller.as_str()
         )
         .execute(&*self.pool)
         .await?;
 
-        Ok(())
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn find_status(&self, id: i64) -> Result<String, sqlx::Error> {
+        let row = sqlx::query!(r#"SELECT status FROM actions WHERE id = $1"#, id)
+            .fetch_one(&*self.pool)
+            .await?;
+
+        Ok(row.status)
     }
 
     #[allow(dead_code)]
@@ -113,7 +270,7 @@ impl ActionRepository {
     ) -> Result<Vec<ActionDTO>, sqlx::Error> {
         sqlx::query_as!(
             ActionDTO,
-            r#"SELECT * FROM actions WHERE pipeline_id = $1 ORDER BY id"#,
+            r#"SELECT id, pipeline_id, name, container_uri, type, status, runs_on, needs, env, condition, attempt, max_attempts, retry_backoff, retry_on, priority FROM actions WHERE pipeline_id = $1 ORDER BY id"#,
             pipeline_id
         )
         .fetch_all(&*self.pool)
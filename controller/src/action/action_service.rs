@@ -5,10 +5,13 @@ use sqlx::PgPool;
 use tracing::info;
 
 use crate::{
-    command::command_service::CommandService, grpc_scheduler::ActionStatus,
-    parser::pipe_parser::Type,
+    command::command_service::CommandService,
+    domain::entities::action::ActionStatus as DomainActionStatus, grpc_scheduler::ActionStatus,
+    logs::{log_hub::LogHub, log_repository::LogRepository, ActionLog},
+    parser::pipe_parser::{RetryPolicy, Type},
 };
 
+use super::action_event::{ActionEventRepository, EventSource};
 use super::action_repository::{Action, ActionRepository};
 
 #[derive(Debug)]
@@ -40,6 +43,32 @@ pub struct ActionDTO {
     pub container_uri: String,
     pub r#type: Type,
     pub status: String,
+    pub runs_on: Vec<String>,
+    pub needs: Vec<String>,
+    pub env: Vec<String>,
+    pub condition: Option<String>,
+    pub attempt: i32,
+    pub max_attempts: i32,
+    pub retry_backoff: i32,
+    pub retry_on: Vec<i32>,
+    pub priority: i32,
+}
+
+impl ActionDTO {
+    pub fn retry(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.max_attempts.max(1) as u32,
+            backoff: self.retry_backoff.max(0) as u32,
+            on: self.retry_on.clone(),
+        }
+    }
+}
+
+/// Some logs of an action, along with the offset of the next ones when there are more.
+#[derive(Debug, Serialize)]
+pub struct ActionLogsPage {
+    pub logs: Vec<ActionLog>,
+    pub next_offset: Option<i64>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -51,16 +80,69 @@ pub struct CommandDTO {
 
 pub struct ActionService {
     repository: Arc<ActionRepository>,
+    event_repository: Arc<ActionEventRepository>,
+    log_repository: Arc<LogRepository>,
     command_service: Arc<CommandService>,
+    log_hub: Arc<LogHub>,
 }
 
 impl ActionService {
-    pub fn new(pool: Arc<PgPool>, command_service: Arc<CommandService>) -> Self {
+    pub fn new(
+        pool: Arc<PgPool>,
+        command_service: Arc<CommandService>,
+        log_hub: Arc<LogHub>,
+    ) -> Self {
         let repository = Arc::new(ActionRepository::new(pool.clone()));
+        let event_repository = Arc::new(ActionEventRepository::new(pool.clone()));
+        let log_repository = Arc::new(LogRepository::new(pool.clone()));
         Self {
             repository,
+            event_repository,
+            log_repository,
             command_service,
+            log_hub,
+        }
+    }
+
+    pub async fn find(&self, id: i64) -> Result<ActionDTO, sqlx::Error> {
+        self.repository.find_by_id(id).await
+    }
+
+    /// At most `limit` logs of an action, from the `offset`-th one on, oldest first. None when there is no such action.
+    pub async fn find_logs(
+        &self,
+        id: i64,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Option<ActionLogsPage>, sqlx::Error> {
+        match self.repository.find_by_id(id).await {
+            Ok(_) => {}
+            Err(sqlx::Error::RowNotFound) => return Ok(None),
+            Err(e) => return Err(e),
         }
+        // One more log than asked tells whether there are more.
+        let mut logs = self
+            .log_repository
+            .find_range(id, offset, Some(limit + 1))
+            .await?;
+        let next_offset = match logs.len() as i64 > limit {
+            true => {
+                logs.truncate(limit as usize);
+                Some(offset + limit)
+            }
+            false => None,
+        };
+        Ok(Some(ActionLogsPage {
+            logs: (offset..)
+                .zip(logs)
+                .map(|(offset, log)| ActionLog {
+                    offset,
+                    attempt: log.attempt,
+                    message: log.message,
+                })
+                .collect(),
+            next_offset,
+        }))
     }
 
     pub async fn create(
@@ -76,6 +158,12 @@ impl ActionService {
                 &action_dto.container_uri,
                 &action_dto.r#type,
                 &action_dto.status,
+                &action_dto.runs_on,
+                &action_dto.needs,
+                &action_dto.env,
+                action_dto.condition.as_deref(),
+                &action_dto.retry(),
+                action_dto.priority,
             )
             .await
             .map_err(|e| {
@@ -83,6 +171,15 @@ impl ActionService {
                 return ActionCreationError::DatabaseInsertionError;
             })?;
 
+        self.event_repository
+            .create(
+                action_dto.id.unwrap(),
+                &action_dto.status,
+                EventSource::Controller,
+            )
+            .await
+            .map_err(|_| ActionCreationError::DatabaseInsertionError)?;
+
         for command in &commands {
             self.command_service
                 .create(action_dto.id.unwrap(), &command)
@@ -100,13 +197,78 @@ impl ActionService {
             commands,
             action_dto.r#type.clone(),
             action_dto.status.clone(),
+            action_dto.runs_on.clone(),
+            action_dto.needs.clone(),
+            action_dto.env.clone(),
+            action_dto.condition.clone(),
         )
+        .map(|action| {
+            action
+                .with_retry(action_dto.retry(), action_dto.attempt)
+                .with_priority(action_dto.priority)
+        })
         .map_err(|_| {
             return ActionCreationError::WrongTypeError;
         })
     }
 
-    pub async fn update_status(&self, id: i64, status: &ActionStatus) -> Result<(), sqlx::Error> {
-        self.repository.alter_status(status.as_str_name(), id).await
+    pub async fn find_status(&self, id: i64) -> Result<ActionStatus, sqlx::Error> {
+        let status = self.repository.find_status(id).await?;
+        // The database only holds known statuses.
+        Ok(ActionStatus::from_str_name(&status).unwrap_or(ActionStatus::Error))
+    }
+
+    /// Start the given attempt at running an action whose last attempt failed: it goes back to pending, unless it is done, e.g. cancelled.
+    /// Return whether the action was restarted.
+    pub async fn retry(&self, id: i64, attempt: i32) -> Result<bool, sqlx::Error> {
+        let from: Vec<String> = DomainActionStatus::ALL
+            .iter()
+            .filter(|status| status.can_restart())
+            .map(|status| status.as_str_name().to_string())
+            .collect();
+        self.repository.restart(attempt, &from, id).await
+    }
+
+    /// Move an action to the given status, unless its lifecycle does not allow it, e.g. once it is done.
+    /// Every change is recorded in the action's history, along with where it comes from. Once the action is done, the followers of its logs are let go.
+    /// Return whether the action is in the given status.
+    pub async fn update_status(
+        &self,
+        id: i64,
+        status: &ActionStatus,
+        source: EventSource,
+    ) -> Result<bool, sqlx::Error> {
+        let reported = *status;
+        let status = DomainActionStatus::from(*status);
+        let from: Vec<String> = status
+            .predecessors()
+            .iter()
+            .map(|predecessor| predecessor.as_str_name().to_string())
+            .collect();
+        let started = matches!(
+            status,
+            DomainActionStatus::Scheduled | DomainActionStatus::Running
+        );
+        let updated = self
+            .repository
+            .alter_status(
+                status.as_str_name(),
+                &from,
+                started,
+                status.is_terminal(),
+                source,
+                id,
+            )
+            .await?;
+        if updated {
+            if status.is_terminal() {
+                self.log_hub.finish(id, reported);
+            }
+            return Ok(true);
+        }
+
+        // Either the action already was in that status, or it cannot move to it.
+        let current = self.repository.find_status(id).await?;
+        Ok(current == status.as_str_name())
     }
 }
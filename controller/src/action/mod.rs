@@ -1,2 +1,4 @@
+pub mod action_controller;
+pub mod action_event;
 pub mod action_repository;
-pub mod action_service;
\ No newline at end of file
+pub mod action_service;
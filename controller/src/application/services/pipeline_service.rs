@@ -63,7 +63,7 @@ impl PipelineService for PipelineServiceImpl {
                     action_name.to_owned(),
                     action_data.configuration.container.clone(),
                     ActionType::Container,
-                    ActionStatus::Pending.to_string(),
+                    ActionStatus::Pending.as_str_name().to_string(),
                     Some(action_data.commands.clone()),
                 )
                 .await;
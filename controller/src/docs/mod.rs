@@ -1,6 +1,9 @@
 use actix_web::{get, HttpResponse, Responder};
 use scalar_doc::scalar_actix::ActixDocumentation;
 
+/// JSON Schema of the pipeline manifests, for editors to complete and lint them.
+pub const PIPELINE_SCHEMA: &str = include_str!("../../../api/schema/pipeline.schema.json");
+
 #[get("/openapi")]
 pub async fn openapi() -> impl Responder {
     let open = include_str!("../../../api/openapi/controller/controller.openapi.yaml");
@@ -13,3 +16,10 @@ pub async fn doc() -> impl Responder {
         .theme(scalar_doc::Theme::Kepler)
         .service()
 }
+
+#[get("/schema/pipeline.json")]
+pub async fn pipeline_schema() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("application/schema+json")
+        .body(PIPELINE_SCHEMA)
+}
@@ -35,21 +35,94 @@ impl From<String> for ActionType {
     }
 }
 
+/// Lifecycle of an action, the same as in the Scheduler and Agent protos: `Pending`, then `Scheduled`, then `Running`, then one of the terminal statuses.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Copy, Eq)]
 pub enum ActionStatus {
     Pending,
+    Scheduled,
     Running,
     Completed,
     Error,
+    Cancelled,
+    TimedOut,
+    Skipped,
+}
+
+impl ActionStatus {
+    pub const ALL: [ActionStatus; 8] = [
+        ActionStatus::Pending,
+        ActionStatus::Scheduled,
+        ActionStatus::Running,
+        ActionStatus::Completed,
+        ActionStatus::Error,
+        ActionStatus::Cancelled,
+        ActionStatus::TimedOut,
+        ActionStatus::Skipped,
+    ];
+
+    /// Name under which the status is stored and served, the one of the proto enum.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            ActionStatus::Pending => "ACTION_STATUS_PENDING",
+            ActionStatus::Scheduled => "ACTION_STATUS_SCHEDULED",
+            ActionStatus::Running => "ACTION_STATUS_RUNNING",
+            ActionStatus::Completed => "ACTION_STATUS_COMPLETED",
+            ActionStatus::Error => "ACTION_STATUS_ERROR",
+            ActionStatus::Cancelled => "ACTION_STATUS_CANCELLED",
+            ActionStatus::TimedOut => "ACTION_STATUS_TIMED_OUT",
+            ActionStatus::Skipped => "ACTION_STATUS_SKIPPED",
+        }
+    }
+
+    /// Whether the action is done: it never changes status again.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            ActionStatus::Completed
+                | ActionStatus::Error
+                | ActionStatus::Cancelled
+                | ActionStatus::TimedOut
+                | ActionStatus::Skipped
+        )
+    }
+
+    /// Whether an action can go from this status to `next`. Staying in the same status is always allowed,
+    /// and a scheduled action goes back to pending when the Scheduler dispatches it again.
+    pub fn can_transition_to(&self, next: ActionStatus) -> bool {
+        match (self, next) {
+            (current, next) if *current == next => true,
+            (ActionStatus::Pending, ActionStatus::Scheduled | ActionStatus::Running) => true,
+            (ActionStatus::Scheduled, ActionStatus::Pending | ActionStatus::Running) => true,
+            (current, next) => !current.is_terminal() && next.is_terminal(),
+        }
+    }
+
+    /// The statuses an action can be in to move to this one.
+    pub fn predecessors(&self) -> Vec<ActionStatus> {
+        ActionStatus::ALL
+            .into_iter()
+            .filter(|status| status.can_transition_to(*self))
+            .collect()
+    }
+
+    /// Whether an action in this status can go back to `Pending` for its next attempt. Only an action that is not done can:
+    /// the failure of an attempt is only stored once the action is not retried anymore.
+    pub fn can_restart(&self) -> bool {
+        !self.is_terminal()
+    }
 }
 
 impl fmt::Display for ActionStatus {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let s = match self {
             ActionStatus::Pending => "Pending",
-            ActionStatus::Running => "Scheduled",
+            ActionStatus::Scheduled => "Scheduled",
+            ActionStatus::Running => "Running",
             ActionStatus::Completed => "Completed",
             ActionStatus::Error => "Error",
+            ActionStatus::Cancelled => "Cancelled",
+            ActionStatus::TimedOut => "TimedOut",
+            ActionStatus::Skipped => "Skipped",
         };
 
         write!(f, "{}", s)
@@ -59,15 +132,12 @@ impl fmt::Display for ActionStatus {
 impl FromStr for ActionStatus {
     type Err = ();
 
+    /// Parse either the stored name of a status or its display name.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "Pending" => Ok(ActionStatus::Pending),
-            "Scheduled" => Ok(ActionStatus::Running),
-            "Running" => Ok(ActionStatus::Running),
-            "Completed" => Ok(ActionStatus::Completed),
-            "Error" => Ok(ActionStatus::Error),
-            _ => Err(()),
-        }
+        ActionStatus::ALL
+            .into_iter()
+            .find(|status| status.as_str_name() == s || status.to_string() == s)
+            .ok_or(())
     }
 }
 
@@ -83,6 +153,9 @@ pub struct ActionRequest {
     pub commands: Vec<String>,
     pub context: ExecutionContext,
     pub repo_url: String,
+    pub priority: u32, // Higher priority actions of a repository are dispatched first
+    pub runs_on: Vec<String>,
+    pub env: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -150,6 +223,8 @@ pub enum ActionError {
     InvalidStatus(String),
     #[error("Invalid input: {0}")]
     InvalidType(String),
+    #[error("Invalid transition: from {0} to {1}")]
+    InvalidTransition(ActionStatus, ActionStatus),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use std::sync::Arc;
+use std::{fmt, io};
+
+use sha2::{Digest, Sha256};
+use tokio::process::Command;
+use tokio::sync::Mutex;
+
+/// Where the manifest of a pipeline is, from the root of its repository.
+pub const MANIFEST_PATH: &str = ".sealci.yml";
+
+#[derive(Debug)]
+pub enum GitError {
+    Command(String), // git failed, e.g. the repository could not be cloned
+    InvalidRef(String),
+    UnknownRef(String),
+    MissingManifest(String), // The commit has no manifest
+    Io(io::Error),
+}
+
+impl fmt::Display for GitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GitError::Command(e) => write!(f, "git failed: {}", e),
+            GitError::InvalidRef(git_ref) => write!(f, "Invalid ref `{}`", git_ref),
+            GitError::UnknownRef(git_ref) => write!(f, "Unknown ref `{}`", git_ref),
+            GitError::MissingManifest(commit) => {
+                write!(f, "No {} at commit {}", MANIFEST_PATH, commit)
+            }
+            GitError::Io(e) => write!(f, "Repository cache error: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for GitError {
+    fn from(e: io::Error) -> Self {
+        GitError::Io(e)
+    }
+}
+
+/// The manifest of a repository at a given revision.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchedManifest {
+    pub commit: String, // Full sha of the commit the ref pointed to
+    pub content: String,
+}
+
+/// Bare mirrors of the repositories pipelines run on, kept between pipelines so that only new commits are fetched.
+pub struct RepositoryCache {
+    dir: PathBuf,
+    locks: Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>,
+}
+
+impl RepositoryCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            locks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Read the manifest of a repository at a ref: a branch, a tag or a commit sha.
+    /// The repository is cloned on its first pipeline, and fetched on the next ones.
+    pub async fn read_manifest(
+        &self,
+        repo_url: &str,
+        git_ref: &str,
+    ) -> Result<FetchedManifest, GitError> {
+        // A ref starting with `-` would be read as an option by git.
+        if git_ref.is_empty() || git_ref.starts_with('-') {
+            return Err(GitError::InvalidRef(git_ref.to_string()));
+        }
+        let path = self.path(repo_url);
+        // Two pipelines of the same repository must not fetch it at once.
+        let lock = self
+            .locks
+            .lock()
+            .await
+            .entry(path.clone())
+            .or_default()
+            .clone();
+        let _guard = lock.lock().await;
+
+        self.update(repo_url, &path, git_ref).await?;
+        let commit = resolve(&path, git_ref)
+            .await?
+            .ok_or_else(|| GitError::UnknownRef(git_ref.to_string()))?;
+        let output = git(&path)
+            .arg("show")
+            .arg(format!("{}:{}", commit, MANIFEST_PATH))
+            .output()
+            .await?;
+        if !output.status.success() {
+            return Err(GitError::MissingManifest(commit));
+        }
+        let content = String::from_utf8(output.stdout)
+            .map_err(|_| GitError::MissingManifest(commit.clone()))?;
+        Ok(FetchedManifest { commit, content })
+    }
+
+    /// Clone the repository, or fetch its new commits. A commit sha already in the cache does not need any fetch.
+    async fn update(&self, repo_url: &str, path: &Path, git_ref: &str) -> Result<(), GitError> {
+        if !path.exists() {
+            tokio::fs::create_dir_all(&self.dir).await?;
+            let output = Command::new("git")
+                .env("GIT_TERMINAL_PROMPT", "0")
+                .args(["clone", "--mirror", "--quiet", "--"])
+                .arg(repo_url)
+                .arg(path)
+                .output()
+                .await?;
+            return check(output);
+        }
+        if is_commit_sha(git_ref) && resolve(path, git_ref).await?.is_some() {
+            return Ok(());
+        }
+        let output = git(path)
+            .args(["fetch", "--prune", "--quiet", "origin"])
+            .output()
+            .await?;
+        check(output)
+    }
+
+    /// Each repository has its own mirror, named after the hash of its URL.
+    fn path(&self, repo_url: &str) -> PathBuf {
+        self.dir
+            .join(format!("{}.git", hex::encode(Sha256::digest(repo_url))))
+    }
+}
+
+/// The sha of the commit a ref points to, if any.
+async fn resolve(path: &Path, git_ref: &str) -> Result<Option<String>, GitError> {
+    let output = git(path)
+        .args(["rev-parse", "--verify", "--quiet"])
+        .arg(format!("{}^{{commit}}", git_ref))
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    Ok(Some(
+        String::from_utf8_lossy(&output.stdout).trim().to_string(),
+    ))
+}
+
+fn git(path: &Path) -> Command {
+    let mut command = Command::new("git");
+    command.env("GIT_TERMINAL_PROMPT", "0").arg("-C").arg(path);
+    command
+}
+
+fn check(output: Output) -> Result<(), GitError> {
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(GitError::Command(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        )),
+    }
+}
+
+fn is_commit_sha(git_ref: &str) -> bool {
+    git_ref.len() == 40 && git_ref.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// The hash a manifest is stored with, to tell which runs used the same one.
+pub fn manifest_hash(manifest: &str) -> String {
+    hex::encode(Sha256::digest(manifest))
+}
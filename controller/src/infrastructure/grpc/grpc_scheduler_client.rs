@@ -8,7 +8,7 @@ use crate::domain::entities::action::{
 use crate::domain::services::scheduler_client::SchedulerClient;
 use crate::grpc_scheduler::controller_client::ControllerClient;
 use crate::grpc_scheduler::{
-    ActionRequest, ActionResponse, ActionResult, ExecutionContext, RunnerType,
+    ActionRequest, ActionResponse, ActionResult, ActionStatus, ExecutionContext,
 };
 use futures::lock::Mutex;
 use futures::{Stream, StreamExt};
@@ -29,20 +29,41 @@ impl From<ActionResponse> for DomainActionResponse {
 impl From<ActionResult> for DomainActionResult {
     fn from(grpc_result: ActionResult) -> Self {
         DomainActionResult {
-            completion: DomainActionStatus::from_i32(grpc_result.completion),
+            // An unknown status is considered an error.
+            completion: ActionStatus::try_from(grpc_result.completion)
+                .map(DomainActionStatus::from)
+                .unwrap_or(DomainActionStatus::Error),
             exit_code: grpc_result.exit_code,
         }
     }
 }
 
-impl DomainActionStatus {
-    pub fn from_i32(value: i32) -> DomainActionStatus {
-        match value {
-            0 => DomainActionStatus::Pending,
-            1 => DomainActionStatus::Running,
-            2 => DomainActionStatus::Completed,
-            3 => DomainActionStatus::Error,
-            _ => DomainActionStatus::Error, // Valeur par défaut en cas d'invalidité
+impl From<ActionStatus> for DomainActionStatus {
+    fn from(status: ActionStatus) -> Self {
+        match status {
+            ActionStatus::Pending => DomainActionStatus::Pending,
+            ActionStatus::Scheduled => DomainActionStatus::Scheduled,
+            ActionStatus::Running => DomainActionStatus::Running,
+            ActionStatus::Completed => DomainActionStatus::Completed,
+            ActionStatus::Error => DomainActionStatus::Error,
+            ActionStatus::Cancelled => DomainActionStatus::Cancelled,
+            ActionStatus::TimedOut => DomainActionStatus::TimedOut,
+            ActionStatus::Skipped => DomainActionStatus::Skipped,
+        }
+    }
+}
+
+impl From<DomainActionStatus> for ActionStatus {
+    fn from(status: DomainActionStatus) -> Self {
+        match status {
+            DomainActionStatus::Pending => ActionStatus::Pending,
+            DomainActionStatus::Scheduled => ActionStatus::Scheduled,
+            DomainActionStatus::Running => ActionStatus::Running,
+            DomainActionStatus::Completed => ActionStatus::Completed,
+            DomainActionStatus::Error => ActionStatus::Error,
+            DomainActionStatus::Cancelled => ActionStatus::Cancelled,
+            DomainActionStatus::TimedOut => ActionStatus::TimedOut,
+            DomainActionStatus::Skipped => ActionStatus::Skipped,
         }
     }
 }
@@ -52,11 +73,15 @@ impl From<DomainActionRequest> for ActionRequest {
         ActionRequest {
             action_id: domain_request.action_id,
             context: Some(ExecutionContext {
-                r#type: RunnerType::Docker as i32,
-                container_image: domain_request.context.container_image.clone(),
+                r#type: domain_request.context.r#type,
+                container_image: domain_request.context.container_image,
             }),
-            commands: domain_request.commands.clone(),
-            repo_url: domain_request.repo_url.clone(),
+            commands: domain_request.commands,
+            repo_url: domain_request.repo_url,
+            priority: domain_request.priority,
+            tenant: String::new(), // The scheduler shares its agents by repository
+            runs_on: domain_request.runs_on,
+            env: domain_request.env,
         }
     }
 }
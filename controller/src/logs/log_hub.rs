@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+
+use crate::grpc_scheduler::ActionStatus;
+
+use super::Log;
+
+/// Number of events a subscriber can fall behind before it misses some.
+const CHANNEL_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone)]
+pub enum LogHubEvent {
+    Log(Log),
+    Done(ActionStatus), // The action is done, after its last attempt: no log follows
+}
+
+/// What the log stream of an action sends: its logs, then its status once it is done.
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum LogStreamEvent {
+    Log {
+        offset: i64, // Position of the log among the ones of the action, from 0
+        attempt: i32,
+        message: String,
+    },
+    End {
+        status: String,
+    },
+}
+
+type Channels = Arc<Mutex<HashMap<i64, broadcast::Sender<LogHubEvent>>>>;
+
+/// Passes the logs of the running actions, as they are received from the scheduler, to whoever follows them.
+/// Each action has its own channel, which only lives while someone follows the action.
+#[derive(Default)]
+pub struct LogHub {
+    channels: Channels,
+}
+
+impl LogHub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Follow the logs of an action, from now on.
+    pub fn subscribe(&self, action_id: i64) -> LogSubscription {
+        let mut channels = self.channels.lock().unwrap();
+        let receiver = channels
+            .entry(action_id)
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe();
+        LogSubscription {
+            action_id,
+            receiver: Some(receiver),
+            channels: self.channels.clone(),
+        }
+    }
+
+    /// Whether someone follows the logs of an action.
+    pub fn is_followed(&self, action_id: i64) -> bool {
+        self.channels.lock().unwrap().contains_key(&action_id)
+    }
+
+    /// Pass a stored log of an action to its followers, if any.
+    pub fn publish(&self, action_id: i64, log: &Log) {
+        self.send(action_id, LogHubEvent::Log(log.clone()), false);
+    }
+
+    /// Tell the followers of an action that it is done, and let them go. A retried action is only done after its last attempt.
+    pub fn finish(&self, action_id: i64, status: ActionStatus) {
+        self.send(action_id, LogHubEvent::Done(status), true);
+    }
+
+    fn send(&self, action_id: i64, event: LogHubEvent, close: bool) {
+        let mut channels = self.channels.lock().unwrap();
+        let Some(sender) = channels.get(&action_id) else {
+            return;
+        };
+        // Sending only fails once every follower is gone.
+        if sender.send(event).is_err() || close {
+            channels.remove(&action_id);
+        }
+    }
+}
+
+/// A follower of the logs of an action. The channel of the action goes away with its last follower,
+/// e.g. when a client disconnects or the action was already done, even if nothing is sent for the action anymore.
+pub struct LogSubscription {
+    action_id: i64,
+    receiver: Option<broadcast::Receiver<LogHubEvent>>, // Only taken when the subscription is dropped
+    channels: Channels,
+}
+
+impl LogSubscription {
+    pub async fn recv(&mut self) -> Result<LogHubEvent, RecvError> {
+        match &mut self.receiver {
+            Some(receiver) => receiver.recv().await,
+            None => Err(RecvError::Closed),
+        }
+    }
+
+    pub fn try_recv(&mut self) -> Result<LogHubEvent, TryRecvError> {
+        match &mut self.receiver {
+            Some(receiver) => receiver.try_recv(),
+            None => Err(TryRecvError::Closed),
+        }
+    }
+}
+
+impl Drop for LogSubscription {
+    fn drop(&mut self) {
+        drop(self.receiver.take());
+        let mut channels = self.channels.lock().unwrap();
+        if channels
+            .get(&self.action_id)
+            .is_some_and(|sender| sender.receiver_count() == 0)
+        {
+            channels.remove(&self.action_id);
+        }
+    }
+}
@@ -6,11 +6,11 @@ use super::Log;
 
 #[derive(Debug, Clone)]
 pub struct LogDTO {
-    #[allow(dead_code)]
     pub id: Option<i64>,
     #[allow(dead_code)]
     pub action_id: i64,
     pub data: String,
+    pub attempt: i32,
 }
 
 pub struct LogRepository {
@@ -22,30 +22,69 @@ impl LogRepository {
         Self { pool }
     }
 
-    pub async fn create(&self, action_id: i64, data: &String) -> Result<Log, sqlx::Error> {
+    /// Store a log of the given attempt at running an action.
+    pub async fn create(
+        &self,
+        action_id: i64,
+        attempt: i32,
+        data: &String,
+    ) -> Result<Log, sqlx::Error> {
         let log_row = sqlx::query_as!(
             LogDTO,
-            r#"INSERT INTO logs (action_id, data) VALUES ($1, $2) RETURNING *"#,
+            r#"INSERT INTO logs (action_id, data, attempt) VALUES ($1, $2, $3) RETURNING id, action_id, data, attempt"#,
             action_id,
-            data
+            data,
+            attempt
         )
         .fetch_one(self.pool.as_ref())
         .await?;
 
         Ok(Log {
+            id: log_row.id.unwrap_or_default(),
+            attempt: log_row.attempt,
             message: log_row.data,
         })
     }
 
+    /// The logs of every attempt at running an action, oldest first.
     pub async fn find_by_action_id(&self, action_id: i64) -> Result<Vec<Log>, sqlx::Error> {
+        self.find_range(action_id, 0, None).await
+    }
+
+    pub async fn count_by_action_id(&self, action_id: i64) -> Result<i64, sqlx::Error> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count!" FROM logs WHERE action_id = $1"#,
+            action_id
+        )
+        .fetch_one(self.pool.as_ref())
+        .await?;
+        Ok(count)
+    }
+
+    /// The logs of an action from the `offset`-th one on, oldest first, at most `limit` of them when given.
+    pub async fn find_range(
+        &self,
+        action_id: i64,
+        offset: i64,
+        limit: Option<i64>,
+    ) -> Result<Vec<Log>, sqlx::Error> {
         let logs = sqlx::query_as!(
             LogDTO,
-            r#"SELECT * FROM logs WHERE action_id = $1"#,
-            action_id
+            r#"SELECT id, action_id, data, attempt FROM logs WHERE action_id = $1 ORDER BY attempt, id OFFSET $2 LIMIT $3"#,
+            action_id,
+            offset,
+            limit
         )
         .fetch_all(self.pool.as_ref())
         .await?;
 
-        Ok(logs.into_iter().map(|log| Log { message: log.data }).collect())
+        Ok(logs
+            .into_iter()
+            .map(|log| Log {
+                id: log.id.unwrap_or_default(),
+                attempt: log.attempt,
+                message: log.data,
+            })
+            .collect())
     }
 }
@@ -1,5 +1,19 @@
+pub mod log_hub;
 pub mod log_repository;
 
+use serde::Serialize;
+
+#[derive(Debug, Clone)]
 pub struct Log {
+    pub id: i64,
+    pub attempt: i32,
+    pub message: String,
+}
+
+/// A log of an action, along with its position among the logs of the action.
+#[derive(Debug, Clone, Serialize)]
+pub struct ActionLog {
+    pub offset: i64, // From 0
+    pub attempt: i32,
     pub message: String,
 }
@@ -1,6 +1,7 @@
-use action::action_service::ActionService;
+use action::{action_controller, action_service::ActionService};
 use clap::Parser;
 use command::command_service::CommandService;
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
@@ -8,8 +9,16 @@ use crate::database::database::Database;
 use actix_cors::Cors;
 use actix_web::{web::Data, App, HttpServer};
 use dotenv::dotenv;
-use parser::pipe_parser::PipeParser;
+use git::repository_cache::RepositoryCache;
+use logs::log_hub::LogHub;
+use parser::{
+    format::ManifestFormat,
+    json_parser::JsonParser,
+    pipe_parser::{ManifestParser, PipeParser},
+    toml_parser::TomlParser,
+};
 use pipeline::pipeline_controller;
+use template::{template_controller, template_store::TemplateStore};
 use tracing::info;
 
 pub mod grpc_scheduler {
@@ -21,12 +30,14 @@ mod command;
 mod database;
 mod docs;
 mod domain;
+mod git;
 mod health;
 mod infrastructure;
 mod logs;
 pub mod parser;
 mod pipeline;
 pub mod scheduler;
+mod template;
 mod tests;
 
 #[derive(Parser, Debug)]
@@ -39,6 +50,18 @@ struct Args {
 
     #[clap(env, long)]
     pub grpc: String,
+
+    /// Directory of the templates pipelines can include.
+    #[clap(env, long, default_value = "./templates")]
+    pub templates_dir: String,
+
+    /// Reject manifests having keys the parser does not know, rather than only warning about them.
+    #[clap(env, long)]
+    pub strict_manifests: bool,
+
+    /// Directory where the repositories are cloned to read their manifest.
+    #[clap(env, long, default_value = "./repositories")]
+    pub repositories_dir: String,
 }
 
 #[actix_web::main]
@@ -63,25 +86,40 @@ async fn main() -> std::io::Result<()> {
     ));
 
     let command_service = Arc::new(CommandService::new(Arc::clone(&pool)));
+    let log_hub = Arc::new(LogHub::new());
 
     let action_service = Arc::new(ActionService::new(
         Arc::clone(&pool),
         Arc::clone(&command_service),
+        Arc::clone(&log_hub),
     ));
 
     let scheduler_service = Arc::new(scheduler::SchedulerService::new(
         client.clone(),
         Arc::new(logs::log_repository::LogRepository::new(Arc::clone(&pool))),
         Arc::clone(&action_service),
+        Arc::clone(&log_hub),
     ));
 
-    let parser_service = Arc::new(PipeParser {});
+    let strict = args.strict_manifests;
+    let parsers: HashMap<ManifestFormat, Arc<dyn ManifestParser>> = HashMap::from([
+        (
+            ManifestFormat::Yaml,
+            Arc::new(PipeParser { strict }) as Arc<dyn ManifestParser>,
+        ),
+        (ManifestFormat::Json, Arc::new(JsonParser { strict })),
+        (ManifestFormat::Toml, Arc::new(TomlParser { strict })),
+    ]);
+    let template_store = Arc::new(TemplateStore::new(&args.templates_dir));
 
     let pipeline_service = Arc::new(pipeline::pipeline_service::PipelineService::new(
         scheduler_service.clone(),
-        parser_service.clone(),
+        parsers,
         Arc::clone(&pool),
         Arc::clone(&action_service),
+        template_store.clone(),
+        Arc::new(RepositoryCache::new(&args.repositories_dir)),
+        Arc::clone(&log_hub),
     ));
 
     info!("Listenning on {}", addr_in);
@@ -98,11 +136,21 @@ async fn main() -> std::io::Result<()> {
             .wrap(actix_web::middleware::Logger::default())
             .app_data(Data::new(pipeline_service.clone())) // TODO: replace this implementation by the real parser
             .app_data(Data::new(Arc::clone(&action_service)))
+            .app_data(Data::new(template_store.clone()))
             .service(pipeline_controller::create_pipeline)
+            .service(pipeline_controller::resolve_pipeline)
+            .service(pipeline_controller::validate_pipeline)
             .service(pipeline_controller::get_pipelines)
             .service(pipeline_controller::get_pipeline)
+            .service(pipeline_controller::stream_action_logs)
+            .service(action_controller::get_action_logs)
+            .service(template_controller::get_templates)
+            .service(template_controller::get_template)
+            .service(template_controller::put_template)
+            .service(template_controller::delete_template)
             .service(docs::doc)
             .service(docs::openapi)
+            .service(docs::pipeline_schema)
             .route(
                 "/health",
                 actix_web::web::get().to(health::handlers::health_check),
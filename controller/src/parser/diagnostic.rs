@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::Marker;
+use yaml_rust::ScanError;
+
+use crate::parser::pipe_parser::ParsingError;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,   // The manifest cannot be run
+    Warning, // The manifest can be run, but likely not the way it was meant to
+}
+
+/// A problem found in a manifest, located in its text.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: String, // The `ParsingError` variant for errors, e.g. `MissingCommands`
+    pub message: String,
+    pub path: String, // Yaml path of the faulty node, e.g. `actions.build.commands[1]`, empty for the whole manifest
+    pub line: Option<usize>, // From 1, when the node is in the manifest rather than in a template
+    pub column: Option<usize>, // From 1
+    pub suggestion: Option<String>,
+}
+
+/// Collects the diagnostics of a manifest while it is checked, along with the errors behind them.
+pub struct Diagnostics {
+    positions: HashMap<String, Marker>,
+    errors: Vec<ParsingError>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn new(yaml: &str) -> Self {
+        Self {
+            positions: index_positions(yaml),
+            errors: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Diagnostics of a manifest whose nodes cannot be located, e.g. a TOML one.
+    pub fn without_positions() -> Self {
+        Self {
+            positions: HashMap::new(),
+            errors: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Keep the value of a check, or report its error at the given path.
+    pub fn check<T>(&mut self, result: Result<T, ParsingError>, path: &str) -> Option<T> {
+        result.map_err(|error| self.error(error, path)).ok()
+    }
+
+    pub fn error(&mut self, error: ParsingError, path: &str) {
+        let suggestion = suggestion(&error);
+        self.error_with(error, path, suggestion);
+    }
+
+    pub fn error_with(&mut self, error: ParsingError, path: &str, suggestion: Option<String>) {
+        let (line, column) = self.locate(path);
+        self.push_error(error, path, line, column, suggestion);
+    }
+
+    /// Report an error found in the text rather than in a node, e.g. at a given line.
+    pub fn error_at(&mut self, error: ParsingError, line: usize, column: usize) {
+        let suggestion = suggestion(&error);
+        self.push_error(error, "", Some(line), Some(column), suggestion);
+    }
+
+    /// Report a manifest that is not yaml at all, where its scanner gave up.
+    pub fn syntax_error(&mut self, error: &ScanError) {
+        let marker = error.marker();
+        self.syntax_error_at(error.to_string(), marker.line(), marker.col() + 1);
+    }
+
+    /// Report a manifest that cannot be read in its format, where its reader gave up.
+    pub fn syntax_error_at(&mut self, message: String, line: usize, column: usize) {
+        self.diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            code: code(&ParsingError::YamlNotCompliant),
+            message,
+            path: String::new(),
+            line: Some(line),
+            column: Some(column),
+            suggestion: None,
+        });
+        self.errors.push(ParsingError::YamlNotCompliant);
+    }
+
+    pub fn warning(&mut self, code: &str, message: String, path: &str, suggestion: Option<String>) {
+        let (line, column) = self.locate(path);
+        self.diagnostics.push(Diagnostic {
+            severity: Severity::Warning,
+            code: code.to_string(),
+            message,
+            path: path.to_string(),
+            line,
+            column,
+            suggestion,
+        });
+    }
+
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
+    /// The first error found, in the order of the manifest.
+    pub fn into_first_error(self) -> Option<ParsingError> {
+        self.errors.into_iter().next()
+    }
+
+    pub fn into_diagnostics(self) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+
+    fn push_error(
+        &mut self,
+        error: ParsingError,
+        path: &str,
+        line: Option<usize>,
+        column: Option<usize>,
+        suggestion: Option<String>,
+    ) {
+        self.diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            code: code(&error),
+            message: error.to_string(),
+            path: path.to_string(),
+            line,
+            column,
+            suggestion,
+        });
+        self.errors.push(error);
+    }
+
+    /// Where a node starts in the manifest. A node coming from a template is located at its closest ancestor in the manifest, if any.
+    fn locate(&self, path: &str) -> (Option<usize>, Option<usize>) {
+        let mut path = path;
+        loop {
+            if let Some(marker) = self.positions.get(path) {
+                return (Some(marker.line()), Some(marker.col() + 1));
+            }
+            match path.rfind(['.', '[']) {
+                Some(end) => path = &path[..end],
+                None => return (None, None),
+            }
+        }
+    }
+}
+
+/// The name of the variant of an error, e.g. `MissingCommands`.
+fn code(error: &ParsingError) -> String {
+    let debug = format!("{:?}", error);
+    debug
+        .split(|c: char| !c.is_alphanumeric())
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// How to fix the manifest, when there is a usual way to.
+fn suggestion(error: &ParsingError) -> Option<String> {
+    let suggestion = match error {
+        ParsingError::InconsistentCommandIndentation => "indent all the commands of the action the same way",
+        ParsingError::InvalidActionName => "only use letters, digits, spaces, `&`, `-` and `_` in action names",
+        ParsingError::MissingName => "add a `name` to the pipeline",
+        ParsingError::MissingActions => "add an `actions` mapping, with at least one action",
+        ParsingError::MissingConfiguration => "add a `configuration` with the `container` image to run the commands in",
+        ParsingError::MissingCommands => "add a `commands` list with at least one command",
+        ParsingError::InvalidRunsOn => "use a label, or a list of labels, e.g. `runs_on: [linux, gpu]`",
+        ParsingError::InvalidNeeds => "use an action name, or a list of action names",
+        ParsingError::CyclicNeeds(_) => "remove one of the needs of the cycle",
+        ParsingError::InvalidMaxParallelism => "use a number of actions greater than 0",
+        ParsingError::InvalidPriority => "use a number, the higher the sooner the actions are dispatched, e.g. `priority: 10`",
+        ParsingError::InvalidEnv => "map each variable name to a value, e.g. `RUST_LOG: debug`",
+        ParsingError::InvalidMatrix => "map each key to a non-empty list of values, without including a combination twice",
+        ParsingError::InvalidTrigger => "use an event, a list of events, or a mapping of `events`, `branches`, `tags` and `paths`",
+        ParsingError::InvalidCondition => "use an expression, e.g. `if: ${{ branch == 'main' }}`",
+        ParsingError::InvalidRetry => "use a number of attempts, or a mapping of `max_attempts`, `backoff` and `on`",
+        ParsingError::UnknownVariable(_) => "use one of `event`, `branch`, `tag` and `changed_paths`",
+        ParsingError::UnknownFunction(_) => {
+            "use one of `success`, `failure`, `always`, `contains`, `startsWith`, `endsWith` and `changed`"
+        }
+        ParsingError::InvalidInclude => "use a template name, or a list of template names",
+        ParsingError::UnknownTemplate(_) => "check the name of the template, `GET /template` lists them",
+        ParsingError::CyclicIncludes(_) | ParsingError::CyclicExtends(_) => "remove one of the steps of the cycle",
+        ParsingError::InvalidExtends => "use an action name, or a list of action names",
+        _ => return None,
+    };
+    Some(suggestion.to_string())
+}
+
+/// The line and column of a byte offset in a text, both from 1.
+pub fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = text.get(..offset).unwrap_or(text);
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// The candidate closest to a misspelled name, if it is close enough to be what was meant.
+pub fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, candidate)| *distance <= candidate.len().div_ceil(3))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Where each node of a manifest starts, by yaml path. A mapping entry is located at its key.
+fn index_positions(yaml: &str) -> HashMap<String, Marker> {
+    let mut indexer = PositionIndexer::default();
+    // Whatever comes before a syntax error is still indexed.
+    let _ = Parser::new(yaml.chars()).load(&mut indexer, false);
+    indexer.positions
+}
+
+enum Frame {
+    Mapping { key: Option<String> }, // The key of the entry being read, None while reading a key
+    Sequence { index: usize },
+}
+
+#[derive(Default)]
+struct PositionIndexer {
+    frames: Vec<Frame>,
+    positions: HashMap<String, Marker>,
+}
+
+impl PositionIndexer {
+    fn path(&self) -> String {
+        let mut path = String::new();
+        for frame in &self.frames {
+            match frame {
+                Frame::Mapping { key: Some(key) } if path.is_empty() => path.push_str(key),
+                Frame::Mapping { key: Some(key) } => {
+                    path.push('.');
+                    path.push_str(key);
+                }
+                Frame::Mapping { key: None } => {}
+                Frame::Sequence { index } => path.push_str(&format!("[{}]", index)),
+            }
+        }
+        path
+    }
+
+    fn record(&mut self, mark: Marker) {
+        let path = self.path();
+        self.positions.entry(path).or_insert(mark);
+    }
+
+    /// Move on to the next entry of the enclosing mapping or sequence, once a value is read.
+    fn advance(&mut self) {
+        match self.frames.last_mut() {
+            Some(Frame::Mapping { key }) => *key = None,
+            Some(Frame::Sequence { index }) => *index += 1,
+            None => {}
+        }
+    }
+}
+
+impl MarkedEventReceiver for PositionIndexer {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::Scalar(..)
+            | Event::Alias(_)
+            | Event::MappingStart(_)
+            | Event::SequenceStart(_) => {
+                let is_key = matches!(self.frames.last(), Some(Frame::Mapping { key: None }));
+                if is_key {
+                    // Manifests only have scalar keys, any other key is indexed as `?`.
+                    let key = match &event {
+                        Event::Scalar(value, ..) => value.clone(),
+                        _ => String::from("?"),
+                    };
+                    if let Some(Frame::Mapping { key: slot }) = self.frames.last_mut() {
+                        *slot = Some(key);
+                    }
+                    self.record(mark);
+                } else if matches!(self.frames.last(), Some(Frame::Sequence { .. })) {
+                    self.record(mark);
+                }
+                match event {
+                    Event::MappingStart(_) => self.frames.push(Frame::Mapping { key: None }),
+                    Event::SequenceStart(_) => self.frames.push(Frame::Sequence { index: 0 }),
+                    _ if is_key => {}
+                    _ => self.advance(),
+                }
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.frames.pop();
+                self.advance();
+            }
+            _ => {}
+        }
+    }
+}
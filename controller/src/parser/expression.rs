@@ -0,0 +1,405 @@
+use crate::parser::{
+    pipe_parser::ParsingError,
+    trigger::{glob_matches, Trigger},
+};
+
+/// An `if:` expression of an action, e.g. `branch == 'main' && changed('src/**')` or `failure()`.
+///
+/// Expressions compare strings and booleans with `==` and `!=`, and combine them with `&&`, `||` and `!`.
+/// They may be written as is, or wrapped in `${{ }}`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Literal(Value),
+    Variable(Variable),
+    Not(Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+    Equal(Box<Expression>, Box<Expression>),
+    NotEqual(Box<Expression>, Box<Expression>),
+    Call(Function, Vec<Expression>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    String(String),
+    List(Vec<String>),
+}
+
+impl Value {
+    fn is_truthy(&self) -> bool {
+        match self {
+            Value::Null => false,
+            Value::Bool(value) => *value,
+            Value::String(value) => !value.is_empty(),
+            Value::List(values) => !values.is_empty(),
+        }
+    }
+
+    fn as_string(&self) -> String {
+        match self {
+            Value::Null => String::new(),
+            Value::Bool(value) => value.to_string(),
+            Value::String(value) => value.clone(),
+            Value::List(values) => values.join(","),
+        }
+    }
+}
+
+/// The parts of the trigger an expression can read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Variable {
+    Event,
+    Branch,
+    Tag,
+    ChangedPaths,
+}
+
+impl Variable {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "event" => Some(Variable::Event),
+            "branch" => Some(Variable::Branch),
+            "tag" => Some(Variable::Tag),
+            "changed_paths" => Some(Variable::ChangedPaths),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Function {
+    Success,    // All the actions it needs have completed
+    Failure,    // An action it needs, directly or not, has failed
+    Always,     // Whatever the other actions ended in
+    Contains,   // contains(list or string, item)
+    StartsWith, // startsWith(string, prefix)
+    EndsWith,   // endsWith(string, suffix)
+    Changed,    // changed(glob): one of the changed paths matches
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "success" => Some(Function::Success),
+            "failure" => Some(Function::Failure),
+            "always" => Some(Function::Always),
+            "contains" => Some(Function::Contains),
+            "startsWith" => Some(Function::StartsWith),
+            "endsWith" => Some(Function::EndsWith),
+            "changed" => Some(Function::Changed),
+            _ => None,
+        }
+    }
+
+    fn arity(&self) -> usize {
+        match self {
+            Function::Success | Function::Failure | Function::Always => 0,
+            Function::Changed => 1,
+            Function::Contains | Function::StartsWith | Function::EndsWith => 2,
+        }
+    }
+
+    fn is_status_check(&self) -> bool {
+        matches!(self, Function::Success | Function::Failure | Function::Always)
+    }
+}
+
+/// What an expression is evaluated against when its action is about to be dispatched.
+pub struct EvaluationContext<'a> {
+    pub trigger: &'a Trigger,
+    pub needs_completed: bool, // All the actions it needs have completed
+    pub needs_failed: bool,    // An action it needs, directly or not, has failed
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, ParsingError> {
+        let source = source.trim();
+        let source = source
+            .strip_prefix("${{")
+            .and_then(|source| source.strip_suffix("}}"))
+            .unwrap_or(source);
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, position: 0 };
+        let expression = parser.parse_or()?;
+        match parser.peek() {
+            None => Ok(expression),
+            Some(token) => Err(ParsingError::InvalidExpression(format!(
+                "unexpected {:?}",
+                token
+            ))),
+        }
+    }
+
+    /// Whether the expression checks how the actions it needs ended. When it does not, it only applies once they have all completed.
+    pub fn checks_status(&self) -> bool {
+        match self {
+            Expression::Literal(_) | Expression::Variable(_) => false,
+            Expression::Not(expression) => expression.checks_status(),
+            Expression::And(left, right)
+            | Expression::Or(left, right)
+            | Expression::Equal(left, right)
+            | Expression::NotEqual(left, right) => left.checks_status() || right.checks_status(),
+            Expression::Call(function, arguments) => {
+                function.is_status_check() || arguments.iter().any(Expression::checks_status)
+            }
+        }
+    }
+
+    /// Whether the action runs.
+    pub fn holds(&self, context: &EvaluationContext) -> bool {
+        if !self.checks_status() && !context.needs_completed {
+            return false;
+        }
+        self.evaluate(context).is_truthy()
+    }
+
+    fn evaluate(&self, context: &EvaluationContext) -> Value {
+        match self {
+            Expression::Literal(value) => value.clone(),
+            Expression::Variable(variable) => {
+                let trigger = context.trigger;
+                match variable {
+                    Variable::Event => Value::String(trigger.event.clone()),
+                    Variable::Branch => trigger.branch.clone().map_or(Value::Null, Value::String),
+                    Variable::Tag => trigger.tag.clone().map_or(Value::Null, Value::String),
+                    Variable::ChangedPaths => Value::List(trigger.changed_paths.clone()),
+                }
+            }
+            Expression::Not(expression) => Value::Bool(!expression.evaluate(context).is_truthy()),
+            Expression::And(left, right) => Value::Bool(
+                left.evaluate(context).is_truthy() && right.evaluate(context).is_truthy(),
+            ),
+            Expression::Or(left, right) => Value::Bool(
+                left.evaluate(context).is_truthy() || right.evaluate(context).is_truthy(),
+            ),
+            Expression::Equal(left, right) => {
+                Value::Bool(left.evaluate(context) == right.evaluate(context))
+            }
+            Expression::NotEqual(left, right) => {
+                Value::Bool(left.evaluate(context) != right.evaluate(context))
+            }
+            Expression::Call(function, arguments) => {
+                let arguments: Vec<Value> = arguments
+                    .iter()
+                    .map(|argument| argument.evaluate(context))
+                    .collect();
+                let result = match function {
+                    Function::Success => context.needs_completed,
+                    Function::Failure => context.needs_failed,
+                    Function::Always => true,
+                    Function::Contains => match &arguments[0] {
+                        Value::List(values) => values.contains(&arguments[1].as_string()),
+                        value => value.as_string().contains(&arguments[1].as_string()),
+                    },
+                    Function::StartsWith => arguments[0]
+                        .as_string()
+                        .starts_with(&arguments[1].as_string()),
+                    Function::EndsWith => arguments[0]
+                        .as_string()
+                        .ends_with(&arguments[1].as_string()),
+                    Function::Changed => {
+                        let pattern = arguments[0].as_string();
+                        context
+                            .trigger
+                            .changed_paths
+                            .iter()
+                            .any(|path| glob_matches(&pattern, path))
+                    }
+                };
+                Value::Bool(result)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Identifier(String),
+    String(String),
+    Equal,
+    NotEqual,
+    And,
+    Or,
+    Not,
+    OpenParen,
+    CloseParen,
+    Comma,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ParsingError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            ' ' | '\t' | '\n' => continue,
+            '(' => Token::OpenParen,
+            ')' => Token::CloseParen,
+            ',' => Token::Comma,
+            '=' if chars.next_if_eq(&'=').is_some() => Token::Equal,
+            '!' if chars.next_if_eq(&'=').is_some() => Token::NotEqual,
+            '!' => Token::Not,
+            '&' if chars.next_if_eq(&'&').is_some() => Token::And,
+            '|' if chars.next_if_eq(&'|').is_some() => Token::Or,
+            // Strings are single quoted, a quote being escaped by doubling it.
+            '\'' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\'') if chars.next_if_eq(&'\'').is_some() => value.push('\''),
+                        Some('\'') => break,
+                        Some(c) => value.push(c),
+                        None => {
+                            return Err(ParsingError::InvalidExpression(String::from(
+                                "unterminated string",
+                            )))
+                        }
+                    }
+                }
+                Token::String(value)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut name = String::from(c);
+                while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+                Token::Identifier(name)
+            }
+            c => {
+                return Err(ParsingError::InvalidExpression(format!(
+                    "unexpected character '{}'",
+                    c
+                )))
+            }
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser, from the loosest operator to the tightest: `||`, `&&`, `!`, then `==` and `!=`.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ParsingError> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(ParsingError::InvalidExpression(format!(
+                "expected {:?}, found {:?}",
+                expected, token
+            ))),
+            None => Err(ParsingError::InvalidExpression(format!(
+                "expected {:?}, found the end",
+                expected
+            ))),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, ParsingError> {
+        let mut expression = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            expression = Expression::Or(Box::new(expression), Box::new(self.parse_and()?));
+        }
+        Ok(expression)
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, ParsingError> {
+        let mut expression = self.parse_not()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            expression = Expression::And(Box::new(expression), Box::new(self.parse_not()?));
+        }
+        Ok(expression)
+    }
+
+    fn parse_not(&mut self) -> Result<Expression, ParsingError> {
+        if self.peek() == Some(&Token::Not) {
+            self.next();
+            return Ok(Expression::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expression, ParsingError> {
+        let left = self.parse_primary()?;
+        match self.peek() {
+            Some(Token::Equal) => {
+                self.next();
+                Ok(Expression::Equal(Box::new(left), Box::new(self.parse_primary()?)))
+            }
+            Some(Token::NotEqual) => {
+                self.next();
+                Ok(Expression::NotEqual(Box::new(left), Box::new(self.parse_primary()?)))
+            }
+            _ => Ok(left),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, ParsingError> {
+        match self.next() {
+            Some(Token::String(value)) => Ok(Expression::Literal(Value::String(value))),
+            Some(Token::OpenParen) => {
+                let expression = self.parse_or()?;
+                self.expect(Token::CloseParen)?;
+                Ok(expression)
+            }
+            Some(Token::Identifier(name)) if self.peek() == Some(&Token::OpenParen) => {
+                self.next();
+                self.parse_call(name)
+            }
+            Some(Token::Identifier(name)) => match name.as_str() {
+                "true" => Ok(Expression::Literal(Value::Bool(true))),
+                "false" => Ok(Expression::Literal(Value::Bool(false))),
+                "null" => Ok(Expression::Literal(Value::Null)),
+                _ => Variable::from_name(&name)
+                    .map(Expression::Variable)
+                    .ok_or(ParsingError::UnknownVariable(name)),
+            },
+            Some(token) => Err(ParsingError::InvalidExpression(format!(
+                "unexpected {:?}",
+                token
+            ))),
+            None => Err(ParsingError::InvalidExpression(String::from(
+                "unexpected end",
+            ))),
+        }
+    }
+
+    /// Parse the arguments of a function, once its opening parenthesis is consumed.
+    fn parse_call(&mut self, name: String) -> Result<Expression, ParsingError> {
+        let function = Function::from_name(&name).ok_or(ParsingError::UnknownFunction(name.clone()))?;
+        let mut arguments = Vec::new();
+        if self.peek() != Some(&Token::CloseParen) {
+            arguments.push(self.parse_or()?);
+            while self.peek() == Some(&Token::Comma) {
+                self.next();
+                arguments.push(self.parse_or()?);
+            }
+        }
+        self.expect(Token::CloseParen)?;
+        if arguments.len() != function.arity() {
+            return Err(ParsingError::InvalidExpression(format!(
+                "{}() takes {} argument(s), {} given",
+                name,
+                function.arity(),
+                arguments.len()
+            )));
+        }
+        Ok(Expression::Call(function, arguments))
+    }
+}
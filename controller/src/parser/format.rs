@@ -0,0 +1,108 @@
+use core::fmt;
+use std::path::Path;
+
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use yaml_rust::yaml::{Hash, Yaml};
+
+/// Formats a manifest can be written in. They all describe the same pipelines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ManifestFormat {
+    #[default]
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl ManifestFormat {
+    /// The format of a manifest file, from its extension, else from its content type. Yaml when neither tells.
+    pub fn detect(file_name: Option<&str>, content_type: Option<&str>) -> Self {
+        let extension = file_name
+            .and_then(|name| Path::new(name).extension())
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("json") => return ManifestFormat::Json,
+            Some("toml") => return ManifestFormat::Toml,
+            Some("yml") | Some("yaml") => return ManifestFormat::Yaml,
+            _ => {}
+        }
+        match content_type {
+            Some(content_type) if content_type.ends_with("json") => ManifestFormat::Json,
+            Some(content_type) if content_type.ends_with("toml") => ManifestFormat::Toml,
+            _ => ManifestFormat::Yaml,
+        }
+    }
+}
+
+/// A manifest read from any format, as the yaml document the parser checks. Mappings keep the order of the manifest.
+pub struct Document(pub Yaml);
+
+impl<'de> Deserialize<'de> for Document {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(DocumentVisitor).map(Document)
+    }
+}
+
+struct DocumentVisitor;
+
+impl<'de> Visitor<'de> for DocumentVisitor {
+    type Value = Yaml;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a pipeline manifest")
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<Yaml, E> {
+        Ok(Yaml::Boolean(value))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Yaml, E> {
+        Ok(Yaml::Integer(value))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Yaml, E> {
+        Ok(i64::try_from(value)
+            .map(Yaml::Integer)
+            .unwrap_or_else(|_| Yaml::Real(value.to_string())))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<Yaml, E> {
+        Ok(Yaml::Real(value.to_string()))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Yaml, E> {
+        Ok(Yaml::String(value.to_string()))
+    }
+
+    fn visit_string<E>(self, value: String) -> Result<Yaml, E> {
+        Ok(Yaml::String(value))
+    }
+
+    fn visit_unit<E>(self) -> Result<Yaml, E> {
+        Ok(Yaml::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<Yaml, E> {
+        Ok(Yaml::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Yaml, D::Error> {
+        Document::deserialize(deserializer).map(|Document(value)| value)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Yaml, A::Error> {
+        let mut values = Vec::new();
+        while let Some(Document(value)) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(Yaml::Array(values))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Yaml, A::Error> {
+        let mut hash = Hash::new();
+        while let Some((Document(key), Document(value))) = map.next_entry()? {
+            hash.insert(key, value);
+        }
+        Ok(Yaml::Hash(hash))
+    }
+}
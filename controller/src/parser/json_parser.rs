@@ -0,0 +1,69 @@
+use yaml_rust::yaml::Yaml;
+
+use crate::parser::diagnostic::{Diagnostic, Diagnostics};
+use crate::parser::format::Document;
+use crate::parser::pipe_parser::{
+    check_document, into_result, resolve_document, ManifestParser, ManifestPipeline, ParsingError,
+};
+use crate::parser::template::{NoTemplates, TemplateSource};
+
+/// Parses JSON manifests, for tools generating pipelines. They have the same keys as yaml ones.
+#[derive(Clone, Default)]
+pub struct JsonParser {
+    pub strict: bool, // Reject unknown keys rather than only warning about them
+}
+
+impl JsonParser {
+    fn load(&self, json: &str, diagnostics: &mut Diagnostics) -> Option<Yaml> {
+        match serde_json::from_str::<Document>(json) {
+            Ok(Document(doc)) => Some(doc),
+            Err(e) => {
+                diagnostics.syntax_error_at(e.to_string(), e.line(), e.column());
+                None
+            }
+        }
+    }
+
+    /// JSON being yaml too, its nodes are located the way yaml ones are.
+    fn check(
+        &self,
+        json: &str,
+        templates: &dyn TemplateSource,
+    ) -> (Option<ManifestPipeline>, Diagnostics) {
+        let mut diagnostics = Diagnostics::new(json);
+        let pipeline = self
+            .load(json, &mut diagnostics)
+            .and_then(|doc| check_document(doc, templates, self.strict, &mut diagnostics));
+        (pipeline, diagnostics)
+    }
+}
+
+impl ManifestParser for JsonParser {
+    fn parse(&self, json: String) -> Result<ManifestPipeline, ParsingError> {
+        self.parse_with_templates(json, &NoTemplates)
+    }
+
+    fn parse_with_templates(
+        &self,
+        json: String,
+        templates: &dyn TemplateSource,
+    ) -> Result<ManifestPipeline, ParsingError> {
+        let (pipeline, diagnostics) = self.check(&json, templates);
+        into_result(pipeline, diagnostics)
+    }
+
+    fn resolve(
+        &self,
+        json: String,
+        templates: &dyn TemplateSource,
+    ) -> Result<String, ParsingError> {
+        let Document(doc) =
+            serde_json::from_str(&json).map_err(|_| ParsingError::YamlNotCompliant)?;
+        resolve_document(doc, templates)
+    }
+
+    fn validate(&self, json: String, templates: &dyn TemplateSource) -> Vec<Diagnostic> {
+        let (_, diagnostics) = self.check(&json, templates);
+        diagnostics.into_diagnostics()
+    }
+}
@@ -1 +1,8 @@
+pub mod diagnostic;
+pub mod expression;
+pub mod format;
+pub mod json_parser;
 pub mod pipe_parser;
+pub mod template;
+pub mod toml_parser;
+pub mod trigger;
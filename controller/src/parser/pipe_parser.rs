@@ -1,24 +1,74 @@
 use core::fmt;
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 use yaml_rust::yaml::Yaml;
-use yaml_rust::YamlLoader;
+use yaml_rust::{YamlEmitter, YamlLoader};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use crate::parser::diagnostic::{self, Diagnostic, Diagnostics};
+use crate::parser::expression::Expression;
+use crate::parser::template::{self, NoTemplates, TemplateSource};
+use crate::parser::trigger::TriggerFilter;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct ManifestPipeline {
     pub name: String,
     pub actions: Vec<ManifestAction>,
+    pub max_parallelism: Option<usize>, // Actions run at once at most, unlimited when None
+    pub on: TriggerFilter,              // Triggers the pipeline runs for
+    pub priority: u32, // Priority of the actions in the scheduler, higher ones of a repository being dispatched first
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct ManifestAction {
     pub name: String,
     pub commands: Vec<String>,
     pub configuration_type: Type,
     pub configuration_version: String,
+    pub runs_on: Vec<String>,
+    pub needs: Vec<String>, // Names of the actions that must complete before this one starts
+    pub env: Vec<String>,   // Environment variables of the commands, as KEY=VALUE
+    pub condition: Option<String>, // `if:` expression, the action is skipped when it does not hold
+    pub retry: RetryPolicy,
 }
 
+/// How many times an action is run before giving up on it, and when.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32, // Including the first one
+    pub backoff: u32,      // Seconds to wait before the second attempt, doubled before each of the next ones
+    pub on: Vec<i32>,      // Exit codes the action is retried on, any failure when empty
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            backoff: 0,
+            on: Vec::new(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether an action failing its `attempt` with the given exit code is run again.
+    pub fn allows_retry(&self, attempt: u32, exit_code: Option<i32>) -> bool {
+        attempt < self.max_attempts
+            && (self.on.is_empty() || exit_code.is_some_and(|code| self.on.contains(&code)))
+    }
+
+    /// Time to wait before the given attempt, from the second one on.
+    pub fn backoff_before(&self, attempt: u32) -> Duration {
+        let doublings = attempt.saturating_sub(2).min(16);
+        Duration::from_secs(u64::from(self.backoff) << doublings)
+    }
+}
+
+/// The values of the matrix an action is run with, in the order of its keys.
+type Combination = Vec<(String, String)>;
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum Type {
     Container,
@@ -55,7 +105,21 @@ pub trait ManifestParser: Sync + Send {
     /// # Errors
     ///
     /// This function will return an error if the yaml is not compliant with the expected format.
+    /// The manifest cannot include any template.
     fn parse(&self, yaml: String) -> Result<ManifestPipeline, ParsingError>;
+
+    /// Parse a manifest, including the templates it asks for from the given source.
+    fn parse_with_templates(
+        &self,
+        yaml: String,
+        templates: &dyn TemplateSource,
+    ) -> Result<ManifestPipeline, ParsingError>;
+
+    /// The manifest once its templates are included and its actions extended, as yaml.
+    fn resolve(&self, yaml: String, templates: &dyn TemplateSource) -> Result<String, ParsingError>;
+
+    /// Check a whole manifest, including its templates, and return everything wrong with it rather than the first error.
+    fn validate(&self, yaml: String, templates: &dyn TemplateSource) -> Vec<Diagnostic>;
 }
 
 #[derive(Debug, PartialEq)]
@@ -68,19 +132,286 @@ pub enum ParsingError {
     MissingConfiguration,
     MissingCommands,
     MissingStepName,
+    InvalidRunsOn,
+    InvalidNeeds,
+    UnknownNeed(String),
+    CyclicNeeds(Vec<String>),
+    InvalidMaxParallelism,
+    InvalidPriority,
+    InvalidEnv,
+    InvalidMatrix,
+    UnknownMatrixKey(String),
+    InvalidTrigger,
+    InvalidCondition,
+    InvalidRetry,
+    InvalidExpression(String),
+    UnknownVariable(String),
+    UnknownFunction(String),
+    InvalidInclude,
+    UnknownTemplate(String),
+    CyclicIncludes(Vec<String>),
+    InvalidExtends,
+    UnknownBase(String),
+    CyclicExtends(Vec<String>),
+    UnknownKey(String),
 }
 
-#[derive(Clone)]
-pub struct PipeParser {}
+impl fmt::Display for ParsingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParsingError::InconsistentCommandIndentation => {
+                write!(f, "the commands of an action are not all indented the same way")
+            }
+            ParsingError::YamlNotCompliant => write!(f, "the manifest does not have the expected format"),
+            ParsingError::InvalidActionName => write!(f, "the name of the action has invalid characters"),
+            ParsingError::MissingName => write!(f, "the pipeline has no name"),
+            ParsingError::MissingActions => write!(f, "the pipeline has no actions"),
+            ParsingError::MissingConfiguration => write!(f, "the action has no container to run in"),
+            ParsingError::MissingCommands => write!(f, "the action has no commands"),
+            ParsingError::MissingStepName => write!(f, "the name of the action is not a string"),
+            ParsingError::InvalidRunsOn => write!(f, "`runs_on` is not a list of labels"),
+            ParsingError::InvalidNeeds => write!(f, "`needs` is not a list of action names"),
+            ParsingError::UnknownNeed(need) => write!(f, "`{}` is not an action of the pipeline", need),
+            ParsingError::CyclicNeeds(cycle) => write!(f, "the actions need each other: {}", cycle.join(" -> ")),
+            ParsingError::InvalidMaxParallelism => write!(f, "`max_parallelism` is not a positive number"),
+            ParsingError::InvalidPriority => write!(f, "`priority` is not a number greater than or equal to 0"),
+            ParsingError::InvalidEnv => write!(f, "`env` does not map variable names to values"),
+            ParsingError::InvalidMatrix => write!(f, "`matrix` does not yield distinct combinations of values"),
+            ParsingError::UnknownMatrixKey(key) => write!(f, "`{}` is not a key of the matrix", key),
+            ParsingError::InvalidTrigger => write!(f, "`on` is not a valid trigger filter"),
+            ParsingError::InvalidCondition => write!(f, "`if` is not an expression"),
+            ParsingError::InvalidRetry => write!(f, "`retry` is not a valid retry policy"),
+            ParsingError::InvalidExpression(reason) => write!(f, "invalid expression: {}", reason),
+            ParsingError::UnknownVariable(name) => write!(f, "`{}` is not a variable", name),
+            ParsingError::UnknownFunction(name) => write!(f, "`{}` is not a function", name),
+            ParsingError::InvalidInclude => write!(f, "`include` is not a list of template names"),
+            ParsingError::UnknownTemplate(name) => write!(f, "there is no template named `{}`", name),
+            ParsingError::CyclicIncludes(cycle) => {
+                write!(f, "the templates include each other: {}", cycle.join(" -> "))
+            }
+            ParsingError::InvalidExtends => write!(f, "`extends` is not a list of action names"),
+            ParsingError::UnknownBase(name) => write!(f, "there is no action named `{}` to extend", name),
+            ParsingError::CyclicExtends(cycle) => {
+                write!(f, "the actions extend each other: {}", cycle.join(" -> "))
+            }
+            ParsingError::UnknownKey(key) => write!(f, "`{}` is not a known key", key),
+        }
+    }
+}
+
+/// Keys of a pipeline and of its actions, once templates are included and actions extended. The JSON Schema of manifests lists the same ones.
+pub const PIPELINE_KEYS: [&str; 5] = ["name", "actions", "max_parallelism", "on", "priority"];
+pub const ACTION_KEYS: [&str; 8] = [
+    "configuration",
+    "commands",
+    "runs_on",
+    "needs",
+    "env",
+    "matrix",
+    "if",
+    "retry",
+];
+
+#[derive(Clone, Default)]
+pub struct PipeParser {
+    pub strict: bool, // Reject unknown keys rather than only warning about them
+}
 
 impl ManifestParser for PipeParser {
     fn parse(&self, yaml: String) -> Result<ManifestPipeline, ParsingError> {
-        check_command_indentation(&yaml)?;
-        let doc = parse_yaml(&yaml)?;
-        let name = parse_pipeline_name(&doc)?;
-        let actions = parse_actions(&doc)?;
+        self.parse_with_templates(yaml, &NoTemplates)
+    }
+
+    fn parse_with_templates(
+        &self,
+        yaml: String,
+        templates: &dyn TemplateSource,
+    ) -> Result<ManifestPipeline, ParsingError> {
+        let mut diagnostics = Diagnostics::new(&yaml);
+        let pipeline = check_manifest(&yaml, templates, self.strict, &mut diagnostics);
+        into_result(pipeline, diagnostics)
+    }
+
+    fn resolve(&self, yaml: String, templates: &dyn TemplateSource) -> Result<String, ParsingError> {
+        resolve_document(parse_yaml(&yaml)?, templates)
+    }
+
+    fn validate(&self, yaml: String, templates: &dyn TemplateSource) -> Vec<Diagnostic> {
+        let mut diagnostics = Diagnostics::new(&yaml);
+        check_manifest(&yaml, templates, self.strict, &mut diagnostics);
+        diagnostics.into_diagnostics()
+    }
+}
+
+/// The pipeline of a checked manifest, or the first error found in it.
+pub fn into_result(
+    pipeline: Option<ManifestPipeline>,
+    diagnostics: Diagnostics,
+) -> Result<ManifestPipeline, ParsingError> {
+    match pipeline {
+        Some(pipeline) if !diagnostics.has_errors() => Ok(pipeline),
+        _ => Err(diagnostics
+            .into_first_error()
+            .unwrap_or(ParsingError::YamlNotCompliant)),
+    }
+}
 
-        Ok(ManifestPipeline { name, actions })
+/// A manifest once its templates are included and its actions extended, as yaml, whatever its format.
+pub fn resolve_document(doc: Yaml, templates: &dyn TemplateSource) -> Result<String, ParsingError> {
+    let doc = template::resolve(doc, templates)?;
+    let mut resolved = String::new();
+    YamlEmitter::new(&mut resolved)
+        .dump(&doc)
+        .map_err(|_| ParsingError::YamlNotCompliant)?;
+    Ok(resolved)
+}
+
+/// Check a whole yaml manifest, reporting each error at the node it is about, in the order of the manifest.
+/// Return the pipeline, unless an error prevents building it. It may still have errors.
+fn check_manifest(
+    yaml: &str,
+    templates: &dyn TemplateSource,
+    strict: bool,
+    diagnostics: &mut Diagnostics,
+) -> Option<ManifestPipeline> {
+    if let Some((line, column)) = find_inconsistent_command_indentation(yaml) {
+        diagnostics.error_at(ParsingError::InconsistentCommandIndentation, line, column);
+    }
+    let doc = match YamlLoader::load_from_str(yaml) {
+        Ok(docs) => docs.into_iter().next(),
+        Err(e) => {
+            diagnostics.syntax_error(&e);
+            return None;
+        }
+    };
+    let doc = diagnostics.check(doc.ok_or(ParsingError::YamlNotCompliant), "")?;
+    check_document(doc, templates, strict, diagnostics)
+}
+
+/// Check a manifest loaded as a document, whatever its format, like `check_manifest` does.
+pub fn check_document(
+    doc: Yaml,
+    templates: &dyn TemplateSource,
+    strict: bool,
+    diagnostics: &mut Diagnostics,
+) -> Option<ManifestPipeline> {
+    let doc = match template::resolve(doc, templates) {
+        Ok(doc) => doc,
+        Err(e) => {
+            let path = match e {
+                ParsingError::InvalidExtends
+                | ParsingError::UnknownBase(_)
+                | ParsingError::CyclicExtends(_) => "actions",
+                _ => "include",
+            };
+            diagnostics.error(e, path);
+            return None;
+        }
+    };
+
+    let name = diagnostics.check(parse_pipeline_name(&doc), "name");
+    check_unknown_keys(&doc, "", &PIPELINE_KEYS, strict, diagnostics);
+    let actions = match doc["actions"].as_hash() {
+        Some(actions) => actions
+            .iter()
+            .map(|(name, action)| check_action(name, action, strict, diagnostics))
+            .collect::<Vec<_>>(),
+        None => {
+            diagnostics.error(ParsingError::MissingActions, "actions");
+            Vec::new()
+        }
+    };
+    let max_parallelism = diagnostics.check(parse_max_parallelism(&doc), "max_parallelism");
+    let on = diagnostics.check(parse_on(&doc), "on");
+    let priority = diagnostics.check(parse_priority(&doc), "priority");
+
+    // The graph of the actions can only be checked once each of them is valid.
+    let (mut actions, matrices): (Vec<_>, Vec<_>) =
+        actions.into_iter().collect::<Option<Vec<_>>>()?.into_iter().unzip();
+    chain_actions(&mut actions);
+    if !check_needs(&actions, diagnostics) {
+        return None;
+    }
+    let actions = diagnostics.check(expand_matrices(actions, matrices), "actions")?;
+
+    Some(ManifestPipeline {
+        name: name?,
+        actions,
+        max_parallelism: max_parallelism?,
+        on: on?,
+        priority: priority?,
+    })
+}
+
+/// Check an action, along with the combinations of its matrix, reporting each of its errors.
+/// The action is still to be expanded with the combinations.
+fn check_action(
+    name: &Yaml,
+    action: &Yaml,
+    strict: bool,
+    diagnostics: &mut Diagnostics,
+) -> Option<(ManifestAction, Vec<Combination>)> {
+    let path = format!("actions.{}", scalar_to_string(name).unwrap_or_default());
+    let at = |key: &str| format!("{}.{}", path, key);
+    let checked_name = diagnostics.check(parse_action_name(name), &path);
+    let configuration = diagnostics.check(parse_configuration(action), &at("configuration"));
+    let commands = diagnostics.check(parse_commands(action), &at("commands"));
+    let runs_on = diagnostics.check(parse_runs_on(action), &at("runs_on"));
+    let needs = diagnostics.check(parse_needs(action), &at("needs"));
+    let env = diagnostics.check(parse_env(action), &at("env"));
+    let matrix = diagnostics.check(parse_matrix(action), &at("matrix"));
+    let condition = diagnostics.check(parse_condition(action), &at("if"));
+    let retry = diagnostics.check(parse_retry(action), &at("retry"));
+    check_unknown_keys(action, &path, &ACTION_KEYS, strict, diagnostics);
+
+    Some((
+        ManifestAction {
+            name: checked_name?,
+            commands: commands?,
+            configuration_type: Type::Container,
+            configuration_version: configuration?,
+            runs_on: runs_on?,
+            needs: needs?,
+            env: env?,
+            condition: condition?,
+            retry: retry?,
+        },
+        matrix?,
+    ))
+}
+
+/// Keys the parser does not know are ignored: they are likely misspelled, so warn about them, or reject them when strict.
+fn check_unknown_keys(
+    node: &Yaml,
+    path: &str,
+    known: &[&str],
+    strict: bool,
+    diagnostics: &mut Diagnostics,
+) {
+    let Some(node) = node.as_hash() else {
+        return;
+    };
+    for key in node.keys().filter_map(|key| key.as_str()) {
+        if known.contains(&key) {
+            continue;
+        }
+        let key_path = if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        };
+        let suggestion = diagnostic::closest(key, known.iter().copied())
+            .map(|candidate| format!("did you mean `{}`?", candidate));
+        if strict {
+            diagnostics.error_with(ParsingError::UnknownKey(key.to_string()), &key_path, suggestion);
+        } else {
+            diagnostics.warning(
+                "UnknownKey",
+                format!("`{}` is not a known key, it is ignored", key),
+                &key_path,
+                suggestion,
+            );
+        }
     }
 }
 
@@ -96,29 +427,6 @@ fn parse_pipeline_name(doc: &Yaml) -> Result<String, ParsingError> {
         .map(String::from)
 }
 
-fn parse_actions(doc: &Yaml) -> Result<Vec<ManifestAction>, ParsingError> {
-    let actions_yaml = doc["actions"]
-        .as_hash()
-        .ok_or(ParsingError::MissingActions)?;
-    actions_yaml
-        .iter()
-        .map(|(name, action)| parse_action(name, action))
-        .collect()
-}
-
-fn parse_action(name: &Yaml, action: &Yaml) -> Result<ManifestAction, ParsingError> {
-    let name = parse_action_name(name)?;
-    let configuration = parse_configuration(action)?;
-    let commands = parse_commands(action)?;
-
-    Ok(ManifestAction {
-        name,
-        commands,
-        configuration_type: Type::Container,
-        configuration_version: configuration,
-    })
-}
-
 fn parse_action_name(name: &Yaml) -> Result<String, ParsingError> {
     let name = name
         .as_str()
@@ -161,31 +469,477 @@ fn parse_commands(action: &Yaml) -> Result<Vec<String>, ParsingError> {
         .collect()
 }
 
+/// `runs_on` is optional, and is either a single label or a list of labels the agent must all have.
+fn parse_runs_on(action: &Yaml) -> Result<Vec<String>, ParsingError> {
+    let labels = match &action["runs_on"] {
+        Yaml::BadValue => return Ok(Vec::new()),
+        Yaml::String(label) => vec![label.clone()],
+        Yaml::Array(labels) => labels
+            .iter()
+            .map(|label| {
+                label
+                    .as_str()
+                    .ok_or(ParsingError::InvalidRunsOn)
+                    .map(String::from)
+            })
+            .collect::<Result<Vec<String>, ParsingError>>()?,
+        _ => return Err(ParsingError::InvalidRunsOn),
+    };
+    if labels.iter().any(|label| label.trim().is_empty()) {
+        return Err(ParsingError::InvalidRunsOn);
+    }
+    Ok(labels)
+}
+
+/// `needs` is optional, and is either a single action name or a list of action names.
+fn parse_needs(action: &Yaml) -> Result<Vec<String>, ParsingError> {
+    let needs = match &action["needs"] {
+        Yaml::BadValue => return Ok(Vec::new()),
+        Yaml::String(need) => vec![need.clone()],
+        Yaml::Array(needs) => needs
+            .iter()
+            .map(|need| {
+                need.as_str()
+                    .ok_or(ParsingError::InvalidNeeds)
+                    .map(String::from)
+            })
+            .collect::<Result<Vec<String>, ParsingError>>()?,
+        _ => return Err(ParsingError::InvalidNeeds),
+    };
+    Ok(needs)
+}
+
+/// `env` is optional, and maps the names of environment variables to their values.
+fn parse_env(action: &Yaml) -> Result<Vec<String>, ParsingError> {
+    let env = match &action["env"] {
+        Yaml::BadValue => return Ok(Vec::new()),
+        Yaml::Hash(env) => env,
+        _ => return Err(ParsingError::InvalidEnv),
+    };
+    env.iter()
+        .map(|(name, value)| {
+            let name = name.as_str().ok_or(ParsingError::InvalidEnv)?;
+            let value = scalar_to_string(value).ok_or(ParsingError::InvalidEnv)?;
+            if name.is_empty() || name.contains('=') {
+                return Err(ParsingError::InvalidEnv);
+            }
+            Ok(format!("{}={}", name, value))
+        })
+        .collect()
+}
+
+/// `if` is optional, and is an expression the action only runs when it holds.
+fn parse_condition(action: &Yaml) -> Result<Option<String>, ParsingError> {
+    let condition = match &action["if"] {
+        Yaml::BadValue => return Ok(None),
+        condition => scalar_to_string(condition).ok_or(ParsingError::InvalidCondition)?,
+    };
+    Expression::parse(&condition)?;
+    Ok(Some(condition))
+}
+
+/// `retry` is optional, and is either a max number of attempts, or a mapping of `max_attempts`, `backoff` in seconds, and the exit codes to retry `on`.
+fn parse_retry(action: &Yaml) -> Result<RetryPolicy, ParsingError> {
+    let number = |value: &Yaml, min: i64| match value {
+        Yaml::Integer(value) if (min..=i64::from(i32::MAX)).contains(value) => Ok(*value),
+        _ => Err(ParsingError::InvalidRetry),
+    };
+
+    let retry = match &action["retry"] {
+        Yaml::BadValue => return Ok(RetryPolicy::default()),
+        Yaml::Hash(retry) => retry,
+        max_attempts => {
+            return Ok(RetryPolicy {
+                max_attempts: number(max_attempts, 1)? as u32,
+                ..Default::default()
+            })
+        }
+    };
+    let mut policy = RetryPolicy::default();
+    for (key, value) in retry {
+        match key.as_str() {
+            Some("max_attempts") => policy.max_attempts = number(value, 1)? as u32,
+            Some("backoff") => policy.backoff = number(value, 0)? as u32,
+            Some("on") => {
+                policy.on = match value {
+                    Yaml::Integer(_) => vec![number(value, 0)? as i32],
+                    Yaml::Array(codes) => codes
+                        .iter()
+                        .map(|code| number(code, 0).map(|code| code as i32))
+                        .collect::<Result<Vec<i32>, ParsingError>>()?,
+                    _ => return Err(ParsingError::InvalidRetry),
+                }
+            }
+            _ => return Err(ParsingError::InvalidRetry),
+        }
+    }
+    Ok(policy)
+}
+
+/// `on` is optional, and is either an event, a list of events, or a mapping of `events`, `branches`, `tags` and `paths`.
+fn parse_on(doc: &Yaml) -> Result<TriggerFilter, ParsingError> {
+    let strings = |value: &Yaml| match value {
+        Yaml::String(value) => Ok(vec![value.clone()]),
+        Yaml::Array(values) => values
+            .iter()
+            .map(|value| {
+                value
+                    .as_str()
+                    .ok_or(ParsingError::InvalidTrigger)
+                    .map(String::from)
+            })
+            .collect(),
+        _ => Err(ParsingError::InvalidTrigger),
+    };
+
+    let on = match &doc["on"] {
+        Yaml::BadValue => return Ok(TriggerFilter::default()),
+        Yaml::Hash(on) => on,
+        events => {
+            return Ok(TriggerFilter {
+                events: strings(events)?,
+                ..Default::default()
+            })
+        }
+    };
+    let mut filter = TriggerFilter::default();
+    for (key, value) in on {
+        let values = strings(value)?;
+        match key.as_str() {
+            Some("events") => filter.events = values,
+            Some("branches") => filter.branches = values,
+            Some("tags") => filter.tags = values,
+            Some("paths") => filter.paths = values,
+            _ => return Err(ParsingError::InvalidTrigger),
+        }
+    }
+    Ok(filter)
+}
+
+/// `matrix` is optional, and maps each of its keys to a list of values. The action is run once per combination of values,
+/// except the ones matching an entry of `exclude`, and once more per entry of `include`.
+/// Return no combination when the action has no matrix.
+fn parse_matrix(action: &Yaml) -> Result<Vec<Combination>, ParsingError> {
+    let matrix = match &action["matrix"] {
+        Yaml::BadValue => return Ok(Vec::new()),
+        Yaml::Hash(matrix) => matrix,
+        _ => return Err(ParsingError::InvalidMatrix),
+    };
+
+    let mut combinations: Vec<Combination> = Vec::new();
+    let mut keys = Vec::new();
+    for (key, values) in matrix {
+        let key = key.as_str().ok_or(ParsingError::InvalidMatrix)?;
+        if key == "include" || key == "exclude" {
+            continue;
+        }
+        let values = values
+            .as_vec()
+            .filter(|values| !values.is_empty())
+            .ok_or(ParsingError::InvalidMatrix)?
+            .iter()
+            .map(|value| scalar_to_string(value).ok_or(ParsingError::InvalidMatrix))
+            .collect::<Result<Vec<String>, ParsingError>>()?;
+        combinations = if keys.is_empty() {
+            values
+                .iter()
+                .map(|value| vec![(key.to_string(), value.clone())])
+                .collect()
+        } else {
+            combinations
+                .iter()
+                .flat_map(|combination| {
+                    values.iter().map(move |value| {
+                        let mut combination = combination.clone();
+                        combination.push((key.to_string(), value.clone()));
+                        combination
+                    })
+                })
+                .collect()
+        };
+        keys.push(key.to_string());
+    }
+
+    let excluded = parse_matrix_entries(&action["matrix"]["exclude"])?;
+    for entry in &excluded {
+        if let Some((key, _)) = entry.iter().find(|(key, _)| !keys.contains(key)) {
+            return Err(ParsingError::UnknownMatrixKey(key.clone()));
+        }
+    }
+    combinations.retain(|combination| {
+        !excluded
+            .iter()
+            .any(|entry| entry.iter().all(|pair| combination.contains(pair)))
+    });
+    combinations.extend(parse_matrix_entries(&action["matrix"]["include"])?);
+
+    if combinations.is_empty() {
+        return Err(ParsingError::InvalidMatrix);
+    }
+    Ok(combinations)
+}
+
+/// `include` and `exclude` are optional lists of combinations, each mapping keys of the matrix to a value.
+fn parse_matrix_entries(entries: &Yaml) -> Result<Vec<Combination>, ParsingError> {
+    let entries = match entries {
+        Yaml::BadValue => return Ok(Vec::new()),
+        Yaml::Array(entries) => entries,
+        _ => return Err(ParsingError::InvalidMatrix),
+    };
+    entries
+        .iter()
+        .map(|entry| {
+            let entry = entry
+                .as_hash()
+                .filter(|entry| !entry.is_empty())
+                .ok_or(ParsingError::InvalidMatrix)?;
+            entry
+                .iter()
+                .map(|(key, value)| {
+                    let key = key.as_str().ok_or(ParsingError::InvalidMatrix)?;
+                    let value = scalar_to_string(value).ok_or(ParsingError::InvalidMatrix)?;
+                    Ok((key.to_string(), value))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn scalar_to_string(value: &Yaml) -> Option<String> {
+    match value {
+        Yaml::String(value) | Yaml::Real(value) => Some(value.clone()),
+        Yaml::Integer(value) => Some(value.to_string()),
+        Yaml::Boolean(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// `priority` is optional, 0 by default. It is stored along with each action, hence its maximum.
+fn parse_priority(doc: &Yaml) -> Result<u32, ParsingError> {
+    match &doc["priority"] {
+        Yaml::BadValue => Ok(0),
+        Yaml::Integer(priority) if (0..=i64::from(i32::MAX)).contains(priority) => Ok(*priority as u32),
+        _ => Err(ParsingError::InvalidPriority),
+    }
+}
+
+/// `max_parallelism` is optional, and is a positive number of actions.
+fn parse_max_parallelism(doc: &Yaml) -> Result<Option<usize>, ParsingError> {
+    match &doc["max_parallelism"] {
+        Yaml::BadValue => Ok(None),
+        Yaml::Integer(max) if *max > 0 => Ok(Some(*max as usize)),
+        _ => Err(ParsingError::InvalidMaxParallelism),
+    }
+}
+
+/// Without any `needs`, the actions of a pipeline run one after the other, in the order of the manifest.
+fn chain_actions(actions: &mut [ManifestAction]) {
+    if actions.iter().any(|action| !action.needs.is_empty()) {
+        return;
+    }
+    for i in 1..actions.len() {
+        actions[i].needs = vec![actions[i - 1].name.clone()];
+    }
+}
+
+/// The `needs` of the actions must form a graph without cycles, between actions of the pipeline.
+/// Return whether they do, reporting each unknown need, or the first cycle found.
+fn check_needs(actions: &[ManifestAction], diagnostics: &mut Diagnostics) -> bool {
+    let by_name: HashMap<&str, &ManifestAction> = actions
+        .iter()
+        .map(|action| (action.name.as_str(), action))
+        .collect();
+    let mut known = true;
+    for action in actions {
+        for need in action.needs.iter().filter(|need| !by_name.contains_key(need.as_str())) {
+            let suggestion = diagnostic::closest(need, by_name.keys().copied())
+                .map(|candidate| format!("did you mean `{}`?", candidate));
+            diagnostics.error_with(
+                ParsingError::UnknownNeed(need.clone()),
+                &format!("actions.{}.needs", action.name),
+                suggestion,
+            );
+            known = false;
+        }
+    }
+    if !known {
+        return false;
+    }
+
+    #[derive(Clone, Copy, PartialEq)]
+    enum Visit {
+        InProgress,
+        Done,
+    }
+
+    // Depth-first search, keeping the path to report the cycle found, if any.
+    fn visit<'a>(
+        name: &'a str,
+        by_name: &HashMap<&'a str, &'a ManifestAction>,
+        visits: &mut HashMap<&'a str, Visit>,
+        path: &mut Vec<&'a str>,
+    ) -> Result<(), Vec<String>> {
+        match visits.get(name) {
+            Some(Visit::Done) => return Ok(()),
+            Some(Visit::InProgress) => {
+                let start = path.iter().position(|step| *step == name).unwrap_or(0);
+                let mut cycle: Vec<String> = path[start..].iter().map(|step| step.to_string()).collect();
+                cycle.push(name.to_string());
+                return Err(cycle);
+            }
+            None => {}
+        }
+        visits.insert(name, Visit::InProgress);
+        path.push(name);
+        for need in &by_name[name].needs {
+            visit(need.as_str(), by_name, visits, path)?;
+        }
+        path.pop();
+        visits.insert(name, Visit::Done);
+        Ok(())
+    }
+
+    let mut visits = HashMap::new();
+    for action in actions {
+        if let Err(cycle) = visit(action.name.as_str(), &by_name, &mut visits, &mut Vec::new()) {
+            let path = format!("actions.{}.needs", cycle[0]);
+            diagnostics.error(ParsingError::CyclicNeeds(cycle), &path);
+            return false;
+        }
+    }
+    true
+}
+
+/// Replace each action having a matrix with one action per combination, named after its values, e.g. `test (rust:1.80, full)`.
+/// The actions needing an action with a matrix need all of its combinations.
+fn expand_matrices(
+    actions: Vec<ManifestAction>,
+    matrices: Vec<Vec<Combination>>,
+) -> Result<Vec<ManifestAction>, ParsingError> {
+    let mut expanded_names: HashMap<String, Vec<String>> = HashMap::new();
+    let mut expanded = Vec::new();
+    for (action, matrix) in actions.into_iter().zip(matrices) {
+        if matrix.is_empty() {
+            expanded_names.insert(action.name.clone(), vec![action.name.clone()]);
+            expanded.push(interpolate_action(&action, &[])?);
+            continue;
+        }
+        let mut names = Vec::new();
+        for combination in &matrix {
+            let mut concrete = interpolate_action(&action, combination)?;
+            let values: Vec<&str> = combination.iter().map(|(_, value)| value.as_str()).collect();
+            concrete.name = format!("{} ({})", action.name, values.join(", "));
+            names.push(concrete.name.clone());
+            expanded.push(concrete);
+        }
+        expanded_names.insert(action.name, names);
+    }
+
+    // `include` may add a combination the matrix already has.
+    let mut names = HashSet::new();
+    if !expanded.iter().all(|action| names.insert(action.name.as_str())) {
+        return Err(ParsingError::InvalidMatrix);
+    }
+
+    for action in &mut expanded {
+        action.needs = action
+            .needs
+            .iter()
+            .flat_map(|need| expanded_names[need].clone())
+            .collect();
+    }
+    Ok(expanded)
+}
+
+/// Replace the `${{ matrix.<key> }}` expressions of an action with the values of a combination.
+fn interpolate_action(
+    action: &ManifestAction,
+    combination: &[(String, String)],
+) -> Result<ManifestAction, ParsingError> {
+    let interpolate_all = |texts: &[String]| {
+        texts
+            .iter()
+            .map(|text| interpolate(text, combination))
+            .collect::<Result<Vec<String>, ParsingError>>()
+    };
+    Ok(ManifestAction {
+        name: action.name.clone(),
+        commands: interpolate_all(&action.commands)?,
+        configuration_type: action.configuration_type.clone(),
+        configuration_version: interpolate(&action.configuration_version, combination)?,
+        runs_on: interpolate_all(&action.runs_on)?,
+        needs: action.needs.clone(),
+        env: interpolate_all(&action.env)?,
+        condition: action
+            .condition
+            .as_ref()
+            .map(|condition| interpolate(condition, combination))
+            .transpose()?,
+        retry: action.retry.clone(),
+    })
+}
+
+/// Replace the `${{ matrix.<key> }}` expressions of a text, leaving any other expression as is.
+fn interpolate(text: &str, combination: &[(String, String)]) -> Result<String, ParsingError> {
+    let mut interpolated = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${{") {
+        let Some(length) = rest[start..].find("}}") else {
+            break;
+        };
+        let expression = &rest[start..start + length + 2];
+        interpolated.push_str(&rest[..start]);
+        match expression[3..length].trim().strip_prefix("matrix.") {
+            Some(key) if key.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') => {
+                let (_, value) = combination
+                    .iter()
+                    .find(|(name, _)| name == key)
+                    .ok_or_else(|| ParsingError::UnknownMatrixKey(key.to_string()))?;
+                interpolated.push_str(value);
+            }
+            _ => interpolated.push_str(expression),
+        }
+        rest = &rest[start + length + 2..];
+    }
+    interpolated.push_str(rest);
+    Ok(interpolated)
+}
+
 fn is_valid_action_name(name: &str) -> bool {
     let valid_chars = |c: char| c.is_alphanumeric() || c == ' ' || c == '&' || c == '-' || c == '_';
     !name.is_empty() && name.chars().all(valid_chars)
 }
 
-fn check_command_indentation(yaml: &str) -> Result<(), ParsingError> {
-    let lines: Vec<&str> = yaml.lines().collect();
+/// The line and column of the first command not indented like the previous ones, if any.
+fn find_inconsistent_command_indentation(yaml: &str) -> Option<(usize, usize)> {
     let mut in_commands = false;
+    let mut commands_key_indent = 0;
     let mut command_indent = None;
 
-    for line in lines {
+    for (number, line) in yaml.lines().enumerate() {
+        let indent = line.chars().take_while(|&c| c == ' ').count();
         if line.trim().starts_with("commands:") {
             in_commands = true;
+            commands_key_indent = indent;
             continue;
         }
+        // The commands end with the next key of the action, e.g. the lists of a matrix are not commands.
+        if in_commands
+            && !line.trim().is_empty()
+            && !line.trim().starts_with('-')
+            && indent <= commands_key_indent
+        {
+            in_commands = false;
+        }
         if in_commands && line.trim().starts_with('-') {
-            let indent = line.chars().take_while(|&c| c == ' ').count();
             if let Some(prev_indent) = command_indent {
                 if indent != prev_indent {
-                    return Err(ParsingError::InconsistentCommandIndentation);
+                    return Some((number + 1, indent + 1));
                 }
             } else {
                 command_indent = Some(indent);
             }
         }
     }
-    Ok(())
+    None
 }
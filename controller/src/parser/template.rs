@@ -0,0 +1,161 @@
+use yaml_rust::yaml::{Hash, Yaml};
+use yaml_rust::YamlLoader;
+
+use crate::parser::pipe_parser::ParsingError;
+
+/// Where the templates a manifest includes come from.
+pub trait TemplateSource: Sync + Send {
+    /// The content of the template with the given name, if there is one.
+    fn find(&self, name: &str) -> Option<String>;
+}
+
+/// A source without any template, for manifests that must not include any.
+pub struct NoTemplates;
+
+impl TemplateSource for NoTemplates {
+    fn find(&self, _name: &str) -> Option<String> {
+        None
+    }
+}
+
+/// Resolve the `include:` of a manifest, then the `extends:` of its actions, into a manifest without either.
+pub fn resolve(doc: Yaml, templates: &dyn TemplateSource) -> Result<Yaml, ParsingError> {
+    let doc = resolve_includes(doc, templates, &mut Vec::new())?;
+    resolve_extends(doc)
+}
+
+/// `include` is optional, and is either a template name or a list of template names.
+/// The templates are merged in order, each one over the previous ones, then the document over all of them.
+fn resolve_includes(
+    doc: Yaml,
+    templates: &dyn TemplateSource,
+    including: &mut Vec<String>,
+) -> Result<Yaml, ParsingError> {
+    let Yaml::Hash(mut doc) = doc else {
+        return Ok(doc);
+    };
+    let names = match doc.remove(&key("include")) {
+        None => return Ok(Yaml::Hash(doc)),
+        Some(Yaml::String(name)) => vec![name],
+        Some(Yaml::Array(names)) => names
+            .iter()
+            .map(|name| {
+                name.as_str()
+                    .ok_or(ParsingError::InvalidInclude)
+                    .map(String::from)
+            })
+            .collect::<Result<Vec<String>, ParsingError>>()?,
+        Some(_) => return Err(ParsingError::InvalidInclude),
+    };
+
+    let mut resolved = Yaml::Hash(Hash::new());
+    for name in names {
+        if including.contains(&name) {
+            let mut cycle = including.clone();
+            cycle.push(name);
+            return Err(ParsingError::CyclicIncludes(cycle));
+        }
+        let content = templates
+            .find(&name)
+            .ok_or_else(|| ParsingError::UnknownTemplate(name.clone()))?;
+        let template = YamlLoader::load_from_str(&content)
+            .ok()
+            .and_then(|docs| docs.into_iter().next())
+            .ok_or(ParsingError::YamlNotCompliant)?;
+        including.push(name);
+        let template = resolve_includes(template, templates, including)?;
+        including.pop();
+        resolved = merge(resolved, template);
+    }
+    Ok(merge(resolved, Yaml::Hash(doc)))
+}
+
+/// `extends` is optional on an action, and is either the name of another action or a list of names.
+/// The action is merged over the actions it extends, in order. Actions whose name starts with a `.` are only there to be extended, and never run.
+fn resolve_extends(doc: Yaml) -> Result<Yaml, ParsingError> {
+    let Yaml::Hash(mut doc) = doc else {
+        return Ok(doc);
+    };
+    let Some(Yaml::Hash(actions)) = doc.get(&key("actions")).cloned() else {
+        return Ok(Yaml::Hash(doc));
+    };
+
+    let mut resolved = Hash::new();
+    for (name, _) in actions.iter() {
+        let Some(name) = name.as_str() else {
+            continue;
+        };
+        if name.starts_with('.') {
+            continue;
+        }
+        let action = resolve_action(name, &actions, &mut Vec::new())?;
+        resolved.insert(key(name), action);
+    }
+    doc.insert(key("actions"), Yaml::Hash(resolved));
+    Ok(Yaml::Hash(doc))
+}
+
+fn resolve_action(
+    name: &str,
+    actions: &Hash,
+    extending: &mut Vec<String>,
+) -> Result<Yaml, ParsingError> {
+    if extending.iter().any(|step| step == name) {
+        let mut cycle = extending.clone();
+        cycle.push(name.to_string());
+        return Err(ParsingError::CyclicExtends(cycle));
+    }
+    let action = actions
+        .get(&key(name))
+        .ok_or_else(|| ParsingError::UnknownBase(name.to_string()))?;
+    let Yaml::Hash(mut action) = action.clone() else {
+        return Ok(action.clone());
+    };
+    let bases = match action.remove(&key("extends")) {
+        None => return Ok(Yaml::Hash(action)),
+        Some(Yaml::String(base)) => vec![base],
+        Some(Yaml::Array(bases)) => bases
+            .iter()
+            .map(|base| {
+                base.as_str()
+                    .ok_or(ParsingError::InvalidExtends)
+                    .map(String::from)
+            })
+            .collect::<Result<Vec<String>, ParsingError>>()?,
+        Some(_) => return Err(ParsingError::InvalidExtends),
+    };
+
+    extending.push(name.to_string());
+    let mut resolved = Yaml::Hash(Hash::new());
+    for base in bases {
+        resolved = merge(resolved, resolve_action(&base, actions, extending)?);
+    }
+    extending.pop();
+    Ok(merge(resolved, Yaml::Hash(action)))
+}
+
+/// Deep-merge two documents: mappings are merged key by key, anything else in `over` replaces what is in `base`.
+fn merge(base: Yaml, over: Yaml) -> Yaml {
+    match (base, over) {
+        (Yaml::Hash(mut base), Yaml::Hash(over)) => {
+            for (key, value) in over {
+                // A key keeps its place in `base`.
+                match base.get_mut(&key) {
+                    Some(slot) => {
+                        let base_value = std::mem::replace(slot, Yaml::Null);
+                        *slot = merge(base_value, value);
+                    }
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+            Yaml::Hash(base)
+        }
+        (_, over) => over,
+    }
+}
+
+fn key(name: &str) -> Yaml {
+    Yaml::String(name.to_string())
+}
@@ -0,0 +1,70 @@
+use yaml_rust::yaml::Yaml;
+
+use crate::parser::diagnostic::{self, Diagnostic, Diagnostics};
+use crate::parser::format::Document;
+use crate::parser::pipe_parser::{
+    check_document, into_result, resolve_document, ManifestParser, ManifestPipeline, ParsingError,
+};
+use crate::parser::template::{NoTemplates, TemplateSource};
+
+/// Parses TOML manifests, for tools generating pipelines. They have the same keys as yaml ones, e.g. `[actions.build]`.
+#[derive(Clone, Default)]
+pub struct TomlParser {
+    pub strict: bool, // Reject unknown keys rather than only warning about them
+}
+
+impl TomlParser {
+    fn load(&self, toml: &str, diagnostics: &mut Diagnostics) -> Option<Yaml> {
+        match toml::from_str::<Document>(toml) {
+            Ok(Document(doc)) => Some(doc),
+            Err(e) => {
+                let offset = e.span().map_or(0, |span| span.start);
+                let (line, column) = diagnostic::line_column(toml, offset);
+                diagnostics.syntax_error_at(e.message().to_string(), line, column);
+                None
+            }
+        }
+    }
+
+    /// Only syntax errors are located in TOML manifests, the other diagnostics only have a path.
+    fn check(
+        &self,
+        toml: &str,
+        templates: &dyn TemplateSource,
+    ) -> (Option<ManifestPipeline>, Diagnostics) {
+        let mut diagnostics = Diagnostics::without_positions();
+        let pipeline = self
+            .load(toml, &mut diagnostics)
+            .and_then(|doc| check_document(doc, templates, self.strict, &mut diagnostics));
+        (pipeline, diagnostics)
+    }
+}
+
+impl ManifestParser for TomlParser {
+    fn parse(&self, toml: String) -> Result<ManifestPipeline, ParsingError> {
+        self.parse_with_templates(toml, &NoTemplates)
+    }
+
+    fn parse_with_templates(
+        &self,
+        toml: String,
+        templates: &dyn TemplateSource,
+    ) -> Result<ManifestPipeline, ParsingError> {
+        let (pipeline, diagnostics) = self.check(&toml, templates);
+        into_result(pipeline, diagnostics)
+    }
+
+    fn resolve(
+        &self,
+        toml: String,
+        templates: &dyn TemplateSource,
+    ) -> Result<String, ParsingError> {
+        let Document(doc) = toml::from_str(&toml).map_err(|_| ParsingError::YamlNotCompliant)?;
+        resolve_document(doc, templates)
+    }
+
+    fn validate(&self, toml: String, templates: &dyn TemplateSource) -> Vec<Diagnostic> {
+        let (_, diagnostics) = self.check(&toml, templates);
+        diagnostics.into_diagnostics()
+    }
+}
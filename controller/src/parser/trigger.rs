@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+
+/// What triggered a pipeline: the `on:` filter of the pipeline and the `if:` expressions of its actions are evaluated against it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Trigger {
+    pub event: String,
+    pub branch: Option<String>,
+    pub tag: Option<String>,
+    pub changed_paths: Vec<String>,
+}
+
+impl Default for Trigger {
+    fn default() -> Self {
+        Self {
+            event: String::from("push"),
+            branch: None,
+            tag: None,
+            changed_paths: Vec::new(),
+        }
+    }
+}
+
+/// The `on:` section of a pipeline: the pipeline only runs for the triggers it matches. An empty list matches anything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TriggerFilter {
+    pub events: Vec<String>,
+    pub branches: Vec<String>, // Glob patterns, e.g. `release/*`
+    pub tags: Vec<String>,     // Glob patterns, e.g. `v*`
+    pub paths: Vec<String>,    // Glob patterns, at least one changed path must match
+}
+
+impl TriggerFilter {
+    pub fn matches(&self, trigger: &Trigger) -> bool {
+        let event_matches = self.events.is_empty() || self.events.contains(&trigger.event);
+
+        // A trigger is either on a branch or on a tag: it matches when either filter does.
+        let matches_any = |patterns: &[String], name: &Option<String>| {
+            name.as_ref()
+                .is_some_and(|name| patterns.iter().any(|pattern| glob_matches(pattern, name)))
+        };
+        let ref_matches = (self.branches.is_empty() && self.tags.is_empty())
+            || matches_any(&self.branches, &trigger.branch)
+            || matches_any(&self.tags, &trigger.tag);
+
+        let paths_matches = self.paths.is_empty()
+            || trigger.changed_paths.iter().any(|path| {
+                self.paths
+                    .iter()
+                    .any(|pattern| glob_matches(pattern, path))
+            });
+
+        event_matches && ref_matches && paths_matches
+    }
+}
+
+/// Match a path against a glob pattern: `*` and `?` match within a path segment, `**` matches across segments.
+pub fn glob_matches(pattern: &str, path: &str) -> bool {
+    fn matches(pattern: &[char], path: &[char]) -> bool {
+        match pattern {
+            [] => path.is_empty(),
+            ['*', '*', rest @ ..] => {
+                // `**/` also matches no directory at all.
+                let rest_without_slash = rest.strip_prefix(&['/']).unwrap_or(rest);
+                (0..=path.len()).any(|i| matches(rest, &path[i..]))
+                    || matches(rest_without_slash, path)
+            }
+            ['*', rest @ ..] => {
+                let segment = path.iter().position(|c| *c == '/').unwrap_or(path.len());
+                (0..=segment).any(|i| matches(rest, &path[i..]))
+            }
+            ['?', rest @ ..] => path
+                .first()
+                .is_some_and(|c| *c != '/' && matches(rest, &path[1..])),
+            [c, rest @ ..] => path.first() == Some(c) && matches(rest, &path[1..]),
+        }
+    }
+
+    let pattern: Vec<char> = pattern.chars().collect();
+    let path: Vec<char> = path.chars().collect();
+    matches(&pattern, &path)
+}
@@ -1,7 +1,11 @@
-use crate::action::action_repository::Action;
+use crate::action::{action_event::ActionEvent, action_repository::Action};
+use crate::domain::entities::action::ActionStatus;
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use time::OffsetDateTime;
 
 pub mod pipeline_controller;
+pub mod pipeline_executor;
 pub mod pipeline_repository;
 pub mod pipeline_service;
 
@@ -11,15 +15,91 @@ pub struct Pipeline {
     pub repository_url: String,
     pub name: String,
     pub actions: Vec<Action>,
+    #[serde(with = "time::serde::rfc3339::option", default)]
+    pub created_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option", default)]
+    pub started_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option", default)]
+    pub finished_at: Option<OffsetDateTime>,
+    /// Time between the start of the first action and the end of the last one, in milliseconds. None until every action is done.
+    #[serde(default)]
+    pub duration: Option<i64>,
+    /// Every status change of the actions, oldest first.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub timeline: Option<Vec<ActionEvent>>,
+    /// Sha256 of the manifest the pipeline was created from, in hex.
+    #[serde(default)]
+    pub manifest_hash: Option<String>,
+    /// The manifest the pipeline was created from, as it was sent or read from the repository.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub manifest: Option<String>,
+    #[serde(flatten)]
+    pub metadata: PipelineMetadata,
+}
+
+/// A page of the pipelines listing, along with the cursor of the next page when there is one.
+#[derive(Debug, Clone, Serialize)]
+pub struct PipelinePage {
+    pub pipelines: Vec<Pipeline>,
+    pub next_cursor: Option<String>,
+}
+
+/// What triggered a pipeline, and on which revision.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct PipelineMetadata {
+    pub commit_sha: Option<String>,
+    #[serde(rename = "ref")]
+    pub git_ref: Option<String>, // e.g. `refs/heads/main`
+    pub event: Option<String>,   // e.g. `push`
+    pub trigger: Option<String>, // What sent the pipeline, e.g. `monitor`
+    pub requester: Option<String>, // Who the pipeline was run for, e.g. the author of the commit
 }
 
 impl Pipeline {
     pub fn new(id: i64, repository_url: String, name: String, actions: Vec<Action>) -> Self {
+        // The pipeline starts with its first action, and is finished once all of them are done.
+        let started_at = actions.iter().filter_map(|action| action.started_at).min();
+        let done = actions.iter().all(|action| {
+            ActionStatus::from_str(action.status()).is_ok_and(|status| status.is_terminal())
+        });
+        let finished_at = match done {
+            true => actions.iter().filter_map(|action| action.finished_at).max(),
+            false => None,
+        };
+        let duration = match (started_at, finished_at) {
+            (Some(started_at), Some(finished_at)) => {
+                Some((finished_at - started_at).whole_milliseconds() as i64)
+            }
+            _ => None,
+        };
         return Pipeline {
             id,
             repository_url,
             name,
             actions,
+            created_at: None,
+            started_at,
+            finished_at,
+            duration,
+            timeline: None,
+            manifest_hash: None,
+            manifest: None,
+            metadata: PipelineMetadata::default(),
         };
     }
+
+    pub fn with_metadata(mut self, metadata: PipelineMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    pub fn with_created_at(mut self, created_at: OffsetDateTime) -> Self {
+        self.created_at = Some(created_at);
+        self
+    }
+
+    pub fn with_manifest_hash(mut self, manifest_hash: Option<String>) -> Self {
+        self.manifest_hash = manifest_hash;
+        self
+    }
 }
@@ -1,20 +1,78 @@
 use actix_multipart::form::{tempfile::TempFile, text::Text as MpText, MultipartForm};
 use actix_web::{
-    get, post,
-    web::{self},
-    HttpResponse, Responder,
+    get,
+    http::header::CACHE_CONTROL,
+    post,
+    web::{self, Bytes},
+    HttpRequest, HttpResponse, Responder,
 };
-use serde::Deserialize;
-use std::{io::Read, sync::Arc};
-use tracing::info;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::{io::Read, str::FromStr, sync::Arc};
+use time::OffsetDateTime;
+use tracing::{error, info};
 
-use crate::{parser::pipe_parser::ParsingError, pipeline::pipeline_service::PipelineService};
+use crate::{
+    domain::entities::action::ActionStatus,
+    git::repository_cache::GitError,
+    logs::log_hub::LogStreamEvent,
+    parser::{
+        diagnostic::{Diagnostic, Severity},
+        format::ManifestFormat,
+        trigger::Trigger,
+    },
+    pipeline::{
+        pipeline_repository::{PipelineCursor, PipelineFilter, PipelineSort, SortOrder},
+        pipeline_service::PipelineService,
+        PipelineMetadata,
+    },
+};
+
+/// Number of pipelines of a page, when not given.
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: i64 = 100;
 
 #[derive(Debug, MultipartForm)]
 struct UploadPipelineForm {
+    // Without a manifest, the one of the repository at `ref` is used.
     #[multipart(rename = "body")]
-    file: TempFile,
+    file: Option<TempFile>,
     repo_url: MpText<String>,
+    #[multipart(rename = "ref")]
+    git_ref: Option<MpText<String>>,
+    // What triggered the pipeline, for its `on:` filter and the `if:` conditions of its actions.
+    event: Option<MpText<String>>,
+    branch: Option<MpText<String>>,
+    tag: Option<MpText<String>>,
+    changed_paths: Vec<MpText<String>>,
+    // Stored with the pipeline, to tell where it comes from.
+    commit_sha: Option<MpText<String>>,
+    trigger: Option<MpText<String>>,
+    requester: Option<MpText<String>>,
+}
+
+#[derive(Debug, MultipartForm)]
+struct ManifestForm {
+    #[multipart(rename = "body")]
+    file: TempFile,
+}
+
+/// What is wrong with a manifest, if anything. It is valid as long as it has no error, whatever its warnings.
+#[derive(Serialize)]
+struct ValidationReport {
+    valid: bool,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl ValidationReport {
+    fn new(diagnostics: Vec<Diagnostic>) -> Self {
+        Self {
+            valid: !diagnostics
+                .iter()
+                .any(|diagnostic| diagnostic.severity == Severity::Error),
+            diagnostics,
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -27,14 +85,90 @@ struct PipelineQueryParams {
     verbose: Option<bool>,
 }
 
+#[derive(Deserialize)]
+struct PipelineListQueryParams {
+    verbose: Option<bool>,
+    cursor: Option<String>,
+    limit: Option<i64>,
+    sort: Option<PipelineSort>,
+    order: Option<SortOrder>,
+    repository: Option<String>,
+    name: Option<String>,
+    status: Option<String>,
+    commit_sha: Option<String>,
+    #[serde(with = "time::serde::rfc3339::option", default)]
+    created_after: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option", default)]
+    created_before: Option<OffsetDateTime>,
+}
+
+#[derive(Deserialize)]
+struct ActionLogsPath {
+    id: i64,
+    action_id: i64,
+}
+
+#[derive(Deserialize)]
+struct LogStreamQueryParams {
+    offset: Option<i64>,
+}
+
+/// List the pipelines matching the given filters, a page at a time. The next page is asked for with the `next_cursor` of the current one.
 #[get("/pipeline")]
 pub async fn get_pipelines(
     pipeline_service: web::Data<Arc<PipelineService>>,
-    query: web::Query<PipelineQueryParams>,
+    query: web::Query<PipelineListQueryParams>,
 ) -> impl Responder {
-    let verbose = query.verbose.unwrap_or(false);
-    let pipelines = pipeline_service.find_all(verbose).await;
-    HttpResponse::Ok().json(pipelines)
+    let query = query.into_inner();
+    let cursor = match query.cursor.as_deref().map(PipelineCursor::decode) {
+        Some(None) => return HttpResponse::BadRequest().body("Invalid cursor"),
+        Some(Some(cursor)) => Some(cursor),
+        None => None,
+    };
+    let status = match query.status.as_deref().map(parse_status) {
+        Some(None) => return HttpResponse::BadRequest().body("Unknown status"),
+        Some(Some(status)) => Some(status.as_str_name().to_string()),
+        None => None,
+    };
+    if let Some(commit_sha) = &query.commit_sha {
+        if commit_sha.is_empty() || !commit_sha.chars().all(|c| c.is_ascii_hexdigit()) {
+            return HttpResponse::BadRequest().body("Invalid commit sha");
+        }
+    }
+    let filter = PipelineFilter {
+        repository: query.repository,
+        name: query.name,
+        status,
+        commit_sha: query.commit_sha.map(|commit_sha| commit_sha.to_ascii_lowercase()),
+        created_after: query.created_after,
+        created_before: query.created_before,
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    match pipeline_service
+        .find_page(
+            &filter,
+            query.sort.unwrap_or_default(),
+            query.order.unwrap_or_default(),
+            cursor.as_ref(),
+            limit,
+            query.verbose.unwrap_or(false),
+        )
+        .await
+    {
+        Ok(page) => HttpResponse::Ok().json(page),
+        Err(e) => {
+            error!("Error while fetching pipelines: {:?}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// A status, either as it is stored, e.g. `ACTION_STATUS_TIMED_OUT`, or by its short name, e.g. `TIMED_OUT`.
+fn parse_status(status: &str) -> Option<ActionStatus> {
+    ActionStatus::from_str(status)
+        .or_else(|_| ActionStatus::from_str(&format!("ACTION_STATUS_{}", status.to_ascii_uppercase())))
+        .ok()
 }
 
 #[get("/pipeline/{id}")]
@@ -53,59 +187,217 @@ pub async fn get_pipeline(
     }
 }
 
+/// Stream the logs of an action as Server-Sent Events: the stored ones from `offset` on, then the new ones, until the action is done.
+#[get("/pipeline/{id}/actions/{action_id}/logs/stream")]
+pub async fn stream_action_logs(
+    path: web::Path<ActionLogsPath>,
+    query: web::Query<LogStreamQueryParams>,
+    request: HttpRequest,
+    pipeline_service: web::Data<Arc<PipelineService>>,
+) -> impl Responder {
+    let offset = resume_offset(&request, query.offset);
+    match pipeline_service
+        .stream_logs(path.id, path.action_id, offset)
+        .await
+    {
+        Some(stream) => HttpResponse::Ok()
+            .content_type("text/event-stream")
+            .insert_header((CACHE_CONTROL, "no-cache"))
+            .streaming(stream.map(|event| Ok::<_, actix_web::Error>(sse_event(&event)))),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+/// Where a log stream starts. A client reconnecting on its own tells the last log it got, which wins over the `offset`
+/// of the URL it first opened the stream with.
+pub(crate) fn resume_offset(request: &HttpRequest, offset: Option<i64>) -> i64 {
+    let last_event_id = request
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|id| id.to_str().ok())
+        .and_then(|id| id.parse::<i64>().ok());
+    last_event_id.map(|id| id + 1).or(offset).unwrap_or(0)
+}
+
+/// An event of a log stream, in the Server-Sent Events format. Logs have their offset as id, to resume the stream after them.
+fn sse_event(event: &LogStreamEvent) -> Bytes {
+    let data = serde_json::to_string(event).unwrap_or_default();
+    let frame = match event {
+        LogStreamEvent::Log { offset, .. } => {
+            format!("id: {}\nevent: log\ndata: {}\n\n", offset, data)
+        }
+        LogStreamEvent::End { .. } => format!("event: end\ndata: {}\n\n", data),
+    };
+    Bytes::from(frame)
+}
+
 #[post("/pipeline")]
 pub async fn create_pipeline(
     MultipartForm(form): MultipartForm<UploadPipelineForm>,
     pipeline_service: web::Data<Arc<PipelineService>>,
 ) -> impl Responder {
-    info!(
-        "Uploaded file {} with repository {}",
-        form.file.size,
-        form.repo_url.as_str()
-    );
     let repo_url = form.repo_url.as_str();
-    let f = form.file;
-    let file_name = match f.file_name {
-        Some(file_name) => file_name,
-        None => return HttpResponse::UnprocessableEntity().body("Invalid file name"),
+    let git_ref = form.git_ref.map(|git_ref| git_ref.into_inner());
+    // A full ref tells the branch or the tag, when they are not given.
+    let from_ref = |prefix: &str| {
+        git_ref
+            .as_deref()
+            .and_then(|git_ref| git_ref.strip_prefix(prefix))
+            .map(str::to_string)
     };
-    let _path = format!("./tmp/{}", file_name);
-    let mut fd_manifest = match f.file.reopen() {
-        Ok(fd) => fd,
-        Err(_) => return HttpResponse::InternalServerError().finish(),
+    let trigger = Trigger {
+        event: form
+            .event
+            .map(|event| event.into_inner())
+            .unwrap_or_else(|| Trigger::default().event),
+        branch: form
+            .branch
+            .map(|branch| branch.into_inner())
+            .or_else(|| from_ref("refs/heads/")),
+        tag: form
+            .tag
+            .map(|tag| tag.into_inner())
+            .or_else(|| from_ref("refs/tags/")),
+        changed_paths: form
+            .changed_paths
+            .into_iter()
+            .map(|path| path.into_inner())
+            .collect(),
     };
-    let mut buffer = String::new();
-    match fd_manifest.read_to_string(&mut buffer) {
-        Err(e) => {
-            if e.kind() == std::io::ErrorKind::InvalidData {
-                return HttpResponse::UnprocessableEntity().body("Invalid data");
+    let mut metadata = PipelineMetadata {
+        commit_sha: form
+            .commit_sha
+            .map(|commit_sha| commit_sha.into_inner().to_ascii_lowercase()),
+        git_ref: git_ref
+            .clone()
+            .or_else(|| match (&trigger.branch, &trigger.tag) {
+                (Some(branch), _) => Some(format!("refs/heads/{}", branch)),
+                (None, Some(tag)) => Some(format!("refs/tags/{}", tag)),
+                (None, None) => None,
+            }),
+        event: Some(trigger.event.clone()),
+        trigger: form.trigger.map(|trigger| trigger.into_inner()),
+        requester: form.requester.map(|requester| requester.into_inner()),
+    };
+
+    // The commit sha is the exact revision, the ref may have moved since.
+    let revision = metadata.commit_sha.clone().or(git_ref);
+    let (buffer, format) = match (form.file, revision.as_deref()) {
+        (Some(f), _) => {
+            info!("Uploaded file {} with repository {}", f.size, repo_url);
+            match read_manifest(ManifestForm { file: f }) {
+                Ok(manifest) => manifest,
+                Err(response) => return response,
+            }
+        }
+        (None, Some(revision)) => {
+            info!(
+                "Fetching manifest of repository {} at {}",
+                repo_url, revision
+            );
+            match pipeline_service.fetch_manifest(repo_url, revision).await {
+                Ok(fetched) => {
+                    info!("Fetched manifest of commit {}", fetched.commit);
+                    metadata.commit_sha = Some(fetched.commit);
+                    (fetched.content, ManifestFormat::Yaml)
+                }
+                Err(GitError::Io(e)) => {
+                    info!("Error while fetching manifest: {:?}", e);
+                    return HttpResponse::InternalServerError().finish();
+                }
+                Err(e) => return HttpResponse::UnprocessableEntity().body(e.to_string()),
             }
         }
-        Ok(_) => {
-            println!("Read {} bytes", buffer);
+        (None, None) => {
+            return HttpResponse::UnprocessableEntity()
+                .body("Either a manifest or a ref is required")
         }
-    }
+    };
 
-    match pipeline_service.try_parse_pipeline(buffer) {
+    match pipeline_service.try_parse_pipeline(buffer.clone(), format) {
         Ok(workflow) => {
+            let max_parallelism = workflow.max_parallelism;
+            let on = workflow.on.clone();
             if let Ok(pipeline) = pipeline_service
-                .create_pipeline_with_actions(workflow, repo_url.to_string())
+                .create_pipeline_with_actions(
+                    workflow,
+                    repo_url.to_string(),
+                    &buffer,
+                    &metadata,
+                )
                 .await
             {
-                for action in &pipeline.actions {
-                    info!("Sending action: {:?}", action);
-                    pipeline_service
-                        .send_action(Arc::new(action.clone()), repo_url.to_string())
-                        .await
-                        .unwrap();
-                }
+                pipeline_service.execute_pipeline(&pipeline, max_parallelism, &on, trigger);
                 return HttpResponse::Ok().json(pipeline);
             } else {
                 info!("Error while creating pipeline");
                 return HttpResponse::InternalServerError().finish();
             }
         }
-        Err(ParsingError::YamlNotCompliant) => HttpResponse::BadRequest().body("Invalid yaml"),
-        Err(err) => HttpResponse::BadRequest().body(format!("{:?}", err)), //TODO: replace this by exhaustive match
+        Err(err) => {
+            info!("Invalid manifest: {}", err);
+            let report = ValidationReport::new(pipeline_service.validate_pipeline(buffer, format));
+            HttpResponse::BadRequest().json(report)
+        }
+    }
+}
+
+/// Show a manifest once its templates are included and its actions extended, without creating any pipeline.
+#[post("/pipeline/resolve")]
+pub async fn resolve_pipeline(
+    MultipartForm(form): MultipartForm<ManifestForm>,
+    pipeline_service: web::Data<Arc<PipelineService>>,
+) -> impl Responder {
+    let (buffer, format) = match read_manifest(form) {
+        Ok(manifest) => manifest,
+        Err(response) => return response,
+    };
+
+    match pipeline_service.resolve_pipeline(buffer.clone(), format) {
+        Ok(manifest) => HttpResponse::Ok().content_type("application/yaml").body(manifest),
+        Err(err) => {
+            info!("Invalid manifest: {}", err);
+            let report = ValidationReport::new(pipeline_service.validate_pipeline(buffer, format));
+            HttpResponse::BadRequest().json(report)
+        }
+    }
+}
+
+/// Check a manifest, including its templates, and report everything wrong with it, without creating any pipeline.
+#[post("/pipeline/validate")]
+pub async fn validate_pipeline(
+    MultipartForm(form): MultipartForm<ManifestForm>,
+    pipeline_service: web::Data<Arc<PipelineService>>,
+) -> impl Responder {
+    let (buffer, format) = match read_manifest(form) {
+        Ok(manifest) => manifest,
+        Err(response) => return response,
+    };
+    HttpResponse::Ok().json(ValidationReport::new(
+        pipeline_service.validate_pipeline(buffer, format),
+    ))
+}
+
+/// The content of a manifest, along with its format.
+fn read_manifest(form: ManifestForm) -> Result<(String, ManifestFormat), HttpResponse> {
+    let format = manifest_format(&form.file);
+    let mut buffer = String::new();
+    let read = form
+        .file
+        .file
+        .reopen()
+        .and_then(|mut fd_manifest| fd_manifest.read_to_string(&mut buffer));
+    if let Err(e) = read {
+        info!("Error while reading manifest: {:?}", e);
+        return Err(HttpResponse::UnprocessableEntity().body("Invalid data"));
     }
+    Ok((buffer, format))
+}
+
+/// The format of a manifest file, from its name or its content type.
+fn manifest_format(file: &TempFile) -> ManifestFormat {
+    ManifestFormat::detect(
+        file.file_name.as_deref(),
+        file.content_type.as_ref().map(|mime| mime.essence_str()),
+    )
 }
@@ -0,0 +1,299 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use tokio::task::JoinSet;
+use tracing::{error, info, warn};
+
+use crate::{
+    action::{action_event::EventSource, action_repository::Action, action_service::ActionService},
+    domain::entities::action::ActionStatus as DomainActionStatus,
+    grpc_scheduler::ActionStatus,
+    parser::{
+        expression::{EvaluationContext, Expression},
+        trigger::Trigger,
+    },
+    scheduler::SchedulerService,
+};
+
+/// Runs the actions of a pipeline along the graph of their `needs`: an action is sent to the scheduler once all the actions it needs have completed.
+/// Independent actions run at the same time, up to the max parallelism of the pipeline. When an action fails, the actions needing it, directly or not, are skipped.
+/// An action with an `if:` condition is instead sent once all the actions it needs have ended, if its condition holds, and skipped otherwise.
+/// A failing action is sent again as long as its retry policy allows it, each attempt keeping its own logs.
+#[derive(Clone)]
+pub struct PipelineExecutor {
+    client: Arc<SchedulerService>,
+    action_service: Arc<ActionService>,
+}
+
+impl PipelineExecutor {
+    pub fn new(client: Arc<SchedulerService>, action_service: Arc<ActionService>) -> Self {
+        Self {
+            client,
+            action_service,
+        }
+    }
+
+    pub async fn execute(
+        &self,
+        actions: Vec<Action>,
+        repo_url: String,
+        max_parallelism: Option<usize>,
+        trigger: Trigger,
+    ) {
+        let max_parallelism = max_parallelism.unwrap_or(usize::MAX).max(1);
+        // Actions not started yet, in the order of the manifest, the status the others ended in, and the ones after a failure.
+        let mut waiting = actions;
+        let mut ended: HashMap<String, ActionStatus> = HashMap::new();
+        let mut after_failure: HashSet<String> = HashSet::new();
+        let mut running = JoinSet::new();
+
+        loop {
+            // Once all the actions it needs have ended, an action either starts, when there is room, or is skipped when its condition does not hold.
+            // Skipping an action may let others be decided on, so go through the waiting actions until none is.
+            let mut decided = true;
+            while decided {
+                decided = false;
+                let mut index = 0;
+                while index < waiting.len() {
+                    let action = &waiting[index];
+                    if !action.needs.iter().all(|need| ended.contains_key(need)) {
+                        index += 1;
+                        continue;
+                    }
+                    let needs_failed = action.needs.iter().any(|need| {
+                        after_failure.contains(need) || ended.get(need).is_some_and(is_failure)
+                    });
+                    let context = EvaluationContext {
+                        trigger: &trigger,
+                        needs_completed: action
+                            .needs
+                            .iter()
+                            .all(|need| ended.get(need) == Some(&ActionStatus::Completed)),
+                        needs_failed,
+                    };
+                    let holds = match &action.condition {
+                        Some(condition) => match Expression::parse(condition) {
+                            Ok(expression) => expression.holds(&context),
+                            Err(e) => {
+                                error!("Invalid condition of action {}: {:?}", action.id, e);
+                                false
+                            }
+                        },
+                        None => context.needs_completed,
+                    };
+                    if holds && running.len() >= max_parallelism {
+                        index += 1;
+                        continue;
+                    }
+
+                    let action = waiting.remove(index);
+                    decided = true;
+                    if needs_failed {
+                        after_failure.insert(action.name.clone());
+                    }
+                    if !holds {
+                        info!("Skipping action {}: its condition does not hold", action.id);
+                        self.skip(action.id).await;
+                        ended.insert(action.name, ActionStatus::Skipped);
+                        continue;
+                    }
+                    let executor = self.clone();
+                    let repo_url = repo_url.clone();
+                    running.spawn(async move {
+                        let name = action.name.clone();
+                        (name, executor.run(action, repo_url).await)
+                    });
+                }
+            }
+
+            let Some(result) = running.join_next().await else {
+                break;
+            };
+            match result {
+                Ok((name, status)) => {
+                    if status != ActionStatus::Completed {
+                        info!("Action {} ended as {}", name, status.as_str_name());
+                    }
+                    ended.insert(name, status);
+                }
+                Err(e) => error!("Action task failed: {:?}", e),
+            }
+        }
+
+        // Nothing runs anymore: whatever is still waiting can never start.
+        for action in waiting {
+            warn!("Action {} never became ready, skipping it", action.id);
+            self.skip(action.id).await;
+        }
+    }
+
+    /// Skip every action of a pipeline, e.g. when it does not run for its trigger.
+    pub async fn skip_all(&self, actions: Vec<Action>) {
+        for action in actions {
+            self.skip(action.id).await;
+        }
+    }
+
+    /// Run an action, again as long as its retry policy allows it, then return the status it ended in.
+    /// Between two attempts the action waits as pending: the failure of an attempt is only stored once the action is not retried anymore.
+    async fn run(&self, mut action: Action, repo_url: String) -> ActionStatus {
+        loop {
+            let (status, source, exit_code) = match self.attempt(&action, &repo_url).await {
+                Attempt::Ended(status) => return status,
+                Attempt::Failed {
+                    status,
+                    source,
+                    exit_code,
+                } => (status, source, exit_code),
+            };
+            if !action.retry.allows_retry(action.attempt as u32, exit_code) {
+                return self.end(action.id, status, source).await;
+            }
+
+            let attempt = action.attempt + 1;
+            match self.action_service.retry(action.id, attempt).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    warn!(
+                        "Action {} cannot be retried from its current status",
+                        action.id
+                    );
+                    return self.end(action.id, status, source).await;
+                }
+                Err(e) => {
+                    error!("Error while retrying action {}: {:?}", action.id, e);
+                    return self.end(action.id, status, source).await;
+                }
+            }
+            let backoff = action.retry.backoff_before(attempt as u32);
+            info!(
+                "Action {} ended as {} on attempt {}, retrying in {:?}",
+                action.id,
+                status.as_str_name(),
+                action.attempt,
+                backoff
+            );
+            tokio::time::sleep(backoff).await;
+            action.attempt = attempt;
+
+            // The action may have been cancelled while waiting.
+            match self.action_service.find_status(action.id).await {
+                Ok(status) if DomainActionStatus::from(status).is_terminal() => return status,
+                Ok(_) => {}
+                Err(e) => {
+                    error!(
+                        "Error while fetching the status of action {}: {:?}",
+                        action.id, e
+                    );
+                    return ActionStatus::Error;
+                }
+            }
+        }
+    }
+
+    /// Send the current attempt at an action to the scheduler and wait for it to finish.
+    async fn attempt(&self, action: &Action, repo_url: &str) -> Attempt {
+        let action_id = action.id;
+        info!("Sending action: {:?}", action);
+        let end = match self
+            .client
+            .send_action(Arc::new(action.clone()), repo_url.to_string())
+            .await
+        {
+            Ok(end) => end,
+            Err(err) => {
+                error!("Error sending action {}: {:?}", action_id, err);
+                return Attempt::failed_here(None);
+            }
+        };
+        if let Some((status, source)) = end.failure {
+            return Attempt::Failed {
+                status,
+                source,
+                exit_code: end.exit_code,
+            };
+        }
+
+        match self.action_service.find_status(action_id).await {
+            Ok(status) if DomainActionStatus::from(status).is_terminal() => Attempt::Ended(status),
+            Ok(status) => {
+                warn!(
+                    "Action {} is still {} once the scheduler is done with it",
+                    action_id,
+                    status.as_str_name()
+                );
+                Attempt::failed_here(end.exit_code)
+            }
+            Err(e) => {
+                error!(
+                    "Error while fetching the status of action {}: {:?}",
+                    action_id, e
+                );
+                Attempt::failed_here(end.exit_code)
+            }
+        }
+    }
+
+    /// Store the failure an action ended in, then return the status it is in: it may have ended otherwise meanwhile, e.g. been cancelled.
+    async fn end(&self, action_id: i64, status: ActionStatus, source: EventSource) -> ActionStatus {
+        match self
+            .action_service
+            .update_status(action_id, &status, source)
+            .await
+        {
+            Ok(true) => status,
+            Ok(false) => self
+                .action_service
+                .find_status(action_id)
+                .await
+                .unwrap_or(status),
+            Err(e) => {
+                error!("Error while updating action status: {:?}", e);
+                status
+            }
+        }
+    }
+
+    async fn skip(&self, action_id: i64) {
+        if let Err(e) = self
+            .action_service
+            .update_status(action_id, &ActionStatus::Skipped, EventSource::Controller)
+            .await
+        {
+            error!("Error while updating action status: {:?}", e);
+        }
+    }
+}
+
+/// How an attempt at an action ended.
+enum Attempt {
+    /// The action ended in this status, already stored.
+    Ended(ActionStatus),
+    /// The attempt failed, which is only stored once the action is not retried.
+    Failed {
+        status: ActionStatus,
+        source: EventSource,
+        exit_code: Option<i32>,
+    },
+}
+
+impl Attempt {
+    /// The attempt failed because of the controller, e.g. when it cannot reach the scheduler.
+    fn failed_here(exit_code: Option<i32>) -> Self {
+        Attempt::Failed {
+            status: ActionStatus::Error,
+            source: EventSource::Controller,
+            exit_code,
+        }
+    }
+}
+
+/// Whether an action ended because it went wrong, rather than completing or being skipped.
+fn is_failure(status: &ActionStatus) -> bool {
+    matches!(
+        status,
+        ActionStatus::Error | ActionStatus::TimedOut | ActionStatus::Cancelled
+    )
+}
@@ -1,34 +1,139 @@
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
 use std::{collections::HashMap, str::FromStr, sync::Arc};
+use time::OffsetDateTime;
 
-use crate::{action::action_repository::Action, parser::pipe_parser::Type};
+use crate::{
+    action::action_repository::Action,
+    parser::pipe_parser::{RetryPolicy, Type},
+};
 
-use super::Pipeline;
+use super::{Pipeline, PipelineMetadata};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PipelineDTO {
     pub id: i64,
     pub name: String,
     pub repository_url: String,
+    pub manifest: Option<String>,
+    pub manifest_hash: Option<String>,
+    pub commit_sha: Option<String>,
+    pub git_ref: Option<String>,
+    pub event: Option<String>,
+    pub trigger: Option<String>,
+    pub requester: Option<String>,
+    pub created_at: OffsetDateTime,
+}
+
+impl PipelineDTO {
+    pub fn metadata(&self) -> PipelineMetadata {
+        PipelineMetadata {
+            commit_sha: self.commit_sha.clone(),
+            git_ref: self.git_ref.clone(),
+            event: self.event.clone(),
+            trigger: self.trigger.clone(),
+            requester: self.requester.clone(),
+        }
+    }
 }
 
 pub struct PipelineDetailDTO {
     pub pipeline_id: i64,
     pub pipeline_name: String,
+    pub pipeline_created_at: OffsetDateTime,
     pub repository_url: String,
+    pub manifest_hash: Option<String>,
+    pub commit_sha: Option<String>,
+    pub git_ref: Option<String>,
+    pub event: Option<String>,
+    pub trigger: Option<String>,
+    pub requester: Option<String>,
     pub action_id: i64,
     pub action_name: String,
     pub action_container_uri: String,
     pub action_status: String,
     pub action_type: String,
+    pub action_runs_on: Vec<String>,
+    pub action_needs: Vec<String>,
+    pub action_env: Vec<String>,
+    pub action_condition: Option<String>,
+    pub action_attempt: i32,
+    pub action_max_attempts: i32,
+    pub action_retry_backoff: i32,
+    pub action_retry_on: Vec<i32>,
+    pub action_priority: i32,
+    pub action_started_at: Option<OffsetDateTime>,
+    pub action_finished_at: Option<OffsetDateTime>,
     pub command: String,
 }
 
+impl PipelineDetailDTO {
+    fn metadata(&self) -> PipelineMetadata {
+        PipelineMetadata {
+            commit_sha: self.commit_sha.clone(),
+            git_ref: self.git_ref.clone(),
+            event: self.event.clone(),
+            trigger: self.trigger.clone(),
+            requester: self.requester.clone(),
+        }
+    }
+}
+
+/// Filters of the pipelines listing, a pipeline matching all of the given ones.
+#[derive(Debug, Clone, Default)]
+pub struct PipelineFilter {
+    pub repository: Option<String>,
+    pub name: Option<String>,
+    pub status: Option<String>, // Pipelines having an action in this status, e.g. `ACTION_STATUS_RUNNING`
+    pub commit_sha: Option<String>, // Full sha, or its first digits
+    pub created_after: Option<OffsetDateTime>,
+    pub created_before: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PipelineSort {
+    #[default]
+    CreatedAt,
+    Name,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc, // Latest pipelines first
+}
+
+/// The last pipeline of a page, the next page starting right after it. Pipelines with the same sort key are told apart by their id.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct PipelineCursor {
+    pub id: i64,
+    pub name: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+impl PipelineCursor {
+    /// The cursor as an opaque token, for clients to send back as is.
+    pub fn encode(&self) -> String {
+        hex::encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    pub fn decode(token: &str) -> Option<Self> {
+        let bytes = hex::decode(token).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+}
+
 struct ActionDetail {
     repository_url: String,
     pipeline_id: i64,
     pipeline_name: String,
+    pipeline_created_at: OffsetDateTime,
+    manifest_hash: Option<String>,
+    metadata: PipelineMetadata,
     action: Action,
 }
 
@@ -41,22 +146,117 @@ impl PipelineRepository {
         Self { pool }
     }
 
-    pub async fn find_all(&self) -> Result<Vec<Pipeline>, sqlx::Error> {
+    /// A page of the pipelines matching a filter, in the given order, starting after the cursor if any.
+    /// Return the pipelines and the cursor of the next page, if there is one.
+    pub async fn find_page(
+        &self,
+        filter: &PipelineFilter,
+        sort: PipelineSort,
+        order: SortOrder,
+        cursor: Option<&PipelineCursor>,
+        limit: i64,
+    ) -> Result<(Vec<Pipeline>, Option<PipelineCursor>), sqlx::Error> {
+        let mut query: QueryBuilder<Postgres> =
+            QueryBuilder::new("SELECT p.id, p.name, p.created_at FROM pipelines p WHERE TRUE");
+        if let Some(repository) = &filter.repository {
+            query.push(" AND p.repository_url = ").push_bind(repository);
+        }
+        if let Some(name) = &filter.name {
+            query.push(" AND p.name = ").push_bind(name);
+        }
+        if let Some(commit_sha) = &filter.commit_sha {
+            // A commit sha only has hex digits, it cannot hold any pattern.
+            query
+                .push(" AND p.commit_sha LIKE ")
+                .push_bind(format!("{}%", commit_sha));
+        }
+        if let Some(created_after) = filter.created_after {
+            query.push(" AND p.created_at >= ").push_bind(created_after);
+        }
+        if let Some(created_before) = filter.created_before {
+            query.push(" AND p.created_at < ").push_bind(created_before);
+        }
+        if let Some(status) = &filter.status {
+            query
+                .push(" AND EXISTS (SELECT 1 FROM actions a WHERE a.pipeline_id = p.id AND a.status = ")
+                .push_bind(status)
+                .push(")");
+        }
+        let (key, comparison, direction) = match (sort, order) {
+            (PipelineSort::CreatedAt, SortOrder::Asc) => ("p.created_at", ">", "ASC"),
+            (PipelineSort::CreatedAt, SortOrder::Desc) => ("p.created_at", "<", "DESC"),
+            (PipelineSort::Name, SortOrder::Asc) => ("p.name", ">", "ASC"),
+            (PipelineSort::Name, SortOrder::Desc) => ("p.name", "<", "DESC"),
+        };
+        if let Some(cursor) = cursor {
+            query.push(format!(" AND ({}, p.id) {} (", key, comparison));
+            match sort {
+                PipelineSort::CreatedAt => query.push_bind(cursor.created_at),
+                PipelineSort::Name => query.push_bind(cursor.name.clone()),
+            };
+            query.push(", ").push_bind(cursor.id).push(")");
+        }
+        // One more pipeline than asked tells whether there is a next page.
+        query
+            .push(format!(" ORDER BY {} {}, p.id {} LIMIT ", key, direction, direction))
+            .push_bind(limit + 1);
+
+        let mut keys: Vec<PipelineCursor> = query.build_query_as().fetch_all(&*self.pool).await?;
+        let next = match keys.len() as i64 > limit {
+            true => {
+                keys.truncate(limit as usize);
+                keys.last().cloned()
+            }
+            false => None,
+        };
+
+        let ids: Vec<i64> = keys.iter().map(|key| key.id).collect();
+        let mut pipelines: HashMap<i64, Pipeline> = self
+            .find_by_ids(&ids)
+            .await?
+            .into_iter()
+            .map(|pipeline| (pipeline.id, pipeline))
+            .collect();
+        let page = ids.iter().filter_map(|id| pipelines.remove(id)).collect();
+        Ok((page, next))
+    }
+
+    async fn find_by_ids(&self, ids: &[i64]) -> Result<Vec<Pipeline>, sqlx::Error> {
         let rows = sqlx::query_as!(
             PipelineDetailDTO,
             r#"SELECT pipelines.id as pipeline_id,
                pipelines.name as pipeline_name,
+               pipelines.created_at as pipeline_created_at,
                pipelines.repository_url as repository_url,
+               pipelines.manifest_hash as manifest_hash,
+               pipelines.commit_sha as commit_sha,
+               pipelines.ref as git_ref,
+               pipelines.event as event,
+               pipelines.trigger as trigger,
+               pipelines.requester as requester,
                a.id as action_id,
                a.name as action_name,
                a.container_uri as action_container_uri,
                a.status as action_status,
                a.type as action_type,
+               a.runs_on as action_runs_on,
+               a.needs as action_needs,
+               a.env as action_env,
+               a.condition as action_condition,
+               a.attempt as action_attempt,
+               a.max_attempts as action_max_attempts,
+               a.retry_backoff as action_retry_backoff,
+               a.retry_on as action_retry_on,
+               a.priority as action_priority,
+               a.started_at as action_started_at,
+               a.finished_at as action_finished_at,
                c.command as command
         FROM pipelines
                  JOIN actions a on pipelines.id = a.pipeline_id
                  JOIN commands c on c.action_id = a.id
-            ORDER BY pipelines.id, a.id;"#
+        WHERE pipelines.id = ANY($1)
+        ORDER BY pipelines.id, a.id;"#,
+            ids
         )
         .fetch_all(&*self.pool)
         .await?;
@@ -65,6 +265,7 @@ impl PipelineRepository {
         let mut actions: HashMap<i64, ActionDetail> = HashMap::new();
         let mut pipelines_map: HashMap<i64, Pipeline> = HashMap::new();
         for row in rows {
+            let metadata = row.metadata();
             let command = row.command;
             let action_id = row.action_id;
             if actions.contains_key(&action_id) {
@@ -79,14 +280,31 @@ impl PipelineRepository {
                     vec![command],
                     Type::from_str(row.action_type.as_str()).unwrap(),
                     row.action_status,
+                    row.action_runs_on,
+                    row.action_needs,
+                    row.action_env,
+                    row.action_condition,
                 )
-                .unwrap();
+                .unwrap()
+                .with_timing(row.action_started_at, row.action_finished_at)
+                .with_retry(
+                    RetryPolicy {
+                        max_attempts: row.action_max_attempts.max(1) as u32,
+                        backoff: row.action_retry_backoff.max(0) as u32,
+                        on: row.action_retry_on,
+                    },
+                    row.action_attempt,
+                )
+                .with_priority(row.action_priority);
                 actions.insert(
                     action_id,
                     ActionDetail {
                         repository_url: row.repository_url.clone(),
                         pipeline_id: row.pipeline_id,
                         pipeline_name: row.pipeline_name.clone(),
+                        pipeline_created_at: row.pipeline_created_at,
+                        manifest_hash: row.manifest_hash.clone(),
+                        metadata,
                         action,
                     },
                 );
@@ -104,7 +322,10 @@ impl PipelineRepository {
                     action_detail.repository_url.clone(),
                     action_detail.pipeline_name.clone(),
                     vec![action_detail.action.clone()],
-                );
+                )
+                .with_created_at(action_detail.pipeline_created_at)
+                .with_manifest_hash(action_detail.manifest_hash.clone())
+                .with_metadata(action_detail.metadata.clone());
                 pipelines_map.insert(pipeline_id, pipeline);
             }
         }
@@ -121,12 +342,30 @@ impl PipelineRepository {
             PipelineDetailDTO,
             r#"SELECT pipelines.id as pipeline_id,
                pipelines.name as pipeline_name,
+               pipelines.created_at as pipeline_created_at,
                pipelines.repository_url as repository_url,
+               pipelines.manifest_hash as manifest_hash,
+               pipelines.commit_sha as commit_sha,
+               pipelines.ref as git_ref,
+               pipelines.event as event,
+               pipelines.trigger as trigger,
+               pipelines.requester as requester,
                a.id as action_id,
                a.name as action_name,
                a.container_uri as action_container_uri,
                a.status as action_status,
                a.type as action_type,
+               a.runs_on as action_runs_on,
+               a.needs as action_needs,
+               a.env as action_env,
+               a.condition as action_condition,
+               a.attempt as action_attempt,
+               a.max_attempts as action_max_attempts,
+               a.retry_backoff as action_retry_backoff,
+               a.retry_on as action_retry_on,
+               a.priority as action_priority,
+               a.started_at as action_started_at,
+               a.finished_at as action_finished_at,
                c.command as command
         FROM pipelines
                  JOIN actions a on pipelines.id = a.pipeline_id
@@ -142,12 +381,18 @@ impl PipelineRepository {
 
         let mut pipeline_id: i64 = 0;
         let mut pipeline_name: String = String::new();
+        let mut created_at = OffsetDateTime::UNIX_EPOCH;
         let mut repository_url: String = String::new();
+        let mut manifest_hash: Option<String> = None;
+        let mut metadata = PipelineMetadata::default();
 
         for row in rows {
             pipeline_id = row.pipeline_id;
             pipeline_name = row.pipeline_name.clone();
+            created_at = row.pipeline_created_at;
             repository_url = row.repository_url.clone();
+            manifest_hash = row.manifest_hash.clone();
+            metadata = row.metadata();
 
             let command = row.command;
             let action_id = row.action_id;
@@ -163,14 +408,31 @@ impl PipelineRepository {
                     vec![command],
                     Type::from_str(row.action_type.as_str()).unwrap(),
                     row.action_status,
+                    row.action_runs_on,
+                    row.action_needs,
+                    row.action_env,
+                    row.action_condition,
+                )
+                .unwrap()
+                .with_timing(row.action_started_at, row.action_finished_at)
+                .with_retry(
+                    RetryPolicy {
+                        max_attempts: row.action_max_attempts.max(1) as u32,
+                        backoff: row.action_retry_backoff.max(0) as u32,
+                        on: row.action_retry_on,
+                    },
+                    row.action_attempt,
                 )
-                .unwrap();
+                .with_priority(row.action_priority);
                 actions_map.insert(
                     action_id,
                     ActionDetail {
                         repository_url: row.repository_url.clone(),
                         pipeline_id: row.pipeline_id,
                         pipeline_name: row.pipeline_name.clone(),
+                        pipeline_created_at: row.pipeline_created_at,
+                        manifest_hash: row.manifest_hash.clone(),
+                        metadata: metadata.clone(),
                         action,
                     },
                 );
@@ -186,24 +448,40 @@ impl PipelineRepository {
             return Err(sqlx::Error::RowNotFound); // no result found since there can not be a pipeline with id 0
         }
 
-        Ok(Pipeline::new(
-            pipeline_id,
-            repository_url,
-            pipeline_name,
-            actions,
-        ))
+        let manifest = sqlx::query_scalar!(r#"SELECT manifest FROM pipelines WHERE id = $1"#, id)
+            .fetch_one(&*self.pool)
+            .await?;
+
+        let mut pipeline = Pipeline::new(pipeline_id, repository_url, pipeline_name, actions)
+            .with_created_at(created_at)
+            .with_manifest_hash(manifest_hash)
+            .with_metadata(metadata);
+        pipeline.manifest = manifest;
+        Ok(pipeline)
     }
 
     pub async fn create(
         &self,
         repository_url: &String,
         name: &String,
+        manifest: &str,
+        manifest_hash: &str,
+        metadata: &PipelineMetadata,
     ) -> Result<PipelineDTO, sqlx::Error> {
         let row = sqlx::query_as!(
             PipelineDTO,
-            r#"INSERT INTO pipelines (repository_url, name) VALUES ($1, $2) RETURNING *"#,
+            r#"INSERT INTO pipelines (repository_url, name, manifest, manifest_hash, commit_sha, ref, event, trigger, requester)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+               RETURNING id, name, repository_url, manifest, manifest_hash, commit_sha, ref as git_ref, event, trigger, requester, created_at"#,
             repository_url,
-            name
+            name,
+            manifest,
+            manifest_hash,
+            metadata.commit_sha,
+            metadata.git_ref,
+            metadata.event,
+            metadata.trigger,
+            metadata.requester
         )
         .fetch_one(&*self.pool)
         .await?;
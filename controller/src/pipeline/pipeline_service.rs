@@ -1,28 +1,46 @@
 use sqlx::PgPool;
+use std::collections::HashMap;
 use std::sync::Arc;
 
+use futures::Stream;
+use tokio::sync::broadcast::error::RecvError;
 use tokio::task;
 use tracing::{error, info};
 
-use crate::action::action_repository::Action;
+use crate::action::action_event::ActionEventRepository;
+use crate::action::action_repository::ActionAttempt;
 use crate::action::action_service::{ActionDTO, ActionService};
+use crate::git::repository_cache::{self, FetchedManifest, GitError, RepositoryCache};
+use crate::domain::entities::action::ActionStatus as DomainActionStatus;
 use crate::grpc_scheduler::ActionStatus;
+use crate::logs::log_hub::{LogHub, LogHubEvent, LogStreamEvent};
 use crate::logs::log_repository::LogRepository;
+use crate::pipeline::pipeline_executor::PipelineExecutor;
 use crate::pipeline::pipeline_repository::PipelineRepository;
 use crate::{
+    parser::diagnostic::Diagnostic,
+    parser::format::ManifestFormat,
     parser::pipe_parser::{ManifestParser, ManifestPipeline, ParsingError},
+    parser::template::TemplateSource,
+    parser::trigger::{Trigger, TriggerFilter},
     scheduler::SchedulerService,
 };
 
-use super::pipeline_repository::PipelineDTO;
-use super::Pipeline;
+use super::pipeline_repository::{
+    PipelineCursor, PipelineDTO, PipelineFilter, PipelineSort, SortOrder,
+};
+use super::{Pipeline, PipelineMetadata, PipelinePage};
 
 pub struct PipelineService {
     client: Arc<SchedulerService>,
-    parser: Arc<dyn ManifestParser>,
+    parsers: HashMap<ManifestFormat, Arc<dyn ManifestParser>>,
     repository: Arc<PipelineRepository>,
     logs_repository: Arc<LogRepository>,
+    event_repository: Arc<ActionEventRepository>,
     action_service: Arc<ActionService>,
+    templates: Arc<dyn TemplateSource>,
+    repositories: Arc<RepositoryCache>,
+    log_hub: Arc<LogHub>,
 }
 
 #[derive(Debug)]
@@ -35,40 +53,54 @@ pub enum PipelineServiceError {
 impl PipelineService {
     pub fn new(
         client: Arc<SchedulerService>,
-        parser: Arc<dyn ManifestParser>,
+        parsers: HashMap<ManifestFormat, Arc<dyn ManifestParser>>,
         pool: Arc<PgPool>,
         action_service: Arc<ActionService>,
+        templates: Arc<dyn TemplateSource>,
+        repositories: Arc<RepositoryCache>,
+        log_hub: Arc<LogHub>,
     ) -> Self {
         let repository = Arc::new(PipelineRepository::new(pool.clone()));
         let logs_repository = Arc::new(LogRepository::new(pool.clone()));
+        let event_repository = Arc::new(ActionEventRepository::new(pool.clone()));
         Self {
             client,
-            parser,
+            parsers,
             repository,
             logs_repository,
+            event_repository,
             action_service,
+            templates,
+            repositories,
+            log_hub,
         }
     }
 
-    pub async fn find_all(&self, verbose: bool) -> Vec<Pipeline> {
-        match self.repository.find_all().await {
-            Ok(mut pipelines) => {
-                if verbose {
-                    for pipeline in &mut pipelines {
-                        if let Err(e) = self.add_verbose_details(pipeline).await {
-                            error!("Error while fetching verbose details for pipeline id {}: {:?}", pipeline.id, e);
-                        } else {
-                            info!("Verbose details added for pipeline id: {}", pipeline.id);
-                        }
-                    }
+    /// A page of the pipelines matching a filter. With `verbose`, the pipelines come with their timeline, but not with the logs of their actions.
+    pub async fn find_page(
+        &self,
+        filter: &PipelineFilter,
+        sort: PipelineSort,
+        order: SortOrder,
+        cursor: Option<&PipelineCursor>,
+        limit: i64,
+        verbose: bool,
+    ) -> Result<PipelinePage, sqlx::Error> {
+        let (mut pipelines, next) = self
+            .repository
+            .find_page(filter, sort, order, cursor, limit)
+            .await?;
+        if verbose {
+            for pipeline in &mut pipelines {
+                if let Err(e) = self.add_timeline(pipeline).await {
+                    error!("Error while fetching verbose details for pipeline id {}: {:?}", pipeline.id, e);
                 }
-                pipelines
-            }
-            Err(e) => {
-                info!("Error while fetching pipelines: {:?}", e);
-                vec![]
             }
         }
+        Ok(PipelinePage {
+            pipelines,
+            next_cursor: next.map(|cursor| cursor.encode()),
+        })
     }
 
     pub async fn find(&self, id: i64, verbose: bool) -> Option<Pipeline> {
@@ -97,23 +129,135 @@ impl PipelineService {
     
             match self.logs_repository.find_by_action_id(action.id).await {
                 Ok(logs) => {
+                    // The logs come ordered by attempt.
+                    let mut attempts: Vec<ActionAttempt> = Vec::new();
+                    for log in &logs {
+                        match attempts.last_mut() {
+                            Some(attempt) if attempt.attempt == log.attempt => {
+                                attempt.logs.push(log.message.clone())
+                            }
+                            _ => attempts.push(ActionAttempt {
+                                attempt: log.attempt,
+                                logs: vec![log.message.clone()],
+                            }),
+                        }
+                    }
                     action.logs = Some(logs.into_iter().map(|log| log.message).collect());
+                    action.attempts = Some(attempts);
                 }
                 Err(e) => {
                     return Err(format!("Error fetching logs for action {}: {}", action.name, e));
                 }
             }
         }
+
+        self.add_timeline(pipeline).await
+    }
+
+    async fn add_timeline(&self, pipeline: &mut Pipeline) -> Result<(), String> {
+        match self.event_repository.find_by_pipeline_id(pipeline.id).await {
+            Ok(events) => pipeline.timeline = Some(events),
+            Err(e) => return Err(format!("Error fetching timeline of pipeline {}: {}", pipeline.id, e)),
+        }
         Ok(())
     }
 
+    /// Follow the logs of an action of a pipeline: the stored ones from `offset` on, then the new ones as they are received.
+    /// The stream ends once the action is done. None when the pipeline has no such action.
+    pub async fn stream_logs(
+        &self,
+        pipeline_id: i64,
+        action_id: i64,
+        offset: i64,
+    ) -> Option<impl Stream<Item = LogStreamEvent> + 'static> {
+        match self.action_service.find(action_id).await {
+            Ok(action) if action.pipeline_id == pipeline_id => {}
+            Ok(_) => return None,
+            Err(e) => {
+                info!("Error while fetching action {}: {:?}", action_id, e);
+                return None;
+            }
+        }
+
+        // Following the action before reading its stored logs, so that no log is missed in between.
+        let mut receiver = self.log_hub.subscribe(action_id);
+        let logs_repository = self.logs_repository.clone();
+        let action_service = self.action_service.clone();
+        let mut offset = offset.max(0);
+        Some(async_stream::stream! {
+            let mut last_id = 0;
+            loop {
+                // The logs of a done action are all stored already.
+                let done = match action_service.find_status(action_id).await {
+                    Ok(status) if DomainActionStatus::from(status).is_terminal() => Some(status),
+                    Ok(_) => None,
+                    Err(e) => {
+                        error!("Error while fetching the status of action {}: {:?}", action_id, e);
+                        return;
+                    }
+                };
+                let logs = match logs_repository.find_range(action_id, offset, None).await {
+                    Ok(logs) => logs,
+                    Err(e) => {
+                        error!("Error while fetching logs of action {}: {:?}", action_id, e);
+                        return;
+                    }
+                };
+                if logs.is_empty() {
+                    // An offset past the stored logs starts from the next log.
+                    match logs_repository.count_by_action_id(action_id).await {
+                        Ok(count) => offset = offset.min(count),
+                        Err(e) => {
+                            error!("Error while counting logs of action {}: {:?}", action_id, e);
+                            return;
+                        }
+                    }
+                }
+                for log in logs {
+                    last_id = last_id.max(log.id);
+                    yield LogStreamEvent::Log { offset, attempt: log.attempt, message: log.message };
+                    offset += 1;
+                }
+                if let Some(status) = done {
+                    yield LogStreamEvent::End { status: status.as_str_name().to_string() };
+                    return;
+                }
+
+                loop {
+                    match receiver.recv().await {
+                        // Logs stored before following the action were already read.
+                        Ok(LogHubEvent::Log(log)) if log.id > last_id => {
+                            last_id = log.id;
+                            yield LogStreamEvent::Log { offset, attempt: log.attempt, message: log.message };
+                            offset += 1;
+                        }
+                        Ok(LogHubEvent::Log(_)) => {}
+                        Ok(LogHubEvent::Done(status)) => {
+                            yield LogStreamEvent::End { status: status.as_str_name().to_string() };
+                            return;
+                        }
+                        // Some logs were missed, they are read again from the database.
+                        Err(RecvError::Lagged(_)) => break,
+                        Err(RecvError::Closed) => return,
+                    }
+                }
+            }
+        })
+    }
+
     pub async fn create_pipeline(
         &self,
         repository_url: &String,
         name: &String,
+        manifest: &str,
+        metadata: &PipelineMetadata,
     ) -> Result<PipelineDTO, Box<dyn std::error::Error>> {
         info!("Creating pipeline for repository: {}", repository_url);
-        let pipeline = self.repository.create(repository_url, name).await;
+        let manifest_hash = repository_cache::manifest_hash(manifest);
+        let pipeline = self
+            .repository
+            .create(repository_url, name, manifest, &manifest_hash, metadata)
+            .await;
         match pipeline {
             Ok(pipeline) => {
                 info!("Created pipeline: {:?}", pipeline);
@@ -130,8 +274,13 @@ impl PipelineService {
         &self,
         manifest: ManifestPipeline,
         repo_url: String,
+        raw_manifest: &str,
+        metadata: &PipelineMetadata,
     ) -> Result<Pipeline, Box<dyn std::error::Error>> {
-        let pipeline = self.create_pipeline(&repo_url, &manifest.name).await?;
+        let pipeline = self
+            .create_pipeline(&repo_url, &manifest.name, raw_manifest, metadata)
+            .await?;
+        let priority = manifest.priority as i32;
         let mut actions = Vec::new();
         for action in manifest.actions {
             info!("Creating action: {:?}", action);
@@ -145,6 +294,15 @@ impl PipelineService {
                         status: ActionStatus::Pending.as_str_name().to_string(),
                         r#type: action.configuration_type,
                         id: None,
+                        runs_on: action.runs_on,
+                        needs: action.needs,
+                        env: action.env,
+                        condition: action.condition,
+                        attempt: 1,
+                        max_attempts: action.retry.max_attempts as i32,
+                        retry_backoff: action.retry.backoff as i32,
+                        retry_on: action.retry.on,
+                        priority,
                     },
                     action.commands,
                 )
@@ -153,30 +311,83 @@ impl PipelineService {
             actions.push(action);
         }
 
+        let metadata = pipeline.metadata();
         Ok(Pipeline::new(
             pipeline.id,
             pipeline.repository_url,
             pipeline.name,
             actions,
-        ))
+        )
+        .with_created_at(pipeline.created_at)
+        .with_manifest_hash(pipeline.manifest_hash)
+        .with_metadata(metadata))
     }
 
-    pub fn try_parse_pipeline(&self, manifest: String) -> Result<ManifestPipeline, ParsingError> {
-        self.parser.parse(manifest)
+    /// The manifest of a repository at the revision a pipeline was triggered on.
+    pub async fn fetch_manifest(
+        &self,
+        repo_url: &str,
+        git_ref: &str,
+    ) -> Result<FetchedManifest, GitError> {
+        self.repositories.read_manifest(repo_url, git_ref).await
     }
 
-    pub async fn send_action(
+    pub fn try_parse_pipeline(
         &self,
-        action: Arc<Action>,
-        repo_url: String,
-    ) -> Result<(), PipelineServiceError> {
-        let client = Arc::clone(&self.client);
+        manifest: String,
+        format: ManifestFormat,
+    ) -> Result<ManifestPipeline, ParsingError> {
+        self.parser(format)
+            .parse_with_templates(manifest, self.templates.as_ref())
+    }
+
+    /// The manifest once its templates are included and its actions extended, as yaml.
+    pub fn resolve_pipeline(
+        &self,
+        manifest: String,
+        format: ManifestFormat,
+    ) -> Result<String, ParsingError> {
+        self.parser(format)
+            .resolve(manifest, self.templates.as_ref())
+    }
+
+    /// Everything wrong with a manifest, including its templates.
+    pub fn validate_pipeline(&self, manifest: String, format: ManifestFormat) -> Vec<Diagnostic> {
+        self.parser(format)
+            .validate(manifest, self.templates.as_ref())
+    }
+
+    /// The parser of a format, the yaml one when there is none for it.
+    fn parser(&self, format: ManifestFormat) -> &dyn ManifestParser {
+        self.parsers
+            .get(&format)
+            .or_else(|| self.parsers.get(&ManifestFormat::Yaml))
+            .expect("The yaml parser is missing")
+            .as_ref()
+    }
+
+    /// Run the actions of a pipeline in the background, along the graph of their needs.
+    /// At most `max_parallelism` actions run at once, when set. When the trigger does not match the `on:` filter of the pipeline, all of its actions are skipped.
+    pub fn execute_pipeline(
+        &self,
+        pipeline: &Pipeline,
+        max_parallelism: Option<usize>,
+        on: &TriggerFilter,
+        trigger: Trigger,
+    ) {
+        let executor = PipelineExecutor::new(self.client.clone(), self.action_service.clone());
+        let actions = pipeline.actions.clone();
+        let repo_url = pipeline.repository_url.clone();
+        if !on.matches(&trigger) {
+            info!("Pipeline {} does not run for {:?}", pipeline.id, trigger);
+            task::spawn(async move { executor.skip_all(actions).await });
+            return;
+        }
         task::spawn(async move {
-            match client.send_action(action, repo_url).await {
-                Ok(_) => info!("Action sent successfully"),
-                Err(err) => error!("Error sending action: {:?}", err), //needs to store the error in database
-            }
+            executor
+                .execute(actions, repo_url, max_parallelism, trigger)
+                .await;
+            info!("Pipeline executed");
         });
-        Ok(())
     }
 }
@@ -1,23 +1,39 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use tokio::sync::Mutex;
 
-use tonic::{transport::Channel, Request};
-use tracing::{error, info};
+use tonic::{transport::Channel, Request, Streaming};
+use tracing::{error, info, warn};
 
 use crate::{
-    action::{action_repository::Action, action_service::ActionService},
+    action::{action_event::EventSource, action_repository::Action, action_service::ActionService},
+    domain::entities::action::{ActionRequest, ExecutionContext},
     grpc_scheduler::{
-        self, controller_client::ControllerClient, ActionStatus, ExecutionContext, RunnerType,
+        self, controller_client::ControllerClient, ActionResponse, ActionStatus,
+        AttachActionRequest, RunnerType,
     },
-    logs::log_repository::LogRepository,
+    logs::{log_hub::LogHub, log_repository::LogRepository},
     pipeline::pipeline_service::PipelineServiceError,
 };
 
+/// Number of times the service tries to attach again to an action after losing its stream.
+const REATTACH_ATTEMPTS: u32 = 5;
+
+/// How an attempt at running an action ended, as told by the scheduler.
+#[derive(Debug, Default)]
+pub struct AttemptEnd {
+    /// The failure the attempt ended in, and where it comes from. It is not stored: the action may be retried instead.
+    pub failure: Option<(ActionStatus, EventSource)>,
+    /// The last exit code the action reported, if any.
+    pub exit_code: Option<i32>,
+}
+
 pub struct SchedulerService {
     client: Arc<Mutex<ControllerClient<Channel>>>,
     log_repository: Arc<LogRepository>,
     action_service: Arc<ActionService>,
+    log_hub: Arc<LogHub>,
 }
 
 impl SchedulerService {
@@ -25,35 +41,44 @@ impl SchedulerService {
         client: Arc<Mutex<ControllerClient<Channel>>>,
         log_repository: Arc<LogRepository>,
         action_service: Arc<ActionService>,
+        log_hub: Arc<LogHub>,
     ) -> Self {
         Self {
             client,
             log_repository,
             action_service,
+            log_hub,
         }
     }
 
+    /// Send the current attempt at running an action to the scheduler, and store its logs and statuses until it is done.
+    /// An `ERROR` or a `TIMED_OUT` is returned rather than stored, as it is up to the caller to retry the action or not.
     pub async fn send_action(
         &self,
         action: Arc<Action>,
         repo_url: String,
-    ) -> Result<(), PipelineServiceError> {
+    ) -> Result<AttemptEnd, PipelineServiceError> {
         let id: Result<u32, _> = action.id.try_into();
-        let action_request = grpc_scheduler::ActionRequest {
-            context: Some(ExecutionContext {
+        let action_id = id.map_err(|e| {
+            error!("Error while converting action id: {:?}", e);
+            PipelineServiceError::SchedulerError
+        })?;
+        let action_request = ActionRequest {
+            context: ExecutionContext {
                 r#type: RunnerType::Docker.into(), //for now we only support container actions
                 container_image: Some(action.container_uri.clone()),
-            }),
-            action_id: id.map_err(|e| {
-                error!("Error while converting action id: {:?}", e);
-                PipelineServiceError::SchedulerError
-            })?,
+            },
+            action_id,
             commands: action.commands.clone(),
             repo_url: repo_url.clone(),
+            priority: action.priority.max(0) as u32,
+            runs_on: action.runs_on.clone(),
+            env: action.env.clone(),
         };
 
-        let request = Request::new(action_request);
-        let mut client = self.client.lock().await;
+        let request = Request::new(grpc_scheduler::ActionRequest::from(action_request));
+        // Each action gets its own handle on the connection, so that actions can run at the same time.
+        let mut client = self.client.lock().await.clone();
 
         let mut stream = client
             .schedule_action(request)
@@ -64,36 +89,97 @@ impl SchedulerService {
             })?
             .into_inner();
 
-        while let Some(response) = stream.message().await.map_err(|_err| {
-            error!("Error while receiving message from scheduler : {:?}", _err);
-            PipelineServiceError::SchedulerError
-        })? {
+        // The scheduler keeps the responses of the action: if the stream breaks, attach again and resume after the last one received.
+        let mut next_seq = 0;
+        let mut end = AttemptEnd::default();
+        loop {
+            let response = match stream.message().await {
+                Ok(Some(response)) => response,
+                Ok(None) => break,
+                Err(err) => {
+                    warn!(
+                        "Lost the scheduler stream of action {}: {:?}, attaching again",
+                        action_id, err
+                    );
+                    stream = Self::reattach(&mut client, action_id, next_seq).await?;
+                    continue;
+                }
+            };
+            next_seq = response.seq + 1;
             info!("[SCHEDULER] RESPONSE={:?}", response);
-            self.log_repository
-                .create(i64::from(response.action_id), &response.log)
+            let log = self
+                .log_repository
+                .create(i64::from(response.action_id), action.attempt, &response.log)
                 .await
                 .map_err(|e| {
                     error!("Error while storing log: {:?}", e);
                     PipelineServiceError::StoringLogError
                 })?;
+            self.log_hub.publish(i64::from(response.action_id), &log);
 
-            let status = ActionStatus::as_str_name(&response.result.unwrap().completion()); //TODO: for now we are going to unwrap all cast probable errors. Though we should handle them properly by sending a Error message through gRPC to the Scheduler
-
-            info!("[SCHEDULER] STATUS={:?}", status);
-            self.action_service
-                .update_status(
-                    i64::from(response.action_id),
-                    &ActionStatus::from_str_name(status).unwrap(), //We can unwrap here because we are sure that the status is a valid one
-                )
-                .await
-                .map_err(|e| {
-                    error!("Error while updating action status: {:?}", e);
-                    PipelineServiceError::SchedulerError
-                })?; //same here we should be sending an error status to the scheduler
+            // Once the attempt failed, its status does not change anymore.
+            if let Some(result) = response.result.as_ref().filter(|_| end.failure.is_none()) {
+                end.exit_code = result.exit_code.or(end.exit_code);
+                let status = result.completion();
+                info!("[SCHEDULER] STATUS={:?}", status.as_str_name());
+                if matches!(status, ActionStatus::Error | ActionStatus::TimedOut) {
+                    end.failure = Some((status, response.source().into()));
+                } else {
+                    let updated = self
+                        .action_service
+                        .update_status(
+                            i64::from(response.action_id),
+                            &status,
+                            response.source().into(),
+                        )
+                        .await
+                        .map_err(|e| {
+                            error!("Error while updating action status: {:?}", e);
+                            PipelineServiceError::SchedulerError
+                        })?; //we should be sending an error status to the scheduler
+                    if !updated {
+                        warn!(
+                            "Action {} cannot move to {}, its status is left as is",
+                            response.action_id,
+                            status.as_str_name()
+                        );
+                    }
+                }
+            }
 
             info!("[SCHEDULER] RESPONSE={:?}", response);
         }
 
-        Ok(())
+        Ok(end)
+    }
+
+    /// Attach again to an action, to get its responses from `from_seq` on.
+    async fn reattach(
+        client: &mut ControllerClient<Channel>,
+        action_id: u32,
+        from_seq: u64,
+    ) -> Result<Streaming<ActionResponse>, PipelineServiceError> {
+        let mut attempt = 1;
+        loop {
+            let request = Request::new(AttachActionRequest {
+                action_id,
+                from_seq,
+            });
+            match client.attach_action(request).await {
+                Ok(response) => return Ok(response.into_inner()),
+                Err(err) if attempt < REATTACH_ATTEMPTS => {
+                    warn!(
+                        "Error while attaching to action {} (attempt {}/{}): {:?}",
+                        action_id, attempt, REATTACH_ATTEMPTS, err
+                    );
+                    tokio::time::sleep(Duration::from_secs(attempt.into())).await;
+                    attempt += 1;
+                }
+                Err(err) => {
+                    error!("Error while attaching to action {} : {:?}", action_id, err);
+                    return Err(PipelineServiceError::SchedulerError);
+                }
+            }
+        }
     }
 }
@@ -6,7 +6,7 @@ use std::time::Duration;
 
 use scheduler::{
     controller_server::{Controller, ControllerServer},
-    ActionRequest, ActionResponse,
+    ActionRequest, ActionResponse, AttachActionRequest,
 };
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
@@ -22,6 +22,7 @@ pub struct MockSchedulerService {}
 #[async_trait]
 impl Controller for MockSchedulerService {
     type ScheduleActionStream = ReceiverStream<Result<ActionResponse, Status>>;
+    type AttachActionStream = ReceiverStream<Result<ActionResponse, Status>>;
 
     async fn schedule_action(
         &self,
@@ -41,7 +42,7 @@ impl Controller for MockSchedulerService {
                 .unwrap()
         );
 
-        for _i in 0..10 {
+        for seq in 0..10 {
             println!("INFO: scheduled");
             tx.send(Ok(ActionResponse {
                 action_id: _request.get_ref().action_id,
@@ -50,6 +51,8 @@ impl Controller for MockSchedulerService {
                     completion: scheduler::ActionStatus::Scheduled as i32,
                     exit_code: Some(1),
                 }),
+                seq,
+                source: scheduler::ResponseSource::Scheduler as i32,
             }))
             .await
             .expect("should be sent");
@@ -59,6 +62,13 @@ impl Controller for MockSchedulerService {
 
         Ok(Response::new(ReceiverStream::new(rx)))
     }
+
+    async fn attach_action(
+        &self,
+        _request: Request<AttachActionRequest>,
+    ) -> Result<Response<Self::AttachActionStream>, Status> {
+        Err(Status::not_found("The mock scheduler does not keep responses"))
+    }
 }
 
 #[tokio::main]
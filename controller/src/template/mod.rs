@@ -0,0 +1,2 @@
+pub mod template_controller;
+pub mod template_store;
@@ -0,0 +1,70 @@
+use actix_web::{
+    delete, get, put,
+    web::{self},
+    HttpResponse, Responder,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::{error, info};
+
+use crate::template::template_store::{TemplateError, TemplateStore};
+
+#[derive(Deserialize)]
+struct TemplateByNameQuery {
+    name: String,
+}
+
+#[get("/template")]
+pub async fn get_templates(template_store: web::Data<Arc<TemplateStore>>) -> impl Responder {
+    match template_store.list() {
+        Ok(names) => HttpResponse::Ok().json(names),
+        Err(e) => error_response(e),
+    }
+}
+
+#[get("/template/{name}")]
+pub async fn get_template(
+    path: web::Path<TemplateByNameQuery>,
+    template_store: web::Data<Arc<TemplateStore>>,
+) -> impl Responder {
+    match template_store.find(&path.name) {
+        Ok(content) => HttpResponse::Ok().content_type("application/yaml").body(content),
+        Err(e) => error_response(e),
+    }
+}
+
+#[put("/template/{name}")]
+pub async fn put_template(
+    path: web::Path<TemplateByNameQuery>,
+    body: String,
+    template_store: web::Data<Arc<TemplateStore>>,
+) -> impl Responder {
+    info!("Storing template {}", path.name);
+    match template_store.save(&path.name, &body) {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(e),
+    }
+}
+
+#[delete("/template/{name}")]
+pub async fn delete_template(
+    path: web::Path<TemplateByNameQuery>,
+    template_store: web::Data<Arc<TemplateStore>>,
+) -> impl Responder {
+    match template_store.delete(&path.name) {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(e),
+    }
+}
+
+fn error_response(e: TemplateError) -> HttpResponse {
+    match e {
+        TemplateError::InvalidName => HttpResponse::UnprocessableEntity().body(e.to_string()),
+        TemplateError::InvalidTemplate => HttpResponse::BadRequest().body(e.to_string()),
+        TemplateError::NotFound => HttpResponse::NotFound().finish(),
+        TemplateError::Io(_) => {
+            error!("{}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
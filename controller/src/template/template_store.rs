@@ -0,0 +1,101 @@
+use std::{fmt, fs, io, path::PathBuf};
+
+use yaml_rust::YamlLoader;
+
+use crate::parser::template::TemplateSource;
+
+/// The templates pipelines can include, each one a yaml file of the templates directory.
+pub struct TemplateStore {
+    dir: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum TemplateError {
+    InvalidName,
+    InvalidTemplate,
+    NotFound,
+    Io(io::Error),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateError::InvalidName => write!(f, "Invalid template name"),
+            TemplateError::InvalidTemplate => write!(f, "Invalid yaml"),
+            TemplateError::NotFound => write!(f, "Template not found"),
+            TemplateError::Io(e) => write!(f, "Template storage error: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for TemplateError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => TemplateError::NotFound,
+            _ => TemplateError::Io(e),
+        }
+    }
+}
+
+impl TemplateStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Store a template, replacing any former template of the same name.
+    pub fn save(&self, name: &str, content: &str) -> Result<(), TemplateError> {
+        let path = self.path(name)?;
+        YamlLoader::load_from_str(content).map_err(|_| TemplateError::InvalidTemplate)?;
+        fs::create_dir_all(&self.dir)?;
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    pub fn find(&self, name: &str) -> Result<String, TemplateError> {
+        Ok(fs::read_to_string(self.path(name)?)?)
+    }
+
+    /// The names of all the templates, sorted.
+    pub fn list(&self) -> Result<Vec<String>, TemplateError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(TemplateError::Io(e)),
+        };
+        let mut names = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+            if let Some(name) = entry.file_name().to_str().filter(|name| is_valid_name(name)) {
+                names.push(name.to_string());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), TemplateError> {
+        Ok(fs::remove_file(self.path(name)?)?)
+    }
+
+    fn path(&self, name: &str) -> Result<PathBuf, TemplateError> {
+        if !is_valid_name(name) {
+            return Err(TemplateError::InvalidName);
+        }
+        Ok(self.dir.join(name))
+    }
+}
+
+impl TemplateSource for TemplateStore {
+    fn find(&self, name: &str) -> Option<String> {
+        TemplateStore::find(self, name).ok()
+    }
+}
+
+/// Template names are file names, e.g. `rust.yaml`, without any path: they cannot point outside of the templates directory.
+fn is_valid_name(name: &str) -> bool {
+    let valid_chars = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.';
+    !name.is_empty() && !name.starts_with('.') && name.chars().all(valid_chars)
+}
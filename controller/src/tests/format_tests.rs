@@ -0,0 +1,159 @@
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+use yaml_rust::{Yaml, YamlLoader};
+
+/// Serializes a yaml document as is, mappings keeping their order, to write it in another format.
+#[allow(dead_code)]
+struct Ordered<'a>(&'a Yaml);
+
+impl Serialize for Ordered<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Yaml::String(value) => serializer.serialize_str(value),
+            Yaml::Integer(value) => serializer.serialize_i64(*value),
+            Yaml::Real(value) => serializer.serialize_f64(value.parse().unwrap()),
+            Yaml::Boolean(value) => serializer.serialize_bool(*value),
+            Yaml::Array(values) => {
+                let mut seq = serializer.serialize_seq(Some(values.len()))?;
+                for value in values {
+                    seq.serialize_element(&Ordered(value))?;
+                }
+                seq.end()
+            }
+            Yaml::Hash(hash) => {
+                let mut map = serializer.serialize_map(Some(hash.len()))?;
+                for (key, value) in hash {
+                    map.serialize_entry(&Ordered(key), &Ordered(value))?;
+                }
+                map.end()
+            }
+            _ => serializer.serialize_unit(),
+        }
+    }
+}
+
+/// A yaml manifest written in JSON and in TOML.
+#[allow(dead_code)]
+fn convert(yaml: &str) -> (String, String) {
+    let doc = &YamlLoader::load_from_str(yaml).unwrap()[0];
+    (
+        serde_json::to_string_pretty(&Ordered(doc)).unwrap(),
+        toml::to_string(&Ordered(doc)).unwrap(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::diagnostic::Diagnostic;
+    use crate::parser::format::ManifestFormat;
+    use crate::parser::json_parser::JsonParser;
+    use crate::parser::pipe_parser::{ManifestParser, ParsingError, PipeParser};
+    use crate::parser::template::NoTemplates;
+    use crate::parser::toml_parser::TomlParser;
+    use crate::template::template_store::TemplateStore;
+    use crate::tests::yaml_parser_tests::read_yaml_file;
+
+    use super::*;
+
+    #[test]
+    fn test_formats_are_equivalent() {
+        let templates = TemplateStore::new("src/tests/data/templates");
+        for manifest in [
+            "classic_pipeline",
+            "needs_pipeline",
+            "runs_on_pipeline",
+            "matrix_pipeline",
+            "conditional_pipeline",
+            "retry_pipeline",
+            "included_pipeline",
+            "full_pipeline",
+        ] {
+            let yaml = read_yaml_file(&format!("src/tests/data/{}.yaml", manifest));
+            let (json, toml) = convert(&yaml);
+
+            let from_yaml = PipeParser::default()
+                .parse_with_templates(yaml, &templates)
+                .unwrap();
+            let from_json = JsonParser::default().parse_with_templates(json.clone(), &templates);
+            let from_toml = TomlParser::default().parse_with_templates(toml.clone(), &templates);
+            assert_eq!(
+                from_json,
+                Ok(from_yaml.clone()),
+                "{} in JSON:\n{}",
+                manifest,
+                json
+            );
+            assert_eq!(from_toml, Ok(from_yaml), "{} in TOML:\n{}", manifest, toml);
+        }
+    }
+
+    #[test]
+    fn test_written_formats_are_equivalent() {
+        let yaml =
+            PipeParser::default().parse(read_yaml_file("src/tests/data/classic_pipeline.yaml"));
+        let json =
+            JsonParser::default().parse(read_yaml_file("src/tests/data/classic_pipeline.json"));
+        let toml =
+            TomlParser::default().parse(read_yaml_file("src/tests/data/classic_pipeline.toml"));
+
+        let names: Vec<String> = yaml
+            .as_ref()
+            .unwrap()
+            .actions
+            .iter()
+            .map(|a| a.name.clone())
+            .collect();
+        assert_eq!(names, vec!["build", "test", "deploy"]);
+        assert_eq!(json, yaml);
+        assert_eq!(toml, yaml);
+    }
+
+    #[test]
+    fn test_formats_report_the_same_errors() {
+        let (json, toml) = convert(&read_yaml_file("src/tests/data/diagnostics_pipeline.yaml"));
+        let codes = |diagnostics: Vec<Diagnostic>| {
+            diagnostics
+                .into_iter()
+                .map(|d| (d.code, d.path))
+                .collect::<Vec<_>>()
+        };
+        let from_yaml = PipeParser::default().validate(
+            read_yaml_file("src/tests/data/diagnostics_pipeline.yaml"),
+            &NoTemplates,
+        );
+        let from_json = JsonParser::default().validate(json, &NoTemplates);
+        assert_eq!(from_json[0].line, Some(9));
+        assert_eq!(codes(from_json), codes(from_yaml.clone()));
+        let from_toml = TomlParser::default().validate(toml, &NoTemplates);
+        assert_eq!(codes(from_toml), codes(from_yaml));
+
+        let diagnostics = TomlParser::default()
+            .validate(String::from("name = \"Broken\"\n[actions\n"), &NoTemplates);
+        assert_eq!(diagnostics[0].code, "YamlNotCompliant");
+        assert_eq!(diagnostics[0].line, Some(2));
+        assert_eq!(
+            JsonParser::default().parse(String::from("{\"name\": ")),
+            Err(ParsingError::YamlNotCompliant)
+        );
+    }
+
+    #[test]
+    fn test_format_detection() {
+        assert_eq!(
+            ManifestFormat::detect(Some("pipeline.JSON"), None),
+            ManifestFormat::Json
+        );
+        assert_eq!(
+            ManifestFormat::detect(Some(".sealci.toml"), Some("application/json")),
+            ManifestFormat::Toml
+        );
+        assert_eq!(
+            ManifestFormat::detect(Some("manifest"), Some("application/toml")),
+            ManifestFormat::Toml
+        );
+        assert_eq!(
+            ManifestFormat::detect(Some(".sealci.yml"), None),
+            ManifestFormat::Yaml
+        );
+        assert_eq!(ManifestFormat::detect(None, None), ManifestFormat::Yaml);
+    }
+}
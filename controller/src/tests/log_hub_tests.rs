@@ -0,0 +1,149 @@
+use crate::logs::Log;
+
+/// A stored log of the first attempt at an action.
+#[allow(dead_code)]
+fn log(id: i64, message: &str) -> Log {
+    attempt_log(id, 1, message)
+}
+
+/// A stored log of the given attempt at an action.
+#[allow(dead_code)]
+fn attempt_log(id: i64, attempt: i32, message: &str) -> Log {
+    Log {
+        id,
+        attempt,
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grpc_scheduler::ActionStatus;
+    use crate::logs::log_hub::{LogHub, LogHubEvent, LogStreamEvent};
+    use crate::pipeline::pipeline_controller::resume_offset;
+    use actix_web::test::TestRequest;
+    use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+
+    #[tokio::test]
+    async fn test_followers_get_logs_until_done() {
+        let hub = LogHub::new();
+        let mut first = hub.subscribe(1);
+        let mut second = hub.subscribe(1);
+        let mut other = hub.subscribe(2);
+
+        hub.publish(1, &log(10, "cargo build"));
+        hub.publish(1, &log(11, "Finished"));
+        hub.finish(1, ActionStatus::Completed);
+
+        for receiver in [&mut first, &mut second] {
+            assert!(matches!(receiver.recv().await, Ok(LogHubEvent::Log(log)) if log.id == 10));
+            assert!(
+                matches!(receiver.recv().await, Ok(LogHubEvent::Log(log)) if log.message == "Finished")
+            );
+            assert!(matches!(
+                receiver.recv().await,
+                Ok(LogHubEvent::Done(ActionStatus::Completed))
+            ));
+            assert!(matches!(receiver.recv().await, Err(RecvError::Closed)));
+        }
+        assert!(matches!(other.try_recv(), Err(TryRecvError::Empty)));
+    }
+
+    #[tokio::test]
+    async fn test_followers_only_get_new_logs() {
+        let hub = LogHub::new();
+        hub.publish(1, &log(10, "before"));
+        let mut receiver = hub.subscribe(1);
+        hub.publish(1, &log(11, "after"));
+        assert!(matches!(receiver.recv().await, Ok(LogHubEvent::Log(log)) if log.id == 11));
+    }
+
+    #[tokio::test]
+    async fn test_followers_get_all_attempts_of_a_retried_action() {
+        let hub = LogHub::new();
+        let mut receiver = hub.subscribe(1);
+
+        // A failed attempt does not end the action while it is retried: only its last attempt does.
+        hub.publish(1, &attempt_log(10, 1, "exit code 1"));
+        hub.publish(1, &attempt_log(11, 2, "cargo test"));
+        hub.finish(1, ActionStatus::Completed);
+
+        assert!(
+            matches!(receiver.recv().await, Ok(LogHubEvent::Log(log)) if log.id == 10 && log.attempt == 1)
+        );
+        assert!(
+            matches!(receiver.recv().await, Ok(LogHubEvent::Log(log)) if log.id == 11 && log.attempt == 2)
+        );
+        assert!(matches!(
+            receiver.recv().await,
+            Ok(LogHubEvent::Done(ActionStatus::Completed))
+        ));
+        assert!(matches!(receiver.recv().await, Err(RecvError::Closed)));
+    }
+
+    #[tokio::test]
+    async fn test_channels_go_away_with_their_followers() {
+        let hub = LogHub::new();
+        // A stream opened on a done action ends right away, nothing being sent for the action anymore.
+        drop(hub.subscribe(1));
+        assert!(!hub.is_followed(1));
+
+        let first = hub.subscribe(2);
+        let mut second = hub.subscribe(2);
+        drop(first);
+        assert!(hub.is_followed(2));
+        hub.publish(2, &log(10, "still followed"));
+        assert!(matches!(second.recv().await, Ok(LogHubEvent::Log(log)) if log.id == 10));
+        drop(second);
+        assert!(!hub.is_followed(2));
+
+        // A follower let go by a done action does not take the channel of a later follower away.
+        let done = hub.subscribe(3);
+        hub.finish(3, ActionStatus::Error);
+        let mut later = hub.subscribe(3);
+        drop(done);
+        assert!(hub.is_followed(3));
+        hub.publish(3, &log(11, "still followed"));
+        assert!(matches!(later.recv().await, Ok(LogHubEvent::Log(log)) if log.id == 11));
+    }
+
+    #[test]
+    fn test_stream_resume_offset() {
+        let request = TestRequest::default().to_http_request();
+        assert_eq!(resume_offset(&request, None), 0);
+        assert_eq!(resume_offset(&request, Some(5)), 5);
+
+        // A client reconnecting to a stream opened with an offset resumes after the last log it got.
+        let request = TestRequest::default()
+            .insert_header(("Last-Event-ID", "7"))
+            .to_http_request();
+        assert_eq!(resume_offset(&request, None), 8);
+        assert_eq!(resume_offset(&request, Some(5)), 8);
+
+        let request = TestRequest::default()
+            .insert_header(("Last-Event-ID", "not an offset"))
+            .to_http_request();
+        assert_eq!(resume_offset(&request, Some(5)), 5);
+    }
+
+    #[test]
+    fn test_stream_events_serialization() {
+        let event = LogStreamEvent::Log {
+            offset: 3,
+            attempt: 2,
+            message: "cargo test".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({"event": "log", "offset": 3, "attempt": 2, "message": "cargo test"})
+        );
+        let event = LogStreamEvent::End {
+            status: "ACTION_STATUS_COMPLETED".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({"event": "end", "status": "ACTION_STATUS_COMPLETED"})
+        );
+    }
+}
@@ -1 +1,6 @@
 pub mod yaml_parser_tests;
+pub mod schema_tests;
+pub mod format_tests;
+pub mod repository_cache_tests;
+pub mod log_hub_tests;
+pub mod pipeline_listing_tests;
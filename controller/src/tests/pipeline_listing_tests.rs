@@ -0,0 +1,38 @@
+#[cfg(test)]
+mod tests {
+    use crate::pipeline::pipeline_repository::{PipelineCursor, PipelineSort, SortOrder};
+    use time::OffsetDateTime;
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = PipelineCursor {
+            id: 42,
+            name: "build & test".to_string(),
+            created_at: OffsetDateTime::from_unix_timestamp_nanos(1_726_660_800_123_456_000)
+                .unwrap(),
+        };
+        let token = cursor.encode();
+        assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+        let decoded = PipelineCursor::decode(&token).unwrap();
+        assert_eq!(decoded.id, 42);
+        assert_eq!(decoded.name, "build & test");
+        assert_eq!(decoded.created_at, cursor.created_at);
+    }
+
+    #[test]
+    fn test_invalid_cursors() {
+        assert!(PipelineCursor::decode("").is_none());
+        assert!(PipelineCursor::decode("not a cursor").is_none());
+        assert!(PipelineCursor::decode(&hex::encode("{\"id\": 1}")).is_none());
+    }
+
+    #[test]
+    fn test_sort_defaults() {
+        assert_eq!(PipelineSort::default(), PipelineSort::CreatedAt);
+        assert_eq!(SortOrder::default(), SortOrder::Desc);
+        assert_eq!(
+            serde_json::from_str::<PipelineSort>("\"created_at\"").unwrap(),
+            PipelineSort::CreatedAt
+        );
+    }
+}
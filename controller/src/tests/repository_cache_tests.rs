@@ -0,0 +1,156 @@
+use std::path::Path;
+use std::process::Command;
+
+/// Run git in a directory, with an identity to commit with, and return what it printed.
+#[allow(dead_code)]
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args([
+            "-c",
+            "user.name=SealCI",
+            "-c",
+            "user.email=sealci@example.com",
+        ])
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .expect("git is not installed");
+    assert!(
+        output.status.success(),
+        "git {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/// Commit the files of a work tree with a message, and return the sha of the commit.
+#[allow(dead_code)]
+fn commit(work: &Path, message: &str) -> String {
+    git(work, &["add", "-A"]);
+    git(work, &["commit", "--quiet", "--allow-empty", "-m", message]);
+    git(work, &["rev-parse", "HEAD"])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    use crate::git::repository_cache::{manifest_hash, GitError, RepositoryCache, MANIFEST_PATH};
+
+    const FIRST_MANIFEST: &str = "name: first\nactions:\n  build:\n    configuration:\n      container: debian\n    commands:\n      - make\n";
+    const SECOND_MANIFEST: &str = "name: second\nactions:\n  test:\n    configuration:\n      container: debian\n    commands:\n      - make test\n";
+
+    /// A bare repository whose `main` branch has two commits, the first one being tagged `v1`.
+    fn setup(root: &Path) -> (String, String, String) {
+        let work = root.join("work");
+        let origin = root.join("origin.git");
+        fs::create_dir_all(&work).unwrap();
+        git(
+            root,
+            &["init", "--quiet", "--bare", origin.to_str().unwrap()],
+        );
+        git(&work, &["init", "--quiet", "-b", "main"]);
+
+        fs::write(work.join(MANIFEST_PATH), FIRST_MANIFEST).unwrap();
+        let first = commit(&work, "first");
+        git(&work, &["tag", "v1"]);
+        fs::write(work.join(MANIFEST_PATH), SECOND_MANIFEST).unwrap();
+        let second = commit(&work, "second");
+        git(
+            &work,
+            &["push", "--quiet", origin.to_str().unwrap(), "main", "v1"],
+        );
+
+        (origin.to_str().unwrap().to_string(), first, second)
+    }
+
+    #[tokio::test]
+    async fn test_read_manifest_at_ref() {
+        let root = tempfile::tempdir().unwrap();
+        let (origin, first, second) = setup(root.path());
+        let cache = RepositoryCache::new(root.path().join("cache"));
+
+        let fetched = cache.read_manifest(&origin, "main").await.unwrap();
+        assert_eq!(fetched.commit, second);
+        assert_eq!(fetched.content, SECOND_MANIFEST);
+
+        let fetched = cache
+            .read_manifest(&origin, "refs/heads/main")
+            .await
+            .unwrap();
+        assert_eq!(fetched.commit, second);
+
+        let fetched = cache.read_manifest(&origin, "v1").await.unwrap();
+        assert_eq!(fetched.commit, first);
+        assert_eq!(fetched.content, FIRST_MANIFEST);
+
+        let fetched = cache.read_manifest(&origin, &first).await.unwrap();
+        assert_eq!(fetched.content, FIRST_MANIFEST);
+    }
+
+    #[tokio::test]
+    async fn test_read_manifest_fetches_new_commits() {
+        let root = tempfile::tempdir().unwrap();
+        let (origin, _, second) = setup(root.path());
+        let cache = RepositoryCache::new(root.path().join("cache"));
+        assert_eq!(
+            cache.read_manifest(&origin, "main").await.unwrap().commit,
+            second
+        );
+
+        let work = root.path().join("work");
+        fs::write(work.join(MANIFEST_PATH), FIRST_MANIFEST).unwrap();
+        let third = commit(&work, "third");
+        git(&work, &["push", "--quiet", &origin, "main"]);
+
+        let fetched = cache.read_manifest(&origin, "main").await.unwrap();
+        assert_eq!(fetched.commit, third);
+        assert_eq!(fetched.content, FIRST_MANIFEST);
+        assert_eq!(
+            cache.read_manifest(&origin, &third).await.unwrap().commit,
+            third
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_manifest_errors() {
+        let root = tempfile::tempdir().unwrap();
+        let (origin, _, _) = setup(root.path());
+        let cache = RepositoryCache::new(root.path().join("cache"));
+
+        let result = cache.read_manifest(&origin, "unknown").await;
+        assert!(matches!(result, Err(GitError::UnknownRef(_))));
+
+        let result = cache.read_manifest(&origin, "--upload-pack=touch").await;
+        assert!(matches!(result, Err(GitError::InvalidRef(_))));
+
+        let work = root.path().join("work");
+        fs::remove_file(work.join(MANIFEST_PATH)).unwrap();
+        let without_manifest = commit(&work, "no manifest");
+        git(&work, &["push", "--quiet", &origin, "main"]);
+        let result = cache.read_manifest(&origin, "main").await;
+        assert!(
+            matches!(result, Err(GitError::MissingManifest(commit)) if commit == without_manifest)
+        );
+
+        let missing = root.path().join("missing.git");
+        let result = cache.read_manifest(missing.to_str().unwrap(), "main").await;
+        assert!(matches!(result, Err(GitError::Command(_))));
+    }
+
+    #[test]
+    fn test_manifest_hash() {
+        assert_eq!(manifest_hash(FIRST_MANIFEST), manifest_hash(FIRST_MANIFEST));
+        assert_ne!(
+            manifest_hash(FIRST_MANIFEST),
+            manifest_hash(SECOND_MANIFEST)
+        );
+        assert_eq!(
+            manifest_hash(""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
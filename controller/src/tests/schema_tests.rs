@@ -0,0 +1,103 @@
+use serde_json::Value;
+
+/// Every key of the `properties` of a schema, at any depth.
+#[allow(dead_code)]
+fn schema_keys(schema: &Value, keys: &mut Vec<String>) {
+    match schema {
+        Value::Object(object) => {
+            if let Some(Value::Object(properties)) = object.get("properties") {
+                keys.extend(properties.keys().cloned());
+            }
+            object.values().for_each(|value| schema_keys(value, keys));
+        }
+        Value::Array(values) => values.iter().for_each(|value| schema_keys(value, keys)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::docs::PIPELINE_SCHEMA;
+    use crate::parser::pipe_parser::{
+        ManifestParser, ParsingError, PipeParser, ACTION_KEYS, PIPELINE_KEYS,
+    };
+    use crate::parser::template::NoTemplates;
+    use crate::template::template_store::TemplateStore;
+    use crate::tests::yaml_parser_tests::read_yaml_file;
+
+    use super::*;
+
+    fn properties(schema: &Value) -> BTreeSet<&str> {
+        schema["properties"]
+            .as_object()
+            .expect("The schema has no properties")
+            .keys()
+            .map(String::as_str)
+            .collect()
+    }
+
+    #[test]
+    fn test_schema_has_the_keys_of_the_parser() {
+        let schema: Value = serde_json::from_str(PIPELINE_SCHEMA).unwrap();
+
+        // `include` and `extends` are resolved before the parser reads the manifest.
+        let mut pipeline_keys: BTreeSet<&str> = PIPELINE_KEYS.into_iter().collect();
+        pipeline_keys.insert("include");
+        assert_eq!(properties(&schema), pipeline_keys);
+
+        let mut action_keys: BTreeSet<&str> = ACTION_KEYS.into_iter().collect();
+        action_keys.insert("extends");
+        assert_eq!(properties(&schema["definitions"]["action"]), action_keys);
+    }
+
+    #[test]
+    fn test_schema_keys_are_parsed_strictly() {
+        // The manifest uses every key of the schema, at any depth.
+        let yaml_content = read_yaml_file("src/tests/data/full_pipeline.yaml");
+        let mut schema_keys_found = Vec::new();
+        schema_keys(
+            &serde_json::from_str(PIPELINE_SCHEMA).unwrap(),
+            &mut schema_keys_found,
+        );
+        for key in schema_keys_found {
+            assert!(
+                yaml_content.contains(&format!("{}:", key)),
+                "The schema has `{}`, which the test manifest does not use",
+                key
+            );
+        }
+
+        let templates = TemplateStore::new("src/tests/data/templates");
+        let parser = PipeParser { strict: true };
+        assert_eq!(parser.validate(yaml_content.clone(), &templates), vec![]);
+        let pipeline = parser
+            .parse_with_templates(yaml_content, &templates)
+            .unwrap();
+        assert_eq!(pipeline.actions.len(), 4);
+    }
+
+    #[test]
+    fn test_strict_parsing_rejects_unknown_keys() {
+        let yaml_content = read_yaml_file("src/tests/data/strict_pipeline.yaml");
+
+        let diagnostics = PipeParser::default().validate(yaml_content.clone(), &NoTemplates);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "UnknownKey");
+        assert!(PipeParser::default().parse(yaml_content.clone()).is_ok());
+
+        let parser = PipeParser { strict: true };
+        let diagnostics = parser.validate(yaml_content.clone(), &NoTemplates);
+        assert_eq!(diagnostics[0].code, "UnknownKey");
+        assert_eq!(diagnostics[0].path, "actions.build.comands");
+        assert_eq!(
+            diagnostics[0].suggestion.as_deref(),
+            Some("did you mean `commands`?")
+        );
+        assert_eq!(
+            parser.parse(yaml_content).unwrap_err(),
+            ParsingError::UnknownKey(String::from("comands"))
+        );
+    }
+}
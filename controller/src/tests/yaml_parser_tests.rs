@@ -2,7 +2,7 @@ use std::fs::File;
 use std::io::Read;
 
 #[allow(dead_code)]
-fn read_yaml_file(file_path: &str) -> String {
+pub fn read_yaml_file(file_path: &str) -> String {
     let mut file = File::open(file_path).expect("Failed to open file");
     let mut content = String::new();
     file.read_to_string(&mut content)
@@ -12,7 +12,13 @@ fn read_yaml_file(file_path: &str) -> String {
 
 #[cfg(test)]
 mod tests {
+    use crate::parser::diagnostic::Severity;
+    use crate::parser::expression::{EvaluationContext, Expression};
     use crate::parser::pipe_parser::{ManifestParser, ParsingError, PipeParser, Type};
+    use crate::parser::template::NoTemplates;
+    use crate::parser::trigger::Trigger;
+    use crate::template::template_store::TemplateStore;
+    use std::time::Duration;
 
     use super::*;
 
@@ -20,7 +26,7 @@ mod tests {
     fn test_yaml_parsing() {
         let yaml_content = read_yaml_file("src/tests/data/classic_pipeline.yaml");
 
-        let parser = PipeParser {};
+        let parser = PipeParser::default();
         let result = parser.parse(yaml_content);
 
         assert!(result.is_ok());
@@ -59,7 +65,7 @@ mod tests {
     fn test_yaml_parsing_without_name() {
         let yaml_content = read_yaml_file("src/tests/data/unnamed_pipeline.yaml");
 
-        let parser = PipeParser {};
+        let parser = PipeParser::default();
         let result = parser.parse(yaml_content);
 
         assert!(result.is_err());
@@ -70,7 +76,7 @@ mod tests {
     fn test_yaml_parsing_with_missing_actions() {
         let yaml_content = read_yaml_file("src/tests/data/missing_actions_pipeline.yaml");
 
-        let parser = PipeParser {};
+        let parser = PipeParser::default();
         let result = parser.parse(yaml_content);
 
         assert!(result.is_err());
@@ -81,7 +87,7 @@ mod tests {
     fn test_yaml_parsing_with_invalid_data() {
         let yaml_content = read_yaml_file("src/tests/data/invalid_pipeline.yaml");
 
-        let parser = PipeParser {};
+        let parser = PipeParser::default();
         let result = parser.parse(yaml_content);
 
         assert!(result.is_err());
@@ -91,7 +97,7 @@ mod tests {
     #[test]
     fn test_yaml_parsing_empty_commands() {
         let yaml_content = read_yaml_file("src/tests/data/empty_commands_pipeline.yaml");
-        let parser = PipeParser {};
+        let parser = PipeParser::default();
         let result = parser.parse(yaml_content);
 
         assert!(result.is_err());
@@ -101,7 +107,7 @@ mod tests {
     #[test]
     fn test_yaml_parsing_special_characters_valid() {
         let yaml_content = read_yaml_file("src/tests/data/valid_special_characters_pipeline.yaml");
-        let parser = PipeParser {};
+        let parser = PipeParser::default();
         let result = parser.parse(yaml_content);
 
         assert!(result.is_ok());
@@ -122,7 +128,7 @@ mod tests {
     fn test_yaml_parsing_special_characters_invalid() {
         let yaml_content =
             read_yaml_file("src/tests/data/invalid_special_characters_pipeline.yaml");
-        let parser = PipeParser {};
+        let parser = PipeParser::default();
         let result = parser.parse(yaml_content);
 
         assert!(result.is_err());
@@ -132,7 +138,7 @@ mod tests {
     #[test]
     fn test_inconsistent_command_indentation() {
         let yaml_content = read_yaml_file("src/tests/data/inconsistent_command_indentation.yaml");
-        let parser = PipeParser {};
+        let parser = PipeParser::default();
         let result = parser.parse(yaml_content);
 
         assert!(result.is_err());
@@ -141,4 +147,314 @@ mod tests {
             Err(ParsingError::InconsistentCommandIndentation)
         ));
     }
+
+    #[test]
+    fn test_yaml_parsing_runs_on() {
+        let yaml_content = read_yaml_file("src/tests/data/runs_on_pipeline.yaml");
+        let parser = PipeParser::default();
+        let result = parser.parse(yaml_content);
+
+        assert!(result.is_ok());
+        let pipeline = result.unwrap();
+
+        let build_action = pipeline.actions.iter().find(|a| a.name == "build").unwrap();
+        assert_eq!(build_action.runs_on, vec!["linux", "aarch64"]);
+
+        let train_action = pipeline.actions.iter().find(|a| a.name == "train").unwrap();
+        assert_eq!(train_action.runs_on, vec!["gpu"]);
+
+        let lint_action = pipeline.actions.iter().find(|a| a.name == "lint").unwrap();
+        assert!(lint_action.runs_on.is_empty());
+    }
+
+    #[test]
+    fn test_yaml_parsing_runs_on_invalid() {
+        let yaml_content = read_yaml_file("src/tests/data/invalid_runs_on_pipeline.yaml");
+        let parser = PipeParser::default();
+        let result = parser.parse(yaml_content);
+
+        assert!(matches!(result, Err(ParsingError::InvalidRunsOn)));
+    }
+
+    #[test]
+    fn test_yaml_parsing_sequential_by_default() {
+        let yaml_content = read_yaml_file("src/tests/data/classic_pipeline.yaml");
+        let parser = PipeParser::default();
+        let pipeline = parser.parse(yaml_content).unwrap();
+
+        // Each action needs the one before it.
+        assert!(pipeline.actions[0].needs.is_empty());
+        assert_eq!(pipeline.actions[1].needs, vec![pipeline.actions[0].name.clone()]);
+        assert_eq!(pipeline.actions[2].needs, vec![pipeline.actions[1].name.clone()]);
+        assert_eq!(pipeline.max_parallelism, None);
+        assert_eq!(pipeline.priority, 0);
+    }
+
+    #[test]
+    fn test_yaml_parsing_needs() {
+        let yaml_content = read_yaml_file("src/tests/data/needs_pipeline.yaml");
+        let parser = PipeParser::default();
+        let result = parser.parse(yaml_content);
+
+        assert!(result.is_ok());
+        let pipeline = result.unwrap();
+        assert_eq!(pipeline.max_parallelism, Some(2));
+        assert_eq!(pipeline.priority, 5);
+
+        let build_action = pipeline.actions.iter().find(|a| a.name == "build").unwrap();
+        assert!(build_action.needs.is_empty());
+
+        let lint_action = pipeline.actions.iter().find(|a| a.name == "lint").unwrap();
+        assert!(lint_action.needs.is_empty());
+
+        let test_action = pipeline.actions.iter().find(|a| a.name == "test").unwrap();
+        assert_eq!(test_action.needs, vec!["build"]);
+
+        let deploy_action = pipeline.actions.iter().find(|a| a.name == "deploy").unwrap();
+        assert_eq!(deploy_action.needs, vec!["test", "lint"]);
+    }
+
+    #[test]
+    fn test_yaml_parsing_needs_unknown() {
+        let yaml_content = read_yaml_file("src/tests/data/unknown_needs_pipeline.yaml");
+        let parser = PipeParser::default();
+        let result = parser.parse(yaml_content);
+
+        assert_eq!(result.unwrap_err(), ParsingError::UnknownNeed(String::from("package")));
+    }
+
+    #[test]
+    fn test_yaml_parsing_needs_cycle() {
+        let yaml_content = read_yaml_file("src/tests/data/cyclic_needs_pipeline.yaml");
+        let parser = PipeParser::default();
+        let result = parser.parse(yaml_content);
+
+        assert_eq!(
+            result.unwrap_err(),
+            ParsingError::CyclicNeeds(vec![
+                String::from("build"),
+                String::from("deploy"),
+                String::from("test"),
+                String::from("build"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_yaml_parsing_matrix() {
+        let yaml_content = read_yaml_file("src/tests/data/matrix_pipeline.yaml");
+        let parser = PipeParser::default();
+        let pipeline = parser.parse(yaml_content).unwrap();
+
+        let names: Vec<&str> = pipeline.actions.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "test (rust:1.75, default)",
+                "test (rust:1.80, default)",
+                "test (rust:1.80, full)",
+                "test (rust:nightly, full)",
+                "package",
+            ]
+        );
+
+        let test_action = &pipeline.actions[2];
+        assert_eq!(test_action.configuration_version, "rust:1.80");
+        assert_eq!(test_action.commands, vec!["cargo test --features full"]);
+        assert_eq!(test_action.env, vec!["FEATURES=full"]);
+        assert!(test_action.needs.is_empty());
+
+        // The actions needing an action with a matrix need all of its combinations.
+        let package_action = &pipeline.actions[4];
+        assert_eq!(package_action.needs, names[..4].to_vec());
+    }
+
+    #[test]
+    fn test_yaml_parsing_matrix_unknown_key() {
+        let yaml_content = read_yaml_file("src/tests/data/unknown_matrix_key_pipeline.yaml");
+        let parser = PipeParser::default();
+        let result = parser.parse(yaml_content);
+
+        assert_eq!(result.unwrap_err(), ParsingError::UnknownMatrixKey(String::from("image")));
+    }
+
+    #[test]
+    fn test_yaml_parsing_conditions() {
+        let yaml_content = read_yaml_file("src/tests/data/conditional_pipeline.yaml");
+        let parser = PipeParser::default();
+        let pipeline = parser.parse(yaml_content).unwrap();
+
+        assert_eq!(pipeline.on.events, vec!["push", "pull_request"]);
+        assert_eq!(pipeline.on.branches, vec!["main", "release/*"]);
+        assert_eq!(pipeline.on.paths, vec!["src/**"]);
+
+        let mut trigger = Trigger {
+            event: String::from("push"),
+            branch: Some(String::from("release/1.0")),
+            tag: None,
+            changed_paths: vec![String::from("src/parser/mod.rs")],
+        };
+        assert!(pipeline.on.matches(&trigger));
+        trigger.changed_paths = vec![String::from("docs/controller.md")];
+        assert!(!pipeline.on.matches(&trigger));
+
+        let deploy_action = pipeline.actions.iter().find(|a| a.name == "deploy").unwrap();
+        let condition = Expression::parse(deploy_action.condition.as_ref().unwrap()).unwrap();
+        let mut context = EvaluationContext {
+            trigger: &trigger,
+            needs_completed: true,
+            needs_failed: false,
+        };
+        assert!(!condition.holds(&context));
+        let main = Trigger {
+            branch: Some(String::from("main")),
+            ..trigger.clone()
+        };
+        context.trigger = &main;
+        assert!(condition.holds(&context));
+
+        // Without a status check, a condition only holds once the actions it needs have completed.
+        context.needs_completed = false;
+        context.needs_failed = true;
+        assert!(!condition.holds(&context));
+        let notify_action = pipeline.actions.iter().find(|a| a.name == "notify").unwrap();
+        let condition = Expression::parse(notify_action.condition.as_ref().unwrap()).unwrap();
+        assert!(condition.holds(&context));
+    }
+
+    #[test]
+    fn test_yaml_parsing_conditions_unknown_function() {
+        let yaml_content = read_yaml_file("src/tests/data/unknown_function_pipeline.yaml");
+        let parser = PipeParser::default();
+        let result = parser.parse(yaml_content);
+
+        assert_eq!(result.unwrap_err(), ParsingError::UnknownFunction(String::from("cancelled")));
+    }
+
+    #[test]
+    fn test_yaml_parsing_retry() {
+        let yaml_content = read_yaml_file("src/tests/data/retry_pipeline.yaml");
+        let parser = PipeParser::default();
+        let pipeline = parser.parse(yaml_content).unwrap();
+
+        let fetch_action = pipeline.actions.iter().find(|a| a.name == "fetch").unwrap();
+        let retry = &fetch_action.retry;
+        assert_eq!(retry.max_attempts, 3);
+        assert!(retry.allows_retry(1, Some(28)));
+        assert!(!retry.allows_retry(1, Some(1)));
+        assert!(!retry.allows_retry(1, None));
+        assert!(!retry.allows_retry(3, Some(28)));
+        assert_eq!(retry.backoff_before(2), Duration::from_secs(5));
+        assert_eq!(retry.backoff_before(3), Duration::from_secs(10));
+
+        let build_action = pipeline.actions.iter().find(|a| a.name == "build").unwrap();
+        assert_eq!(build_action.retry.max_attempts, 2);
+        assert!(build_action.retry.allows_retry(1, None));
+        assert_eq!(build_action.retry.backoff_before(2), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_yaml_parsing_retry_invalid() {
+        let yaml_content = read_yaml_file("src/tests/data/invalid_retry_pipeline.yaml");
+        let parser = PipeParser::default();
+        let result = parser.parse(yaml_content);
+
+        assert_eq!(result.unwrap_err(), ParsingError::InvalidRetry);
+    }
+
+    #[test]
+    fn test_yaml_parsing_include_and_extends() {
+        let yaml_content = read_yaml_file("src/tests/data/included_pipeline.yaml");
+        let templates = TemplateStore::new("src/tests/data/templates");
+        let parser = PipeParser::default();
+        let pipeline = parser
+            .parse_with_templates(yaml_content.clone(), &templates)
+            .unwrap();
+
+        assert_eq!(pipeline.max_parallelism, Some(2));
+        let names: Vec<&str> = pipeline.actions.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, vec!["build", "test"]);
+
+        let build_action = &pipeline.actions[0];
+        assert_eq!(build_action.configuration_version, "rust:1.81");
+        assert_eq!(build_action.commands, vec!["cargo build --release"]);
+        assert_eq!(build_action.env, vec!["CARGO_TERM_COLOR=always"]);
+
+        let test_action = &pipeline.actions[1];
+        assert_eq!(test_action.configuration_version, "rust:1.80");
+        assert_eq!(test_action.env, vec!["CARGO_TERM_COLOR=always", "RUST_LOG=debug"]);
+
+        let resolved = parser.resolve(yaml_content.clone(), &templates).unwrap();
+        assert!(resolved.contains("rust:1.81"));
+        assert!(!resolved.contains("include"));
+        assert!(!resolved.contains("extends"));
+
+        // Without templates, the manifest cannot be parsed.
+        let result = parser.parse(yaml_content);
+        assert_eq!(result.unwrap_err(), ParsingError::UnknownTemplate(String::from("rust.yaml")));
+    }
+
+    #[test]
+    fn test_yaml_parsing_include_cycle() {
+        let yaml_content = read_yaml_file("src/tests/data/cyclic_include_pipeline.yaml");
+        let templates = TemplateStore::new("src/tests/data/templates");
+        let parser = PipeParser::default();
+        let result = parser.parse_with_templates(yaml_content, &templates);
+
+        assert_eq!(
+            result.unwrap_err(),
+            ParsingError::CyclicIncludes(vec![
+                String::from("cycle_a.yaml"),
+                String::from("cycle_b.yaml"),
+                String::from("cycle_a.yaml"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_yaml_validation_reports_every_error() {
+        let yaml_content = read_yaml_file("src/tests/data/diagnostics_pipeline.yaml");
+        let parser = PipeParser::default();
+        let diagnostics = parser.validate(yaml_content.clone(), &NoTemplates);
+
+        let found: Vec<(Severity, &str, &str, Option<usize>)> = diagnostics
+            .iter()
+            .map(|d| (d.severity, d.code.as_str(), d.path.as_str(), d.line))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Severity::Error, "MissingCommands", "actions.build.commands", Some(7)),
+                (Severity::Error, "MissingCommands", "actions.test.commands", Some(8)),
+                (Severity::Error, "InvalidRetry", "actions.test.retry", Some(13)),
+                (Severity::Warning, "UnknownKey", "actions.test.command", Some(11)),
+                (Severity::Error, "InvalidMaxParallelism", "max_parallelism", Some(2)),
+                (Severity::Error, "InvalidPriority", "priority", Some(14)),
+            ]
+        );
+        assert_eq!(diagnostics[0].column, Some(5));
+        assert_eq!(diagnostics[3].suggestion.as_deref(), Some("did you mean `commands`?"));
+
+        // Parsing still stops at the first error.
+        let result = parser.parse(yaml_content);
+        assert_eq!(result.unwrap_err(), ParsingError::MissingCommands);
+    }
+
+    #[test]
+    fn test_yaml_validation_suggests_need() {
+        let yaml_content = read_yaml_file("src/tests/data/unknown_need_typo_pipeline.yaml");
+        let parser = PipeParser::default();
+        let diagnostics = parser.validate(yaml_content, &NoTemplates);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "UnknownNeed");
+        assert_eq!(diagnostics[0].path, "actions.test.needs");
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (Some(13), Some(5)));
+        assert_eq!(diagnostics[0].suggestion.as_deref(), Some("did you mean `build`?"));
+
+        let diagnostics = parser.validate(String::from("name: test\n  actions: none\n"), &NoTemplates);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "YamlNotCompliant");
+        assert_eq!(diagnostics[0].line, Some(2));
+    }
 }
@@ -6,10 +6,12 @@ fn main() -> Result<(), Box<dyn Error>> {
 
 	tonic_build::configure()
 		.file_descriptor_set_path(out_dir.join("scheduler_descriptor.bin"))
+		.type_attribute("scheduler.Capabilities", "#[derive(Eq)]") // Agents of the Pool are compared as a whole
 		.compile(
 			&[
 				"../api/proto/scheduler/agent.proto",
 				"../api/proto/scheduler/controller.proto",
+				"../api/proto/scheduler/admin.proto",
 				"../api/proto/agent/actions.proto",
 			],
 			&["../api/proto"])?;
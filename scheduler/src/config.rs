@@ -0,0 +1,387 @@
+use crate::interfaces::client::scale_hook::ScaleHook;
+use crate::logic::agent_pool_logic::Strategy;
+use crate::logic::autoscaler_logic::Autoscaler;
+
+use clap::Parser;
+use log::LevelFilter;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use tonic::transport::{Certificate, Identity, ServerTlsConfig};
+
+/// Command line arguments of the Scheduler.
+/// Each of them can also be set through its environment variable, and overrides the configuration file.
+#[derive(Parser, Debug, Default)]
+#[command(author, version, about, long_about = None)]
+pub struct Args {
+    /// Path to a YAML configuration file
+    #[arg(long, env = "SCHEDULER_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Address the gRPC server listens on
+    #[arg(long, env = "SCHEDULER_LISTEN_ADDRESS")]
+    pub listen_address: Option<SocketAddr>,
+
+    /// Seconds without any health report after which an Agent is removed from the Pool
+    #[arg(long, env = "SCHEDULER_HEARTBEAT_TIMEOUT")]
+    pub heartbeat_timeout: Option<u64>,
+
+    /// Maximum number of Actions waiting in the Action Queue
+    #[arg(long, env = "SCHEDULER_MAX_QUEUED_ACTIONS")]
+    pub max_queued_actions: Option<usize>,
+
+    /// Strategy used to pick an Agent: lowest-score, highest-score or least-loaded
+    #[arg(long, env = "SCHEDULER_STRATEGY")]
+    pub strategy: Option<Strategy>,
+
+    /// Maximum number of Actions an Agent runs at the same time
+    #[arg(long, env = "SCHEDULER_AGENT_CAPACITY")]
+    pub agent_capacity: Option<usize>,
+
+    /// Number of different Agents an Action is tried on before giving up on dispatching it
+    #[arg(long, env = "SCHEDULER_DISPATCH_ATTEMPTS")]
+    pub dispatch_attempts: Option<u32>,
+
+    /// Number of failed dispatches in a row after which an Agent is quarantined
+    #[arg(long, env = "SCHEDULER_BREAKER_THRESHOLD")]
+    pub breaker_threshold: Option<u32>,
+
+    /// Seconds an Agent stays quarantined before it is given a probing Action
+    #[arg(long, env = "SCHEDULER_BREAKER_COOLDOWN")]
+    pub breaker_cooldown: Option<u64>,
+
+    /// Number of responses kept per Action, for Controllers to attach again
+    #[arg(long, env = "SCHEDULER_LOG_BUFFER_SIZE")]
+    pub log_buffer_size: Option<usize>,
+
+    /// Seconds a Controller has to attach again to an Action before it is cancelled
+    #[arg(long, env = "SCHEDULER_REATTACH_GRACE_PERIOD")]
+    pub reattach_grace_period: Option<u64>,
+
+    /// Path to the PEM encoded TLS certificate of the server
+    #[arg(long, env = "SCHEDULER_TLS_CERT")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// Path to the PEM encoded TLS private key of the server
+    #[arg(long, env = "SCHEDULER_TLS_KEY")]
+    pub tls_key: Option<PathBuf>,
+
+    /// Path to the PEM encoded CA certificate clients must present a certificate from
+    #[arg(long, env = "SCHEDULER_TLS_CLIENT_CA")]
+    pub tls_client_ca: Option<PathBuf>,
+
+    /// Log level: off, error, warn, info, debug or trace. RUST_LOG still takes precedence.
+    #[arg(long, env = "SCHEDULER_LOG_LEVEL")]
+    pub log_level: Option<String>,
+}
+
+/// TLS certificate paths of the gRPC server.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    pub client_ca: Option<PathBuf>,
+}
+
+/// Autoscaling settings: when to call the scaling hook, and which one.
+/// Exactly one of `command` and `url` must be set, along with at least one threshold.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AutoscalingConfig {
+    pub command: Option<String>, // Shell command given the scale request on its standard input
+    pub url: Option<String>, // HTTP URL the scale request is POSTed to
+    pub interval: u64, // In seconds, between two checks of the thresholds
+    pub queue_depth: Option<usize>, // Queued Actions from which more Agents are asked for
+    pub wait_time: Option<u64>, // In seconds an Action waited, from which more Agents are asked for
+    pub idle_time: Option<u64>, // In seconds an Agent sat idle, from which fewer Agents are asked for
+    pub scale_up_cooldown: u64, // In seconds
+    pub scale_down_cooldown: u64, // In seconds
+}
+
+impl Default for AutoscalingConfig {
+    fn default() -> Self {
+        Self {
+            command: None,
+            url: None,
+            interval: 10,
+            queue_depth: None,
+            wait_time: None,
+            idle_time: None,
+            scale_up_cooldown: 60,
+            scale_down_cooldown: 300,
+        }
+    }
+}
+
+impl AutoscalingConfig {
+    /// The Autoscaler with these thresholds and cooldowns.
+    pub fn autoscaler(&self) -> Autoscaler {
+        Autoscaler::new()
+            .with_queue_depth(self.queue_depth)
+            .with_wait_time(self.wait_time.map(Duration::from_secs))
+            .with_idle_time(self.idle_time.map(Duration::from_secs))
+            .with_cooldowns(
+                Duration::from_secs(self.scale_up_cooldown),
+                Duration::from_secs(self.scale_down_cooldown),
+            )
+    }
+
+    /// The hook to call. Only valid once the settings are validated.
+    pub fn hook(&self) -> ScaleHook {
+        match (&self.command, &self.url) {
+            (Some(command), _) => ScaleHook::Command(command.clone()),
+            (None, url) => ScaleHook::Url(url.clone().unwrap_or_default()),
+        }
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        match (&self.command, &self.url) {
+            (Some(_), Some(_)) | (None, None) => {
+                return Err(ConfigError::InvalidValue(String::from(
+                    "autoscaling needs either a command or a URL to call",
+                )))
+            }
+            (None, Some(url)) if !url.starts_with("http://") => {
+                return Err(ConfigError::InvalidValue(format!(
+                    "the autoscaling URL '{}' must be an http:// URL",
+                    url
+                )))
+            }
+            _ => {}
+        }
+        if self.queue_depth.is_none() && self.wait_time.is_none() && self.idle_time.is_none() {
+            return Err(ConfigError::InvalidValue(String::from(
+                "autoscaling needs at least one of the queue depth, wait time and idle time thresholds",
+            )));
+        }
+        if self.queue_depth == Some(0) {
+            return Err(ConfigError::InvalidValue(String::from(
+                "the autoscaling queue depth must be at least 1 Action",
+            )));
+        }
+        if self.interval == 0 {
+            return Err(ConfigError::InvalidValue(String::from(
+                "the autoscaling interval must be at least 1 second",
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Configuration of the Scheduler.
+/// It is read from the configuration file, if any, then overridden by the command line arguments and environment variables.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub listen_address: SocketAddr,
+    pub heartbeat_timeout: u64, // In seconds
+    pub max_queued_actions: usize,
+    pub strategy: Strategy,
+    pub agent_capacity: usize,
+    pub tenant_weights: HashMap<String, u32>, // Share of the Agents each tenant gets, relative to the others. Tenants not listed have a weight of 1
+    pub dispatch_attempts: u32,
+    pub breaker_threshold: u32,
+    pub breaker_cooldown: u64, // In seconds
+    pub log_buffer_size: usize,
+    pub reattach_grace_period: u64, // In seconds
+    pub tls: Option<TlsConfig>,
+    pub autoscaling: Option<AutoscalingConfig>,
+    pub log_level: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            listen_address: SocketAddr::from(([0u16; 8], 50051)),
+            heartbeat_timeout: 60,
+            max_queued_actions: 1000,
+            strategy: Strategy::default(),
+            agent_capacity: 1,
+            tenant_weights: HashMap::new(),
+            dispatch_attempts: 3,
+            breaker_threshold: 3,
+            breaker_cooldown: 30,
+            log_buffer_size: 1000,
+            reattach_grace_period: 30,
+            tls: None,
+            autoscaling: None,
+            log_level: String::from("info"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    ReadError(PathBuf, std::io::Error),
+    ParseError(PathBuf, serde_yaml::Error),
+    InvalidValue(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::ReadError(path, e) => write!(f, "Failed to read {}: {}", path.display(), e),
+            ConfigError::ParseError(path, e) => write!(f, "Failed to parse {}: {}", path.display(), e),
+            ConfigError::InvalidValue(message) => write!(f, "Invalid configuration: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Build the configuration from the command line arguments and environment variables, on top of the configuration file they point to.
+    /// The resulting configuration is validated.
+    pub fn load(args: Args) -> Result<Self, ConfigError> {
+        let mut config = match &args.config {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+
+        if let Some(listen_address) = args.listen_address {
+            config.listen_address = listen_address;
+        }
+        if let Some(heartbeat_timeout) = args.heartbeat_timeout {
+            config.heartbeat_timeout = heartbeat_timeout;
+        }
+        if let Some(max_queued_actions) = args.max_queued_actions {
+            config.max_queued_actions = max_queued_actions;
+        }
+        if let Some(strategy) = args.strategy {
+            config.strategy = strategy;
+        }
+        if let Some(agent_capacity) = args.agent_capacity {
+            config.agent_capacity = agent_capacity;
+        }
+        if let Some(dispatch_attempts) = args.dispatch_attempts {
+            config.dispatch_attempts = dispatch_attempts;
+        }
+        if let Some(breaker_threshold) = args.breaker_threshold {
+            config.breaker_threshold = breaker_threshold;
+        }
+        if let Some(breaker_cooldown) = args.breaker_cooldown {
+            config.breaker_cooldown = breaker_cooldown;
+        }
+        if let Some(log_buffer_size) = args.log_buffer_size {
+            config.log_buffer_size = log_buffer_size;
+        }
+        if let Some(reattach_grace_period) = args.reattach_grace_period {
+            config.reattach_grace_period = reattach_grace_period;
+        }
+        if let Some(log_level) = args.log_level {
+            config.log_level = log_level;
+        }
+        match (args.tls_cert, args.tls_key) {
+            (Some(cert), Some(key)) => {
+                let client_ca = args
+                    .tls_client_ca
+                    .or_else(|| config.tls.take().and_then(|tls| tls.client_ca));
+                config.tls = Some(TlsConfig { cert, key, client_ca });
+            }
+            (None, None) => {
+                if let Some(client_ca) = args.tls_client_ca {
+                    match config.tls.as_mut() {
+                        Some(tls) => tls.client_ca = Some(client_ca),
+                        None => {
+                            return Err(ConfigError::InvalidValue(String::from(
+                                "a TLS client CA requires a TLS certificate and key",
+                            )))
+                        }
+                    }
+                }
+            }
+            _ => {
+                return Err(ConfigError::InvalidValue(String::from(
+                    "the TLS certificate and key must be given together",
+                )))
+            }
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Read the configuration from a YAML file. Missing settings take their default value.
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let contents = std::fs::read_to_string(path).map_err(|e| ConfigError::ReadError(path.to_path_buf(), e))?;
+        serde_yaml::from_str(&contents).map_err(|e| ConfigError::ParseError(path.to_path_buf(), e))
+    }
+
+    /// Check that the settings are usable.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.heartbeat_timeout == 0 {
+            return Err(ConfigError::InvalidValue(String::from(
+                "the heartbeat timeout must be at least 1 second",
+            )));
+        }
+        if self.max_queued_actions == 0 {
+            return Err(ConfigError::InvalidValue(String::from(
+                "the Action Queue must hold at least 1 Action",
+            )));
+        }
+        if self.agent_capacity == 0 {
+            return Err(ConfigError::InvalidValue(String::from(
+                "an Agent must be able to run at least 1 Action",
+            )));
+        }
+        if let Some((tenant, _)) = self.tenant_weights.iter().find(|(_, weight)| **weight == 0) {
+            return Err(ConfigError::InvalidValue(format!(
+                "the weight of tenant '{}' must be at least 1",
+                tenant
+            )));
+        }
+        if self.dispatch_attempts == 0 {
+            return Err(ConfigError::InvalidValue(String::from(
+                "an Action must be tried on at least 1 Agent",
+            )));
+        }
+        if self.breaker_threshold == 0 {
+            return Err(ConfigError::InvalidValue(String::from(
+                "the circuit breaker threshold must be at least 1 failure",
+            )));
+        }
+        if self.log_buffer_size == 0 {
+            return Err(ConfigError::InvalidValue(String::from(
+                "at least 1 response must be kept per Action",
+            )));
+        }
+        if LevelFilter::from_str(&self.log_level).is_err() {
+            return Err(ConfigError::InvalidValue(format!(
+                "unknown log level '{}', expected one of: off, error, warn, info, debug, trace",
+                self.log_level
+            )));
+        }
+        if let Some(autoscaling) = &self.autoscaling {
+            autoscaling.validate()?;
+        }
+        if let Some(tls) = &self.tls {
+            let paths = [Some(&tls.cert), Some(&tls.key), tls.client_ca.as_ref()];
+            for path in paths.into_iter().flatten() {
+                if !path.is_file() {
+                    return Err(ConfigError::InvalidValue(format!("TLS file {} not found", path.display())));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Build the TLS configuration of the gRPC server from the certificate files, or return None if TLS is not enabled.
+    pub fn tls_config(&self) -> Result<Option<ServerTlsConfig>, ConfigError> {
+        let tls = match &self.tls {
+            Some(tls) => tls,
+            None => return Ok(None),
+        };
+
+        let read = |path: &PathBuf| std::fs::read(path).map_err(|e| ConfigError::ReadError(path.clone(), e));
+        let mut tls_config = ServerTlsConfig::new().identity(Identity::from_pem(read(&tls.cert)?, read(&tls.key)?));
+        if let Some(client_ca) = &tls.client_ca {
+            tls_config = tls_config.client_ca_root(Certificate::from_pem(read(client_ca)?));
+        }
+        Ok(Some(tls_config))
+    }
+}
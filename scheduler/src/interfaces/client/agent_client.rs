@@ -2,23 +2,32 @@ use crate::proto::actions as proto;
 use proto::action_service_client::ActionServiceClient as ActionClient;
 
 use crate::logic::action_queue_logic::Action;
+use crate::logic::agent_pool_logic::AgentConnection;
 
 use tonic::transport::Channel;
 use tonic::Request;
 use std::error::Error;
 use log::{info, error};
+use tokio::sync::mpsc;
 
-pub(crate) async fn execution_action(action: Action, agent_address: String) -> Result<tonic::Streaming<proto::ActionResponseStream>, Box<dyn Error + Send + Sync>> {
-    // Handle case where hostname is empty
-    if agent_address == "unknown:unknown" {
-        error!("Hostname is empty. Cannot resolve IP address.");
-        return Err(Box::from("Hostname is empty. Cannot resolve IP address."));
-    }
+/// The responses of an Action from its Agent: either the stream of the Agent the Scheduler dialed, or the responses coming up the Work stream of a pull-mode Agent.
+pub(crate) enum ResponseStream {
+    Push(Box<tonic::Streaming<proto::ActionResponseStream>>),
+    Pull(mpsc::UnboundedReceiver<proto::ActionResponseStream>),
+}
 
-    let channel = Channel::builder(agent_address.parse()?).connect().await?;
-    let mut client = ActionClient::new(channel);
+impl ResponseStream {
+    /// Wait for the next response, or return None once the Agent is done with the Action.
+    pub(crate) async fn message(&mut self) -> Result<Option<proto::ActionResponseStream>, tonic::Status> {
+        match self {
+            ResponseStream::Push(stream) => stream.message().await,
+            ResponseStream::Pull(receiver) => Ok(receiver.recv().await),
+        }
+    }
+}
 
-    let request = Request::new(proto::ActionRequest {
+pub(crate) async fn execution_action(action: &Action, connection: &AgentConnection) -> Result<ResponseStream, Box<dyn Error + Send + Sync>> {
+    let request = proto::ActionRequest {
         action_id: action.get_action_id(),
         context: Some(proto::ExecutionContext {
             r#type: action.get_runner_type(),
@@ -26,9 +35,56 @@ pub(crate) async fn execution_action(action: Action, agent_address: String) -> R
         }),
         commands: action.get_commands().iter().map(|comm: &String| String::from(comm)).collect(),
         repo_url: action.get_repo_url().clone(),
-    });
+        env: action.get_env().to_vec(),
+    };
+
+    let agent_address = match connection {
+        AgentConnection::Push(agent_address) => agent_address,
+        // A pull-mode Agent gets the Action down its Work stream, then tells whether it started it.
+        AgentConnection::Pull(work_stream) => {
+            return match work_stream.start(request).await {
+                Ok(receiver) => Ok(ResponseStream::Pull(receiver)),
+                Err(reason) => Err(Box::from(reason)),
+            };
+        }
+    };
+
+    // Handle case where hostname is empty
+    if agent_address == "unknown:unknown" {
+        error!("Hostname is empty. Cannot resolve IP address.");
+        return Err(Box::from("Hostname is empty. Cannot resolve IP address."));
+    }
+
+    let channel = Channel::builder(agent_address.parse()?).connect().await?;
+    let mut client = ActionClient::new(channel);
 
     // The response stream is returned to the caller function for further processing. (controller_interface.rs)
-    let response_stream = client.execution_action(request).await?.into_inner();
-    Ok(response_stream)
+    let response_stream = client.execution_action(Request::new(request)).await?.into_inner();
+    Ok(ResponseStream::Push(Box::new(response_stream)))
+}
+
+/// Ask the Agent to cancel the given Action, stopping it where it is.
+pub(crate) async fn cancel_action(action_id: u32, connection: &AgentConnection) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let agent_address = match connection {
+        AgentConnection::Push(agent_address) => agent_address,
+        AgentConnection::Pull(work_stream) => {
+            if !work_stream.cancel(action_id) {
+                return Err(Box::from("The Work stream of the Agent is closed."));
+            }
+            info!("Action {} cancelled through the Work stream of its Agent", action_id);
+            return Ok(());
+        }
+    };
+
+    if agent_address == "unknown:unknown" {
+        error!("Hostname is empty. Cannot resolve IP address.");
+        return Err(Box::from("Hostname is empty. Cannot resolve IP address."));
+    }
+
+    let channel = Channel::builder(agent_address.parse()?).connect().await?;
+    let mut client = ActionClient::new(channel);
+
+    client.cancel_action(Request::new(proto::CancelActionRequest { action_id })).await?;
+    info!("Action {} cancelled on Agent {}", action_id, agent_address);
+    Ok(())
 }
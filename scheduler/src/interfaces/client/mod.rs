@@ -1 +1,2 @@
 pub mod agent_client;
+pub mod scale_hook;
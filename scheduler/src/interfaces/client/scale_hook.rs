@@ -0,0 +1,97 @@
+use crate::logic::action_queue_logic::ActionsQueue;
+use crate::logic::agent_pool_logic::AgentPool;
+use crate::logic::autoscaler_logic::{Autoscaler, ScaleRequest};
+
+use log::{error, info};
+use std::error::Error;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::Mutex;
+
+/// Time a scaling hook has to complete before it is given up on.
+const HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What the Autoscaler calls to scale the Agents, with the JSON scale request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScaleHook {
+    /// A shell command, given the request on its standard input. It must exit successfully.
+    Command(String),
+    /// An HTTP URL the request is POSTed to. It must respond with a success status.
+    Url(String),
+}
+
+impl ScaleHook {
+    /// Call the hook with the given request.
+    pub async fn call(&self, request: &ScaleRequest) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let payload = serde_json::to_vec(request)?;
+        match tokio::time::timeout(HOOK_TIMEOUT, self.send(payload)).await {
+            Ok(result) => result,
+            Err(_) => Err(Box::from("The scaling hook timed out.")),
+        }
+    }
+
+    async fn send(&self, payload: Vec<u8>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match self {
+            ScaleHook::Command(command) => {
+                let mut child = Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .stdin(Stdio::piped())
+                    .kill_on_drop(true)
+                    .spawn()?;
+                if let Some(mut stdin) = child.stdin.take() {
+                    stdin.write_all(&payload).await?;
+                }  // Dropping stdin closes it, so that the command sees the end of the request
+                let status = child.wait().await?;
+                if !status.success() {
+                    return Err(format!("The scaling command failed: {}", status).into());
+                }
+            }
+            ScaleHook::Url(url) => {
+                let request = hyper::Request::post(url.as_str())
+                    .header(hyper::header::CONTENT_TYPE, "application/json")
+                    .body(hyper::Body::from(payload))?;
+                let response = hyper::Client::new().request(request).await?;
+                if !response.status().is_success() {
+                    return Err(format!("The scaling URL responded with {}", response.status()).into());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check the Queue and the Pool every `interval`, and call the hook whenever the Autoscaler asks for more or fewer Agents.
+/// A failed call does not start the cooldown, so it is tried again on the next check.
+pub async fn autoscale(
+    mut autoscaler: Autoscaler,
+    hook: ScaleHook,
+    agent_pool: Arc<Mutex<AgentPool>>,
+    action_queue: Arc<Mutex<ActionsQueue>>,
+    interval: Duration,
+) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        let request = {
+            let pool = agent_pool.lock().await;
+            let queue = action_queue.lock().await;
+            autoscaler.evaluate(&pool, &queue)
+        };
+        let Some(request) = request else {
+            continue;
+        };
+
+        info!(
+            "Scaling {:?} to {} Agents ({:?}, {} queued Actions)",
+            request.direction, request.desired_agents, request.reason, request.queued_actions
+        );
+        match hook.call(&request).await {
+            Ok(()) => autoscaler.record_scaled(),
+            Err(e) => error!("Scaling hook failed: {}", e),
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use crate::logic::action_queue_logic::ActionsQueue;
+use crate::logic::agent_pool_logic::Agent as PoolAgent;
+use crate::logic::agent_pool_logic::AgentPool;
+
+use crate::proto::scheduler as proto;
+use proto::admin_server::Admin;
+
+use log::{info, warn};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+pub struct AdminService {
+    agent_pool: Arc<Mutex<AgentPool>>,
+    action_queue: Arc<Mutex<ActionsQueue>>,
+}
+
+impl AdminService {
+    pub fn new(agent_pool: Arc<Mutex<AgentPool>>, action_queue: Arc<Mutex<ActionsQueue>>) -> Self {
+        Self {
+            agent_pool,
+            action_queue,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Admin for AdminService {
+    async fn list_agents(
+        &self,
+        _request: tonic::Request<proto::ListAgentsRequest>,
+    ) -> Result<tonic::Response<proto::ListAgentsResponse>, tonic::Status> {
+        let pool = self.agent_pool.lock().await;
+
+        let agents = pool
+            .iter()
+            .map(|agent| agent_info(agent, pool.has_work_stream(agent.get_id())))
+            .collect();
+
+        Ok(tonic::Response::new(proto::ListAgentsResponse { agents }))
+    }
+
+    async fn list_queue(
+        &self,
+        _request: tonic::Request<proto::ListQueueRequest>,
+    ) -> Result<tonic::Response<proto::ListQueueResponse>, tonic::Status> {
+        let queue = self.action_queue.lock().await;
+
+        let actions = queue
+            .iter()
+            .map(|queued| {
+                let action = queued.get_action();
+                proto::QueuedActionInfo {
+                    action_id: action.get_action_id(),
+                    repo_url: action.get_repo_url().clone(),
+                    container_image: action.get_context().container_image.clone(),
+                    enqueued_at: unix_seconds(queued.get_enqueued_at()),
+                    tenant: action.get_tenant().to_string(),
+                    priority: action.get_priority(),
+                    runs_on: action.get_runs_on().to_vec(),
+                }
+            })
+            .collect();
+
+        Ok(tonic::Response::new(proto::ListQueueResponse { actions }))
+    }
+
+    async fn get_action(
+        &self,
+        request: tonic::Request<proto::GetActionRequest>,
+    ) -> Result<tonic::Response<proto::ActionInfo>, tonic::Status> {
+        let action_id = request.into_inner().action_id;
+
+        // Lock the Agent Pool first, then the Action Queue, as everywhere else, so that the Action cannot be dispatched in between.
+        let pool = self.agent_pool.lock().await;
+        let queue = self.action_queue.lock().await;
+
+        if let Some((agent, running)) = pool.find_running_action(action_id) {
+            return Ok(tonic::Response::new(proto::ActionInfo {
+                action_id,
+                state: proto::AdminActionState::Running.into(),
+                status: proto::ActionStatus::from(proto::AdminActionState::Running).into(),
+                agent_id: Some(agent.get_id()),
+                since: unix_seconds(running.get_since()),
+            }));
+        }
+
+        if let Some(queued) = queue.find(action_id) {
+            return Ok(tonic::Response::new(proto::ActionInfo {
+                action_id,
+                state: proto::AdminActionState::Queued.into(),
+                status: proto::ActionStatus::from(proto::AdminActionState::Queued).into(),
+                agent_id: None,
+                since: unix_seconds(queued.get_enqueued_at()),
+            }));
+        }
+
+        if let Some(cancelled) = queue.find_cancelled(action_id) {
+            return Ok(tonic::Response::new(proto::ActionInfo {
+                action_id,
+                state: proto::AdminActionState::Cancelled.into(),
+                status: proto::ActionStatus::from(proto::AdminActionState::Cancelled).into(),
+                agent_id: cancelled.get_agent_id(),
+                since: unix_seconds(cancelled.get_cancelled_at()),
+            }));
+        }
+
+        Err(tonic::Status::not_found(format!("Action {} is neither queued, running nor cancelled", action_id)))
+    }
+
+    async fn cordon_agent(
+        &self,
+        request: tonic::Request<proto::AgentRequest>,
+    ) -> Result<tonic::Response<proto::AgentInfo>, tonic::Status> {
+        let agent_id = request.into_inner().agent_id;
+
+        let mut pool = self.agent_pool.lock().await;
+        let connected = pool.has_work_stream(agent_id);
+        let agent = pool
+            .find_agent_mut(agent_id)
+            .ok_or_else(|| agent_not_found(agent_id))?;
+
+        agent.set_cordoned(true);
+        info!("Agent {} cordoned", agent_id);
+
+        Ok(tonic::Response::new(agent_info(agent, connected)))
+    }
+
+    async fn uncordon_agent(
+        &self,
+        request: tonic::Request<proto::AgentRequest>,
+    ) -> Result<tonic::Response<proto::AgentInfo>, tonic::Status> {
+        let agent_id = request.into_inner().agent_id;
+
+        let mut pool = self.agent_pool.lock().await;
+        let agent = pool
+            .find_agent_mut(agent_id)
+            .ok_or_else(|| agent_not_found(agent_id))?;
+
+        agent.set_cordoned(false);
+        info!("Agent {} uncordoned", agent_id);
+
+        // The Agent can take Actions again: hand it the ones waiting in the Queue.
+        let mut queue = self.action_queue.lock().await;
+        queue.dispatch(&mut pool);
+
+        let agent = pool
+            .find_agent(agent_id)
+            .ok_or_else(|| agent_not_found(agent_id))?;
+
+        Ok(tonic::Response::new(agent_info(agent, pool.has_work_stream(agent_id))))
+    }
+
+    async fn evict_agent(
+        &self,
+        request: tonic::Request<proto::AgentRequest>,
+    ) -> Result<tonic::Response<proto::AgentInfo>, tonic::Status> {
+        let agent_id = request.into_inner().agent_id;
+
+        let mut pool = self.agent_pool.lock().await;
+        let agent = pool
+            .remove(agent_id)
+            .ok_or_else(|| agent_not_found(agent_id))?;
+
+        // Running Actions are not interrupted, the evicted Agent will just not be given any new Action.
+        if !agent.get_running_actions().is_empty() {
+            warn!(
+                "Agent {} evicted while running {} Action(s)",
+                agent_id,
+                agent.get_running_actions().len()
+            );
+        }
+        info!("Agent {} evicted", agent_id);
+
+        Ok(tonic::Response::new(agent_info(&agent, false)))
+    }
+}
+
+fn agent_info(agent: &PoolAgent, connected: bool) -> proto::AgentInfo {
+    let (cpu_avail, memory_avail) = agent.get_health();
+    proto::AgentInfo {
+        id: agent.get_id(),
+        hostname: Some(proto::Hostname {
+            host: agent.get_hostname().get_host().to_string(),
+            port: agent.get_hostname().get_port(),
+        }),
+        health: Some(proto::Health {
+            cpu_avail,
+            memory_avail,
+        }),
+        score: agent.get_score(),
+        running_actions: agent
+            .get_running_actions()
+            .iter()
+            .map(|running| running.get_action_id())
+            .collect(),
+        cordoned: agent.is_cordoned(),
+        quarantined: agent.is_quarantined(),
+        labels: agent.get_labels().to_vec(),
+        capabilities: Some(agent.get_capabilities().clone()),
+        pull: agent.is_pull(),
+        connected: agent.is_pull() && connected,
+        ephemeral: agent.is_ephemeral(),
+    }
+}
+
+fn agent_not_found(agent_id: u32) -> tonic::Status {
+    tonic::Status::not_found(format!("Agent {} not found in the Pool", agent_id))
+}
+
+/// Convert a point in time to a Unix timestamp, in seconds.
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
@@ -1,30 +1,41 @@
+use crate::logic::action_queue_logic::ActionsQueue;
 use crate::logic::agent_pool_logic::Agent as PoolAgent;
 use crate::logic::agent_pool_logic::{compute_score, AgentPool};
-use crate::logic::agent_pool_logic::Hostname;
-use log::{error, info};
+use crate::logic::agent_pool_logic::{Hostname, WorkStream};
+use log::{error, info, warn};
 
 //use crate::proto::agent as proto;
 use crate::proto::scheduler as proto;
 use proto::agent_server::Agent;
+use proto::work_request::Message;
 
+use std::pin::Pin;
 use std::sync::Arc;
+use tokio::sync::mpsc;
 use tokio::sync::Mutex;
-use tokio_stream::StreamExt;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_stream::{Stream, StreamExt};
 
 pub struct AgentService {
     agent_pool: Arc<Mutex<AgentPool>>, // The ArcMutex is on the agent_pool, for the highest level of granularity on concurrency control
+    action_queue: Arc<Mutex<ActionsQueue>>,
 }
 
 impl AgentService {
-    pub fn new(agent_pool: Arc<Mutex<AgentPool>>) -> Self {
+    pub fn new(agent_pool: Arc<Mutex<AgentPool>>, action_queue: Arc<Mutex<ActionsQueue>>) -> Self {
         Self {
             agent_pool,
+            action_queue,
         }
     }
 }
 
+type WorkResponseStream = Pin<Box<dyn Stream<Item = Result<proto::WorkResponse, tonic::Status>> + Send>>;
+
 #[tonic::async_trait]
 impl Agent for AgentService {
+    type WorkStream = WorkResponseStream;
+
     async fn register_agent(
         &self,
         request: tonic::Request<proto::RegisterAgentRequest>,
@@ -46,8 +57,10 @@ impl Agent for AgentService {
             input.cpu_avail, input.memory_avail
         );
 
+        // A pull-mode Agent is never dialed, so it does not need to be reachable.
         let hostname = match inner_req.hostname {
             Some(host) => host,
+            None if inner_req.pull => proto::Hostname::default(),
             None => {
                 error!("Hostname is missing in the request");
                 return Err(tonic::Status::invalid_argument("Hostname is missing"));
@@ -68,7 +81,17 @@ impl Agent for AgentService {
         let new_hostname = Hostname::new(hostname.host, hostname.port);
 
         // Create a new Agent and add it to the Pool (it gets sorted)
-        let new_agent = PoolAgent::new(id, new_hostname, score);
+        let mut new_agent = PoolAgent::new(id, new_hostname, score);
+        new_agent.set_health(input.cpu_avail, input.memory_avail);
+        let capabilities = inner_req.capabilities.unwrap_or_default();
+        info!(
+            "Agent {} labels: {:?}, capabilities: {:?}",
+            id, inner_req.labels, capabilities
+        );
+        new_agent.set_labels(inner_req.labels);
+        new_agent.set_capabilities(capabilities);
+        new_agent.set_pull(inner_req.pull);
+        new_agent.set_ephemeral(inner_req.ephemeral);
 
         // Response is the newly created Agent's ID.
         let response = proto::RegisterAgentResponse {
@@ -77,6 +100,10 @@ impl Agent for AgentService {
 
         pool.push(new_agent);
 
+        // The new Agent can take the Actions waiting in the Queue.
+        let mut queue = self.action_queue.lock().await;
+        queue.dispatch(&mut pool);
+
         Ok(tonic::Response::new(response))
     }
 
@@ -123,6 +150,7 @@ impl Agent for AgentService {
             // Compute the Agent's new score and set it.
             let updated_score = compute_score(health.cpu_avail, health.memory_avail / 100_000_000);  // Divide by 10^8 to have the same scale/order of magnitude as the CPU.
             agent.set_score(updated_score);
+            agent.set_health(health.cpu_avail, health.memory_avail);
 
             // Check if the Agent's position in the Pool is now out of order
             let is_out_of_order = pool.check_agent_neighbors(status.agent_id);
@@ -133,4 +161,100 @@ impl Agent for AgentService {
 
         Ok(tonic::Response::new(proto::Empty {}))
     }
+
+    async fn unregister_agent(
+        &self,
+        request: tonic::Request<proto::UnregisterAgentRequest>,
+    ) -> Result<tonic::Response<proto::Empty>, tonic::Status> {
+        let agent_id = request.into_inner().id;
+
+        let mut pool = self.agent_pool.lock().await;
+        let agent = match pool.remove(agent_id) {
+            Some(agent) => agent,
+            None => {
+                error!("Agent ID {} not found in the Pool", agent_id);
+                return Err(tonic::Status::not_found(format!("Agent {} not found", agent_id)));
+            }
+        };
+
+        // Running Actions are not interrupted, like when the Agent is evicted.
+        if !agent.get_running_actions().is_empty() {
+            warn!(
+                "Agent {} unregistered while running {} Action(s)",
+                agent_id,
+                agent.get_running_actions().len()
+            );
+        }
+        info!("Agent {} unregistered", agent_id);
+
+        Ok(tonic::Response::new(proto::Empty {}))
+    }
+
+    async fn work(
+        &self,
+        request: tonic::Request<tonic::Streaming<proto::WorkRequest>>,
+    ) -> Result<tonic::Response<Self::WorkStream>, tonic::Status> {
+        let mut stream = request.into_inner();
+
+        // The Agent first tells which one it is.
+        let agent_id = match stream.message().await? {
+            Some(proto::WorkRequest {
+                message: Some(Message::AgentId(agent_id)),
+            }) => agent_id,
+            _ => {
+                error!("Work stream opened without any Agent ID");
+                return Err(tonic::Status::invalid_argument("The Work stream must start with the Agent ID"));
+            }
+        };
+
+        let (work_tx, work_rx) = mpsc::unbounded_channel();
+        let work_stream = WorkStream::new(work_tx);
+        {
+            // Lock the Agent Pool, then the Action Queue, to hand the Agent the Actions waiting in the Queue.
+            let mut pool = self.agent_pool.lock().await;
+            match pool.find_agent(agent_id) {
+                Some(agent) if agent.is_pull() => {}
+                Some(_) => {
+                    error!("Agent {} opened a Work stream without registering in pull mode", agent_id);
+                    return Err(tonic::Status::failed_precondition("The Agent did not register in pull mode"));
+                }
+                None => {
+                    error!("Agent ID {} not found in the Pool", agent_id);
+                    return Err(tonic::Status::not_found(format!("Agent {} not found", agent_id)));
+                }
+            }
+            pool.open_work_stream(agent_id, work_stream.clone());
+            info!("Agent {} opened its Work stream", agent_id);
+
+            let mut queue = self.action_queue.lock().await;
+            queue.dispatch(&mut pool);
+        }
+
+        // The responses coming up the stream are passed on to their Actions, until the Agent goes away.
+        let agent_pool = self.agent_pool.clone();
+        tokio::spawn(async move {
+            loop {
+                match stream.message().await {
+                    Ok(Some(request)) => match request.message {
+                        Some(Message::Response(response)) => work_stream.respond(response),
+                        Some(Message::FinishedActionId(action_id)) => work_stream.finish(action_id),
+                        Some(Message::Launched(result)) => work_stream.launched(result),
+                        Some(Message::AgentId(_)) | None => {
+                            warn!("Unexpected message on the Work stream of Agent {}", agent_id)
+                        }
+                    },
+                    Ok(None) => break,
+                    Err(e) => {
+                        warn!("Work stream of Agent {} failed: {}", agent_id, e);
+                        break;
+                    }
+                }
+            }
+            info!("Agent {} closed its Work stream", agent_id);
+            agent_pool.lock().await.close_work_stream(agent_id, &work_stream);
+        });
+
+        let work_rx = UnboundedReceiverStream::new(work_rx).map(Ok);
+        Ok(tonic::Response::new(Box::pin(work_rx) as Self::WorkStream))
+    }
 }
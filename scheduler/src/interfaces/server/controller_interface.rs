@@ -1,9 +1,10 @@
 use crate::interfaces::client::agent_client;
 
-use crate::logic::action_queue_logic::Action;
+use crate::logic::action_log_logic::ActionLogs;
+use crate::logic::action_queue_logic::{Action, ActionsQueue};
 use crate::logic::agent_pool_logic::AgentPool;
+use crate::logic::action_status_logic::{reported_status, ActionLifecycle};
 
-use crate::proto::scheduler::ActionStatus;
 //use crate::proto::controller as proto
 use crate::proto::scheduler as proto;
 use proto::controller_server::Controller;
@@ -11,16 +12,46 @@ use proto::controller_server::Controller;
 use log::{info, warn};
 use std::sync::Arc;
 use tokio::sync::mpsc;
+use tokio::sync::watch;
 use tokio::sync::Mutex;
+use tokio::time::Duration;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 
 pub struct ControllerService {
     agent_pool: Arc<Mutex<AgentPool>>,
+    action_queue: Arc<Mutex<ActionsQueue>>,
+    action_logs: Arc<Mutex<ActionLogs>>,
+    dispatch_attempts: u32,
+    reattach_grace_period: Duration,
 }
 
 impl ControllerService {
-    pub fn new(agent_pool: Arc<Mutex<AgentPool>>) -> Self {
-        Self { agent_pool }
+    pub fn new(agent_pool: Arc<Mutex<AgentPool>>, action_queue: Arc<Mutex<ActionsQueue>>) -> Self {
+        Self {
+            agent_pool,
+            action_queue,
+            action_logs: Arc::new(Mutex::new(ActionLogs::new())),
+            dispatch_attempts: 3,
+            reattach_grace_period: Duration::from_secs(30),
+        }
+    }
+
+    /// Try to dispatch an Action to at most `dispatch_attempts` different Agents before giving up. 3 by default.
+    pub fn with_dispatch_attempts(mut self, dispatch_attempts: u32) -> Self {
+        self.dispatch_attempts = dispatch_attempts;
+        self
+    }
+
+    /// Keep at most `log_buffer_size` responses per Action for the Controllers attaching again. 1000 by default.
+    pub fn with_log_buffer_size(mut self, log_buffer_size: usize) -> Self {
+        self.action_logs = Arc::new(Mutex::new(ActionLogs::new().with_buffer_size(log_buffer_size)));
+        self
+    }
+
+    /// Cancel an Action once no Controller has been attached to it for `reattach_grace_period`. 30 seconds by default.
+    pub fn with_reattach_grace_period(mut self, reattach_grace_period: Duration) -> Self {
+        self.reattach_grace_period = reattach_grace_period;
+        self
     }
 }
 
@@ -29,6 +60,7 @@ type ScheduleActionStream = UnboundedReceiverStream<Result<proto::ActionResponse
 #[tonic::async_trait]
 impl Controller for ControllerService {
     type ScheduleActionStream = ScheduleActionStream;
+    type AttachActionStream = ScheduleActionStream;
 
     async fn schedule_action(
         &self,
@@ -45,11 +77,30 @@ impl Controller for ControllerService {
             runner_type.as_str_name()
         );
 
-        // Lock the agent pool a moment to check for available agents
-        let pool = self.agent_pool.lock().await;
-        let agent = match pool.peek() {
-            Some(agent) => agent,
-            None => {
+        // Create the action object. Agents are shared fairly between tenants, the repository being the tenant unless told otherwise.
+        let tenant = if action_request.tenant.is_empty() {
+            action_request.repo_url.clone()
+        } else {
+            action_request.tenant.clone()
+        };
+        let mut action = Action::new(
+            action_request.action_id,
+            proto::ExecutionContext {
+                container_image,
+                r#type: runner_type.into(),
+            },
+            action_request.commands,
+            action_request.repo_url,
+        );
+        action.set_priority(action_request.priority);
+        action.set_tenant(tenant.clone());
+        action.set_runs_on(action_request.runs_on.clone());
+        action.set_env(action_request.env.clone());
+
+        // Lock the agent pool, then the action queue, to queue the action and dispatch whatever can be.
+        let assignment_rx = {
+            let mut pool = self.agent_pool.lock().await;
+            if pool.is_empty() {
                 warn!("No Agents available to execute Action");
                 // Send back an error response now, and close the stream.
                 let (tx, rx) = mpsc::unbounded_channel();
@@ -60,83 +111,212 @@ impl Controller for ControllerService {
                         completion: proto::ActionStatus::Error.into(),
                         exit_code: None,
                     }),
+                    seq: 0,
+                    source: proto::ResponseSource::Scheduler.into(),
                 };
                 tx.send(Ok(error_response)).unwrap_or_default(); // Send Ok or Err back? need to say schedule_action errored!!
                 return Ok(tonic::Response::new(UnboundedReceiverStream::new(rx)));
             }
+
+            // The action waits in the queue while every matching agent is cordoned or busy, or while no agent matches at all.
+            let mut queue = self.action_queue.lock().await;
+            if queue.is_full() {
+                warn!("Action Queue is full, rejecting Action {}", action_request.action_id);
+                return Err(tonic::Status::resource_exhausted("Action queue is full"));
+            }
+            let assignment_rx = queue.push(action);
+            queue.dispatch(&mut pool);
+            assignment_rx
         };
 
-        let agent_ip = agent.get_ip_address().to_string();
+        // Every response of the action goes to its log, which the controller reads from the start.
+        // If the controller goes away, it can attach again and resume where it left off.
+        let action_id = action_request.action_id;
+        let attached = self.action_logs.lock().await.open(action_id).watch_attached();
+        let response_stream = attach(self.action_logs.clone(), action_id, 0).await?;
 
-        // Create the action object
-        let action = Action::new(
-            action_request.action_id,
-            proto::ExecutionContext {
-                container_image,
-                r#type: runner_type.into(),
-            },
-            action_request.commands,
-            action_request.repo_url,
-        );
-
-        // Use an unbounded channel to create the response stream
-        let (tx, rx) = mpsc::unbounded_channel();
-        // The transmitter is passed into the spawned task to send the response back to the client.
+        let agent_pool = self.agent_pool.clone();
+        let action_queue = self.action_queue.clone();
+        let action_logs = self.action_logs.clone();
+        let dispatch_attempts = self.dispatch_attempts;
+        let abandoned = abandoned(attached, self.reattach_grace_period);
 
         // Spawn an async task to handle action execution
         tokio::spawn(async move {
-            // Send the action to the agent and forward the response/transfer the logs
-            // The tokio::spawn function is used to create a new asynchronous task. To call execution_action without blocking the main schedule_action procedure.
-            // execution_action returns a Stream, which is validated, error-handled, and passed to schedule action's response stream. This is the log transfer operation.
-            match agent_client::execution_action(action, agent_ip).await {
-                // The response stream from the Agent is received and processed here directly; in a spawned task. This is simply because it is much easier than handling multiple streams by ID.
-                // Each received message is forwarded back to the controller.
-                Ok(mut response_stream) => {
-                    while let Some(response) = response_stream.message().await.unwrap_or(None) {
-                        // Use match to handle the presence or absence of a result in the response
-                        match response.result {
-                            Some(result) => {
-                                println!("Received a response with a result {:?}", result);
-                                let completion = match result.exit_code {
-                                    Some(exit_code) => {
-                                        if exit_code == 0 {
-                                            3
-                                        } else {
-                                            4
-                                        }
-                                    }
-                                    None => result.completion,
-                                };
-                                let action_response = proto::ActionResponse {
-                                    action_id: response.action_id,
-                                    log: response.log,
-                                    result: Some(proto::ActionResult {
-                                        completion: completion.into(),
-                                        exit_code: result.exit_code,
-                                    }),
-                                };
-
-                                if tx.send(Ok(action_response)).is_err() {
-                                    warn!("Failed to send action response");
-                                    break;
-                                }
+            tokio::pin!(abandoned);
+            let mut assignment_rx = assignment_rx;
+            let mut attempt = 1;
+            // The status forwarded to the controller only moves forward, and stays put once the action is done.
+            let mut lifecycle = ActionLifecycle::new(action_id);
+            loop {
+                // Wait for the action to be dispatched to an agent, unless nobody waits for it anymore.
+                let assignment = tokio::select! {
+                    assignment = &mut assignment_rx => match assignment {
+                        Ok(assignment) => assignment,
+                        Err(_) => {
+                            warn!("Action {} left the queue without being dispatched", action_id);
+                            let log = String::from("Action was never dispatched");
+                            publish(&action_logs, action_id, dispatch_response(action_id, log, lifecycle.advance(proto::ActionStatus::Error))).await;
+                            break;
+                        }
+                    },
+                    _ = &mut abandoned => {
+                        let mut pool = agent_pool.lock().await;
+                        let mut queue = action_queue.lock().await;
+                        if queue.remove(action_id).is_some() {
+                            warn!("Controller went away, Action {} cancelled while queued", action_id);
+                            queue.record_cancelled(action_id, None);
+                        } else if let Ok(assignment) = assignment_rx.try_recv() {
+                            // The action was dispatched in the meantime, but the agent was not contacted yet: just free it.
+                            warn!("Controller went away, Action {} cancelled before reaching Agent {}", action_id, assignment.agent_id);
+                            queue.record_cancelled(action_id, Some(assignment.agent_id));
+                            if let Some(agent) = pool.find_agent_mut(assignment.agent_id) {
+                                agent.unassign_action(action_id);
                             }
-                            None => {
-                                warn!("Received a response with no result");
+                            free_agent(&mut pool, &mut queue, assignment.agent_id, action_id, &tenant);
+                        }
+                        drop(queue);
+                        drop(pool);
+                        publish(&action_logs, action_id, dispatch_response(action_id, String::from(CANCELLED_LOG), lifecycle.advance(proto::ActionStatus::Cancelled))).await;
+                        break;
+                    }
+                };
+                let agent_id = assignment.agent_id;
+                let connection = assignment.connection.clone();
+                info!(
+                    "Action {} dispatched to Agent {} (attempt {}/{})",
+                    action_id, agent_id, attempt, dispatch_attempts
+                );
+
+                // Send the action to the agent and forward the response/transfer the logs
+                // The tokio::spawn function is used to create a new asynchronous task. To call execution_action without blocking the main schedule_action procedure.
+                // execution_action returns a Stream, which is validated, error-handled, and passed to schedule action's response stream. This is the log transfer operation.
+                let mut response_stream =
+                    match agent_client::execution_action(&assignment.action, &connection).await {
+                        Ok(response_stream) => response_stream,
+                        Err(e) => {
+                            warn!("Failed to dispatch Action {} to Agent {}: {}", action_id, agent_id, e);
+                            let reason = e.to_string();
+                            let mut action = assignment.action;
+
+                            // Free the agent and count the failure against it, then give the tenant its share back.
+                            let mut pool = agent_pool.lock().await;
+                            if let Some(agent) = pool.find_agent_mut(agent_id) {
+                                agent.unassign_action(action_id);
+                            }
+                            if pool.record_failure(agent_id) {
+                                warn!("Agent {} quarantined after failed dispatches", agent_id);
+                            }
+                            let mut queue = action_queue.lock().await;
+                            queue.release(&tenant);
+
+                            // Retry on another matching agent, as long as there are attempts left and another agent to try.
+                            action.exclude_agent(agent_id);
+                            let other_agent = pool.iter().any(|agent| {
+                                agent.matches(action.get_runs_on()) && !action.get_excluded_agents().contains(&agent.get_id())
+                            });
+                            if attempt >= dispatch_attempts || !other_agent {
+                                queue.dispatch(&mut pool);
+                                drop(queue);
+                                drop(pool);
+                                let log = format!(
+                                    "Failed to dispatch Action to Agent {} (attempt {}/{}): {}. Giving up.",
+                                    agent_id, attempt, dispatch_attempts, reason
+                                );
+                                publish(&action_logs, action_id, dispatch_response(action_id, log, lifecycle.advance(proto::ActionStatus::Error))).await;
+                                break;
                             }
+
+                            let log = format!(
+                                "Failed to dispatch Action to Agent {} (attempt {}/{}): {}. Retrying on another Agent.",
+                                agent_id, attempt, dispatch_attempts, reason
+                            );
+                            attempt += 1;
+                            assignment_rx = queue.push(action);
+                            queue.dispatch(&mut pool);
+                            drop(queue);
+                            drop(pool);
+                            publish(&action_logs, action_id, dispatch_response(action_id, log, lifecycle.advance(proto::ActionStatus::Scheduled))).await;
+                            continue;
+                        }
+                    };
+                agent_pool.lock().await.record_success(agent_id);
+
+                // The response stream from the Agent is received and processed here directly; in a spawned task. This is simply because it is much easier than handling multiple streams by ID.
+                // Each received message is added to the action's log, for the controllers attached to it.
+                // If no controller has been attached for the grace period, nobody is waiting for the action anymore: it is cancelled.
+                let mut cancelled = false;
+                loop {
+                    let response = tokio::select! {
+                        response = response_stream.message() => response.unwrap_or(None),
+                        _ = &mut abandoned => {
+                            cancelled = true;
+                            break;
+                        }
+                    };
+                    let Some(response) = response else {
+                        break;
+                    };
+                    // Use match to handle the presence or absence of a result in the response
+                    match response.result {
+                        Some(result) => {
+                            println!("Received a response with a result {:?}", result);
+                            let status = lifecycle.advance(reported_status(&result));
+                            let action_response = proto::ActionResponse {
+                                action_id: response.action_id,
+                                log: response.log,
+                                result: Some(proto::ActionResult {
+                                    completion: status.into(),
+                                    exit_code: result.exit_code,
+                                }),
+                                seq: 0, // Numbered by the log
+                                source: proto::ResponseSource::Agent.into(),
+                            };
+                            publish(&action_logs, action_id, action_response).await;
+                        }
+                        None => {
+                            warn!("Received a response with no result");
                         }
                     }
                 }
-                Err(e) => {
-                    warn!("Failed to execute Action: {}", e);
-                    let _ = tx.send(Err(tonic::Status::internal("Failed to execute Action")));
+
+                if cancelled {
+                    warn!("Controller went away, cancelling Action {} on Agent {}", action_id, agent_id);
+                    if let Err(e) = agent_client::cancel_action(action_id, &connection).await {
+                        warn!("Failed to cancel Action {} on Agent {}: {}", action_id, agent_id, e);
+                    }
+                }
+
+                // The agent is done with the action: free it right away.
+                let mut pool = agent_pool.lock().await;
+                let mut queue = action_queue.lock().await;
+                if cancelled {
+                    queue.record_cancelled(action_id, Some(agent_id));
                 }
+                free_agent(&mut pool, &mut queue, agent_id, action_id, &tenant);
+                drop(queue);
+                drop(pool);
+                if cancelled {
+                    publish(&action_logs, action_id, dispatch_response(action_id, String::from(CANCELLED_LOG), lifecycle.advance(proto::ActionStatus::Cancelled))).await;
+                }
+                break;
             }
+
+            // The action is done, one way or another: its log stays around for the controllers attaching late.
+            action_logs.lock().await.finish(action_id);
         });
 
-        // Now outside the spawned task, the response stream is created and the receiver side of the channel is returned to the client/calling service.
+        Ok(tonic::Response::new(response_stream))
+    }
 
-        let response_stream = UnboundedReceiverStream::new(rx);
+    async fn attach_action(
+        &self,
+        request: tonic::Request<proto::AttachActionRequest>,
+    ) -> Result<tonic::Response<Self::AttachActionStream>, tonic::Status> {
+        let request = request.into_inner();
+        info!("Controller attaching to Action {} from response {}", request.action_id, request.from_seq);
+
+        let response_stream = attach(self.action_logs.clone(), request.action_id, request.from_seq).await?;
         Ok(tonic::Response::new(response_stream))
     }
 }
@@ -163,3 +343,104 @@ impl ControllerService {
         Ok((runner_type, Some(container_image)))
     }
 }
+
+/// Build a response carrying a log line about the dispatch of an Action, rather than one from its Agent.
+fn dispatch_response(action_id: u32, log: String, status: proto::ActionStatus) -> proto::ActionResponse {
+    proto::ActionResponse {
+        action_id,
+        log,
+        result: Some(proto::ActionResult {
+            completion: status.into(),
+            exit_code: None,
+        }),
+        seq: 0, // Numbered by the log
+        source: proto::ResponseSource::Scheduler.into(),
+    }
+}
+
+/// Log line of an Action cancelled because nobody was waiting for it anymore.
+const CANCELLED_LOG: &str = "Action cancelled: no Controller attached to it during the grace period";
+
+/// Add a response to the log of an Action, for the controllers attached to it.
+async fn publish(action_logs: &Mutex<ActionLogs>, action_id: u32, response: proto::ActionResponse) {
+    action_logs.lock().await.push(action_id, response);
+}
+
+/// Stream the responses of an Action from the given sequence number, first the logged ones, then the new ones as they come, until the Action is done.
+async fn attach(
+    action_logs: Arc<Mutex<ActionLogs>>,
+    action_id: u32,
+    from_seq: u64,
+) -> Result<ScheduleActionStream, tonic::Status> {
+    let mut updates = {
+        let mut logs = action_logs.lock().await;
+        let log = logs
+            .find_mut(action_id)
+            .ok_or_else(|| tonic::Status::not_found(format!("Action {} is neither running nor recently finished", action_id)))?;
+        log.attach();
+        log.watch_updates()
+    };
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut next_seq = from_seq;
+        'stream: loop {
+            // Mark the updates as seen while reading the log, so that no response pushed in between is missed.
+            let (responses, finished) = {
+                let logs = action_logs.lock().await;
+                let Some(log) = logs.find(action_id) else {
+                    break;
+                };
+                updates.borrow_and_update();
+                (log.read(next_seq), log.is_finished())
+            };
+            for response in responses {
+                next_seq = response.seq + 1;
+                if tx.send(Ok(response)).is_err() {
+                    break 'stream;
+                }
+            }
+            if finished {
+                break;
+            }
+            tokio::select! {
+                changed = updates.changed() => if changed.is_err() {
+                    break;
+                },
+                _ = tx.closed() => break,
+            }
+        }
+        if let Some(log) = action_logs.lock().await.find_mut(action_id) {
+            log.detach();
+        }
+    });
+
+    Ok(UnboundedReceiverStream::new(rx))
+}
+
+/// Resolve once no controller has been attached to an Action for the whole grace period.
+async fn abandoned(mut attached: watch::Receiver<usize>, grace_period: Duration) {
+    loop {
+        if attached.wait_for(|attached| *attached == 0).await.is_err() {
+            // The log is gone, which only happens once the action is done.
+            return std::future::pending().await;
+        }
+        let reattached = tokio::time::timeout(grace_period, attached.wait_for(|attached| *attached > 0))
+            .await
+            .map(|reattached| reattached.is_ok());
+        match reattached {
+            Ok(true) => continue,
+            Ok(false) => return std::future::pending().await,
+            Err(_) => return,
+        }
+    }
+}
+
+/// Free the agent an action was given to, give the action's tenant its share back, and dispatch the next queued actions.
+fn free_agent(pool: &mut AgentPool, queue: &mut ActionsQueue, agent_id: u32, action_id: u32, tenant: &str) {
+    if let Some(agent) = pool.find_agent_mut(agent_id) {
+        agent.finish_action(action_id);
+    }
+    queue.release(tenant);
+    queue.dispatch(pool);
+}
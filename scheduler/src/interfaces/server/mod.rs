@@ -1,2 +1,3 @@
 pub mod agent_interface;
 pub mod controller_interface;
+pub mod admin_interface;
@@ -1,3 +1,4 @@
 pub mod proto;
 pub mod interfaces;
 pub mod logic;
+pub mod config;
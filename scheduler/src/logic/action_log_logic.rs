@@ -0,0 +1,147 @@
+use crate::proto::scheduler as proto;
+
+use std::collections::{HashMap, VecDeque};
+use tokio::sync::watch;
+
+/// A struct representing the responses of an Action, as sent to the Controller.
+/// Each response is numbered by its sequence number, from 0, so that a Controller can attach again and resume where it left off.
+/// Only the last `capacity` responses are kept: older ones are lost for good.
+#[derive(Debug)]
+pub(crate) struct ActionLog {
+    responses: VecDeque<proto::ActionResponse>, // Oldest first
+    capacity: usize,
+    next_seq: u64,
+    finished: bool,
+    updates: watch::Sender<()>, // Notified of every new response, and when the Action is finished
+    attached: watch::Sender<usize>, // Number of Controllers reading the responses
+}
+
+impl ActionLog {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            responses: VecDeque::new(),
+            capacity,
+            next_seq: 0,
+            finished: false,
+            updates: watch::channel(()).0,
+            attached: watch::channel(0).0,
+        }
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Number the response and keep it, dropping the oldest one if the log is full. Return the response's sequence number.
+    pub(crate) fn push(&mut self, mut response: proto::ActionResponse) -> u64 {
+        let seq = self.next_seq;
+        response.seq = seq;
+        if self.responses.len() >= self.capacity {
+            self.responses.pop_front();
+        }
+        self.responses.push_back(response);
+        self.next_seq += 1;
+        self.updates.send_replace(());
+        seq
+    }
+
+    /// Mark the Action as finished: it will not get any other response.
+    pub(crate) fn finish(&mut self) {
+        self.finished = true;
+        self.updates.send_replace(());
+    }
+
+    /// Return the kept responses from the given sequence number onwards.
+    pub(crate) fn read(&self, from_seq: u64) -> Vec<proto::ActionResponse> {
+        self.responses
+            .iter()
+            .filter(|response| response.seq >= from_seq)
+            .cloned()
+            .collect()
+    }
+
+    /// Watch for new responses. A watcher is notified of every response pushed after its last read.
+    pub(crate) fn watch_updates(&self) -> watch::Receiver<()> {
+        self.updates.subscribe()
+    }
+
+    /// Watch for the number of Controllers reading the responses.
+    pub(crate) fn watch_attached(&self) -> watch::Receiver<usize> {
+        self.attached.subscribe()
+    }
+
+    pub(crate) fn attach(&mut self) {
+        self.attached.send_modify(|attached| *attached += 1);
+    }
+
+    pub(crate) fn detach(&mut self) {
+        self.attached.send_modify(|attached| *attached = attached.saturating_sub(1));
+    }
+}
+
+/// Number of finished Actions whose logs are kept.
+const FINISHED_HISTORY: usize = 1000;
+
+/// The logs of the running Actions and of the most recently finished ones.
+#[derive(Debug)]
+pub struct ActionLogs {
+    logs: HashMap<u32, ActionLog>,
+    finished: VecDeque<u32>, // IDs of the finished Actions, oldest first
+    buffer_size: usize,
+}
+
+impl ActionLogs {
+    pub fn new() -> Self {
+        Self {
+            logs: HashMap::new(),
+            finished: VecDeque::new(),
+            buffer_size: 1000,
+        }
+    }
+
+    /// Keep at most `buffer_size` responses per Action. 1000 by default.
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    /// Start a new, empty log for the given Action, replacing any former log of the same ID.
+    pub(crate) fn open(&mut self, action_id: u32) -> &mut ActionLog {
+        self.finished.retain(|id| *id != action_id);
+        self.logs.insert(action_id, ActionLog::new(self.buffer_size));
+        self.logs.get_mut(&action_id).unwrap()
+    }
+
+    /// Add a response to the log of the given Action. Return its sequence number, or None if the Action has no log.
+    pub(crate) fn push(&mut self, action_id: u32, response: proto::ActionResponse) -> Option<u64> {
+        self.logs.get_mut(&action_id).map(|log| log.push(response))
+    }
+
+    /// Mark the log of the given Action as finished. The logs of the oldest finished Actions are dropped past the history size.
+    pub(crate) fn finish(&mut self, action_id: u32) {
+        let Some(log) = self.logs.get_mut(&action_id) else {
+            return;
+        };
+        log.finish();
+        self.finished.push_back(action_id);
+        if self.finished.len() > FINISHED_HISTORY {
+            if let Some(oldest) = self.finished.pop_front() {
+                self.logs.remove(&oldest);
+            }
+        }
+    }
+
+    pub(crate) fn find(&self, action_id: u32) -> Option<&ActionLog> {
+        self.logs.get(&action_id)
+    }
+
+    pub(crate) fn find_mut(&mut self, action_id: u32) -> Option<&mut ActionLog> {
+        self.logs.get_mut(&action_id)
+    }
+}
+
+impl Default for ActionLogs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
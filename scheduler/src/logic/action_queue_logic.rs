@@ -1,14 +1,25 @@
 //use crate::proto::controller as proto;
 use crate::proto::scheduler as proto;
+use crate::logic::agent_pool_logic::{AgentConnection, AgentPool};
+
+use std::collections::{HashMap, VecDeque};
+use std::time::SystemTime;
+use tokio::sync::oneshot;
 
 /// A struct representing an action in the queue.
-/// The action has an ID, a score, and additional fields from the ActionRequest proto.
+/// The action has an ID, a priority, the tenant it belongs to, and additional fields from the ActionRequest proto.
+/// The tenant is the key Agents are shared fairly by. It defaults to the repository URL.
 #[derive(Debug)]
 pub(crate) struct Action {
     action_id: u32,
     context: proto::ExecutionContext,
     commands: Vec<String>,
     repo_url: String,
+    priority: u32,
+    tenant: String,
+    excluded_agents: Vec<u32>, // Agents the Action could not be dispatched to
+    runs_on: Vec<String>, // Labels or capabilities the Agent must all have
+    env: Vec<String>, // Environment variables of the commands, as KEY=VALUE
 }
 
 impl Action {
@@ -18,7 +29,12 @@ impl Action {
             action_id,
             context,
             commands,
+            tenant: repo_url.clone(),
             repo_url,
+            priority: 0,
+            excluded_agents: Vec::new(),
+            runs_on: Vec::new(),
+            env: Vec::new(),
         }
     }
 
@@ -55,6 +71,46 @@ impl Action {
         &self.repo_url
     }
 
+    /// Priority getter
+    pub(crate) fn get_priority(&self) -> u32 {
+        self.priority
+    }
+
+    /// Tenant getter
+    pub(crate) fn get_tenant(&self) -> &str {
+        &self.tenant
+    }
+
+    /// Agent selector getter
+    pub(crate) fn get_runs_on(&self) -> &[String] {
+        &self.runs_on
+    }
+
+    /// Agent selector setter
+    pub(crate) fn set_runs_on(&mut self, runs_on: Vec<String>) {
+        self.runs_on = runs_on;
+    }
+
+    /// Environment variables getter
+    pub(crate) fn get_env(&self) -> &[String] {
+        &self.env
+    }
+
+    /// Environment variables setter
+    pub(crate) fn set_env(&mut self, env: Vec<String>) {
+        self.env = env;
+    }
+
+    /// Excluded Agents getter
+    pub(crate) fn get_excluded_agents(&self) -> &[u32] {
+        &self.excluded_agents
+    }
+
+    /// Keep the Action from being dispatched to the given Agent again.
+    pub(crate) fn exclude_agent(&mut self, agent_id: u32) {
+        self.excluded_agents.push(agent_id);
+    }
+
     /// Action ID setter
     pub(crate) fn set_action_id(&mut self, action_id: u32) {
         self.action_id = action_id;
@@ -85,31 +141,266 @@ impl Action {
         self.repo_url = repo_url;
     }
 
+    /// Priority setter
+    pub(crate) fn set_priority(&mut self, priority: u32) {
+        self.priority = priority;
+    }
+
+    /// Tenant setter
+    pub(crate) fn set_tenant(&mut self, tenant: String) {
+        self.tenant = tenant;
+    }
+
+}
+
+/// The outcome of dispatching an Action: the Action itself, and the Agent it was given to.
+#[derive(Debug)]
+pub(crate) struct Assignment {
+    pub(crate) action: Action,
+    pub(crate) agent_id: u32,
+    pub(crate) connection: AgentConnection,
+}
+
+/// An Action waiting in the Queue for an Agent.
+/// The Assignment is sent through `assignment_tx` once the Action is dispatched.
+#[derive(Debug)]
+pub(crate) struct QueuedAction {
+    action: Action,
+    enqueued_at: SystemTime,
+    assignment_tx: oneshot::Sender<Assignment>,
+}
+
+impl QueuedAction {
+    /// Action getter
+    pub(crate) fn get_action(&self) -> &Action {
+        &self.action
+    }
+
+    /// Enqueue time getter
+    pub(crate) fn get_enqueued_at(&self) -> SystemTime {
+        self.enqueued_at
+    }
+}
+
+/// A struct representing an Action that was cancelled because the Controller went away, before or while it ran.
+#[derive(Debug, Clone)]
+pub(crate) struct CancelledAction {
+    action_id: u32,
+    agent_id: Option<u32>, // None when the Action was still queued
+    cancelled_at: SystemTime,
+}
+
+impl CancelledAction {
+    /// Agent ID getter
+    pub(crate) fn get_agent_id(&self) -> Option<u32> {
+        self.agent_id
+    }
+
+    /// Cancellation time getter
+    pub(crate) fn get_cancelled_at(&self) -> SystemTime {
+        self.cancelled_at
+    }
 }
 
-/// ActionsQueue is a collection of Actions stored in a vector.
-/// The vector is sorted whenever necessary to maintain order.
+/// Number of cancelled Actions the Queue remembers.
+const CANCELLED_HISTORY: usize = 1000;
+
+/// Virtual time a tenant of weight 1 is charged for each dispatched Action.
+const STRIDE: u64 = 1 << 20;
+
+/// ActionsQueue is a weighted fair queue of Actions, stored in a vector in the order they were received.
+/// Agents are shared fairly between tenants: the next Action dispatched belongs to the tenant running the fewest Actions relative to its weight.
+/// Between tenants running as many Actions, the one that was served the least recently, relative to its weight, goes first (stride scheduling).
+/// Within a tenant, higher priority Actions are dispatched first, and Actions of the same priority in the order they were received.
+/// The Queue holds at most `max_length` Actions.
 pub struct ActionsQueue {
-    actions: Vec<Action>,
+    actions: Vec<QueuedAction>,
+    max_length: usize,
+    tenant_weights: HashMap<String, u32>,
+    running_per_tenant: HashMap<String, usize>,
+    passes: HashMap<String, u64>, // Virtual time at which each tenant is next served
+    virtual_time: u64,
+    cancelled: VecDeque<CancelledAction>, // The most recently cancelled Actions, oldest first
 }
 
 impl ActionsQueue {
-    /// Constructor
+    /// Constructor, for a Queue without any practical limit
     pub fn new() -> Self {
-        Self { actions: Vec::new() }
+        Self::with_max_length(usize::MAX)
+    }
+
+    /// Constructor, for a Queue holding at most `max_length` Actions
+    pub fn with_max_length(max_length: usize) -> Self {
+        Self {
+            actions: Vec::new(),
+            max_length,
+            tenant_weights: HashMap::new(),
+            running_per_tenant: HashMap::new(),
+            passes: HashMap::new(),
+            virtual_time: 0,
+            cancelled: VecDeque::new(),
+        }
+    }
+
+    /// Give tenants their weight. Tenants without a weight have a weight of 1.
+    pub fn with_tenant_weights(mut self, tenant_weights: HashMap<String, u32>) -> Self {
+        self.tenant_weights = tenant_weights;
+        self
     }
 
-    /// Insert an Action into the Action Queue and sort the Queue by score.
-    pub(crate) fn push(&mut self, item: Action) {
-        self.actions.push(item);
+    /// Return the weight of the given tenant
+    fn weight(&self, tenant: &str) -> u64 {
+        u64::from(self.tenant_weights.get(tenant).copied().unwrap_or(1).max(1))
     }
 
-    /// Remove and return the Action with the lowest score (that is, the first Action), or return None if the Queue is empty.
+    /// Return the number of dispatched Actions of the given tenant that are not released yet
+    pub(crate) fn running(&self, tenant: &str) -> usize {
+        self.running_per_tenant.get(tenant).copied().unwrap_or(0)
+    }
+
+    /// Return the virtual time at which the given tenant is next served.
+    /// A tenant that had nothing queued for a while does not get credit for it: it is served no earlier than now.
+    fn pass(&self, tenant: &str) -> u64 {
+        self.passes.get(tenant).copied().unwrap_or(0).max(self.virtual_time)
+    }
+
+    /// Check if the Action Queue cannot take any more Action
+    pub(crate) fn is_full(&self) -> bool {
+        self.actions.len() >= self.max_length
+    }
+
+    /// Insert an Action at the back of the Action Queue.
+    /// Returns the receiving end of the channel the Action's Assignment will be sent through once it is dispatched.
+    pub(crate) fn push(&mut self, item: Action) -> oneshot::Receiver<Assignment> {
+        let (assignment_tx, assignment_rx) = oneshot::channel();
+        self.actions.push(QueuedAction {
+            action: item,
+            enqueued_at: SystemTime::now(),
+            assignment_tx,
+        });
+        assignment_rx
+    }
+
+    /// Remove and return the next Action to dispatch, or return None if the Queue is empty.
     pub(crate) fn pop(&mut self) -> Option<Action> {
-        if self.actions.is_empty() {
-            None
-        } else {
-            Some(self.actions.remove(0))
+        let index = self.next_index(|_| true)?;
+        Some(self.actions.remove(index).action)
+    }
+
+    /// Return the index of the next Action to dispatch among the ones matching `dispatchable`, or None if there is no such Action.
+    /// The tenant is the one with the fewest running Actions relative to its weight, then the one with the lowest pass, then the one waiting the longest.
+    /// The Action is that tenant's highest priority one, the oldest on a tie.
+    fn next_index(&self, dispatchable: impl Fn(&Action) -> bool) -> Option<usize> {
+        // For each tenant: the index of its oldest Action, and the index of its next Action.
+        let mut tenants: HashMap<&str, (usize, usize)> = HashMap::new();
+        for (index, queued) in self.actions.iter().enumerate() {
+            if !dispatchable(&queued.action) {
+                continue;
+            }
+            let (_, next) = tenants.entry(queued.action.get_tenant()).or_insert((index, index));
+            if queued.action.priority > self.actions[*next].action.priority {
+                *next = index;
+            }
+        }
+
+        tenants
+            .into_iter()
+            .min_by(|(tenant_a, (oldest_a, _)), (tenant_b, (oldest_b, _))| {
+                // running_a / weight_a < running_b / weight_b, without dividing
+                let share_a = self.running(tenant_a) as u64 * self.weight(tenant_b);
+                let share_b = self.running(tenant_b) as u64 * self.weight(tenant_a);
+                share_a
+                    .cmp(&share_b)
+                    .then(self.pass(tenant_a).cmp(&self.pass(tenant_b)))
+                    .then(oldest_a.cmp(oldest_b))
+            })
+            .map(|(_, (_, next))| next)
+    }
+
+    /// Remove and return the queued Action of the given ID, or return None if the Action is not in the Queue.
+    pub(crate) fn remove(&mut self, action_id: u32) -> Option<Action> {
+        let index = self.actions.iter().position(|queued| queued.action.action_id == action_id)?;
+        Some(self.actions.remove(index).action)
+    }
+
+    /// Remember that the given Action was cancelled, and which Agent was running it if any.
+    pub(crate) fn record_cancelled(&mut self, action_id: u32, agent_id: Option<u32>) {
+        if self.cancelled.len() >= CANCELLED_HISTORY {
+            self.cancelled.pop_front();
+        }
+        self.cancelled.push_back(CancelledAction {
+            action_id,
+            agent_id,
+            cancelled_at: SystemTime::now(),
+        });
+    }
+
+    /// Return the cancelled Action of the given ID, or None if the Action was not cancelled recently.
+    pub(crate) fn find_cancelled(&self, action_id: u32) -> Option<&CancelledAction> {
+        self.cancelled.iter().rev().find(|cancelled| cancelled.action_id == action_id)
+    }
+
+    /// Return a reference to the queued Action of the given ID, or None if the Action is not in the Queue.
+    pub(crate) fn find(&self, action_id: u32) -> Option<&QueuedAction> {
+        self.actions.iter().find(|queued| queued.action.action_id == action_id)
+    }
+
+    /// Iterate over the queued Actions, in the order they were received.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &QueuedAction> {
+        self.actions.iter()
+    }
+
+    /// Dispatch the queued Actions, in fair order, to the available Agents of the Pool.
+    /// An Action is only dispatched to an Agent matching its selector, and never to an Agent it is excluded from. Stops as soon as no Action can be dispatched.
+    /// Returns the number of dispatched Actions. Each of them must be released once its Agent is done with it.
+    pub(crate) fn dispatch(&mut self, pool: &mut AgentPool) -> usize {
+        let mut dispatched = 0;
+        while let Some(index) =
+            self.next_index(|action| pool.peek_available_for(&action.runs_on, &action.excluded_agents).is_some())
+        {
+            let action = &self.actions[index].action;
+            let (agent_id, connection) = match pool.peek_available_for(&action.runs_on, &action.excluded_agents) {
+                Some(agent) => match pool.connection(agent) {
+                    Some(connection) => (agent.get_id(), connection),
+                    None => break,
+                },
+                None => break,
+            };
+            let queued = self.actions.remove(index);
+            let action_id = queued.action.action_id;
+            let tenant = queued.action.tenant.clone();
+            let assignment = Assignment {
+                action: queued.action,
+                agent_id,
+                connection,
+            };
+            // The receiver is gone when the Action was given up on, so the Agent is left untouched.
+            if queued.assignment_tx.send(assignment).is_ok() {
+                if let Some(agent) = pool.find_agent_mut(agent_id) {
+                    agent.start_action(action_id);
+                }
+                // Charge the tenant for its share, and move the virtual time forward to when it was served.
+                let pass = self.pass(&tenant);
+                self.virtual_time = pass;
+                self.passes.insert(tenant.clone(), pass + STRIDE / self.weight(&tenant));
+                *self.running_per_tenant.entry(tenant).or_insert(0) += 1;
+                dispatched += 1;
+            }
+        }
+        dispatched
+    }
+
+    /// Release a dispatched Action of the given tenant, once its Agent is done with it, so that the tenant gets its share back.
+    pub(crate) fn release(&mut self, tenant: &str) {
+        if let Some(running) = self.running_per_tenant.get_mut(tenant) {
+            *running -= 1;
+            if *running == 0 {
+                self.running_per_tenant.remove(tenant);
+                // A pass that is already behind the virtual time has no effect anymore.
+                if self.passes.get(tenant).is_some_and(|pass| *pass <= self.virtual_time) {
+                    self.passes.remove(tenant);
+                }
+            }
         }
     }
 
@@ -122,5 +413,4 @@ impl ActionsQueue {
     pub fn is_empty(&self) -> bool {
         self.actions.is_empty()
     }
-    
 }
@@ -0,0 +1,106 @@
+use crate::proto::actions;
+use crate::proto::scheduler as proto;
+
+use log::warn;
+
+/// The status an Agent reports, as sent to the Controller. Both protos number the lifecycle the same, but each status is still converted explicitly.
+impl From<actions::ActionStatus> for proto::ActionStatus {
+    fn from(status: actions::ActionStatus) -> Self {
+        match status {
+            actions::ActionStatus::Pending => proto::ActionStatus::Pending,
+            actions::ActionStatus::Scheduled => proto::ActionStatus::Scheduled,
+            actions::ActionStatus::Running => proto::ActionStatus::Running,
+            actions::ActionStatus::Completed => proto::ActionStatus::Completed,
+            actions::ActionStatus::Error => proto::ActionStatus::Error,
+            actions::ActionStatus::Cancelled => proto::ActionStatus::Cancelled,
+            actions::ActionStatus::TimedOut => proto::ActionStatus::TimedOut,
+            actions::ActionStatus::Skipped => proto::ActionStatus::Skipped,
+        }
+    }
+}
+
+/// The state the Admin interface shows, in the shared lifecycle.
+impl From<proto::AdminActionState> for proto::ActionStatus {
+    fn from(state: proto::AdminActionState) -> Self {
+        match state {
+            proto::AdminActionState::Queued => proto::ActionStatus::Pending,
+            proto::AdminActionState::Running => proto::ActionStatus::Running,
+            proto::AdminActionState::Cancelled => proto::ActionStatus::Cancelled,
+        }
+    }
+}
+
+impl proto::ActionStatus {
+    /// Check if the Action is done: it never changes status again.
+    pub fn is_terminal(self) -> bool {
+        matches!(
+            self,
+            proto::ActionStatus::Completed
+                | proto::ActionStatus::Error
+                | proto::ActionStatus::Cancelled
+                | proto::ActionStatus::TimedOut
+                | proto::ActionStatus::Skipped
+        )
+    }
+
+    /// Check if an Action can go from this status to the given one.
+    /// Staying in the same status is always allowed. A scheduled Action goes back to pending when it is dispatched again to another Agent.
+    pub fn can_transition_to(self, next: proto::ActionStatus) -> bool {
+        use proto::ActionStatus::*;
+        match (self, next) {
+            (current, next) if current == next => true,
+            (Pending, Scheduled | Running) => true,
+            (Scheduled, Pending | Running) => true,
+            (current, next) => !current.is_terminal() && next.is_terminal(),
+        }
+    }
+}
+
+/// The status of an Action from a result reported by its Agent: its exit code, when there is one, tells whether it completed.
+pub fn reported_status(result: &actions::ActionResult) -> proto::ActionStatus {
+    match result.exit_code {
+        Some(0) => proto::ActionStatus::Completed,
+        Some(_) => proto::ActionStatus::Error,
+        None => match actions::ActionStatus::try_from(result.completion) {
+            Ok(status) => status.into(),
+            Err(_) => {
+                warn!("Unknown Action status {} reported, considered an error", result.completion);
+                proto::ActionStatus::Error
+            }
+        },
+    }
+}
+
+/// The status of an Action through its lifecycle, as forwarded to the Controller.
+/// Transitions that are not allowed are ignored: once an Action is done, it keeps its terminal status.
+#[derive(Debug, Clone, Copy)]
+pub struct ActionLifecycle {
+    action_id: u32,
+    status: proto::ActionStatus,
+}
+
+impl ActionLifecycle {
+    /// Constructor, for an Action waiting in the Queue.
+    pub fn new(action_id: u32) -> Self {
+        Self {
+            action_id,
+            status: proto::ActionStatus::Pending,
+        }
+    }
+
+    /// Move the Action to the given status if it can, and return the status it is in.
+    pub fn advance(&mut self, next: proto::ActionStatus) -> proto::ActionStatus {
+        if self.status.can_transition_to(next) {
+            self.status = next;
+        } else {
+            warn!(
+                "Action {} cannot go from {} to {}, it stays {}",
+                self.action_id,
+                self.status.as_str_name(),
+                next.as_str_name(),
+                self.status.as_str_name()
+            );
+        }
+        self.status
+    }
+}
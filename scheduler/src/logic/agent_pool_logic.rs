@@ -1,26 +1,135 @@
+use crate::proto::actions;
+use crate::proto::scheduler as proto;
+
+use serde::Deserialize;
 use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::{mpsc, oneshot};
 
 /// Top secret algorithm used to mathematically compute the freeness score of an Agent. Do not leak!
 pub(crate) fn compute_score(cpu_avail: u32, memory_avail: u64) -> u64 {
     (0.5 * cpu_avail as f64 + 0.5 * memory_avail as f64) as u64
 }
 
+/// The strategy used to pick an Agent among the available ones of the Pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strategy {
+    /// The Agent with the lowest score.
+    #[default]
+    LowestScore,
+    /// The Agent with the highest score.
+    HighestScore,
+    /// The Agent running the fewest Actions, by lowest score when several run as many.
+    LeastLoaded,
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Strategy::LowestScore => write!(f, "lowest-score"),
+            Strategy::HighestScore => write!(f, "highest-score"),
+            Strategy::LeastLoaded => write!(f, "least-loaded"),
+        }
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lowest-score" => Ok(Strategy::LowestScore),
+            "highest-score" => Ok(Strategy::HighestScore),
+            "least-loaded" => Ok(Strategy::LeastLoaded),
+            _ => Err(format!(
+                "unknown scheduling strategy '{}', expected one of: lowest-score, highest-score, least-loaded",
+                s
+            )),
+        }
+    }
+}
+
+/// A struct representing an Action currently running on an Agent.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub(crate) struct RunningAction {
+    action_id: u32,
+    since: SystemTime,
+}
+
+impl RunningAction {
+    /// Action ID getter
+    pub(crate) fn get_action_id(&self) -> u32 {
+        self.action_id
+    }
+
+    /// Start time getter
+    pub(crate) fn get_since(&self) -> SystemTime {
+        self.since
+    }
+}
+
+/// State of the circuit breaker of an Agent, tripped by Actions that could not be dispatched to it.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub(crate) enum Breaker {
+    /// The Agent is given Actions.
+    Closed,
+    /// The Agent is quarantined until the given instant.
+    Open(Instant),
+    /// The quarantine is over, and a single Action is probing whether the Agent recovered.
+    HalfOpen,
+}
+
 /// A struct representing an Agent in the Pool.
-/// The Agent has an ID and a score.
+/// The Agent has an ID and a score, the last health it reported and the Actions it is running.
+/// A cordoned Agent stays in the Pool but is not given any new Action, and neither is a quarantined one.
+/// Actions can target Agents by their labels and capabilities.
+/// A pull-mode Agent is not dialed by the Scheduler: it gets its Actions through the Work stream it opens.
+/// An ephemeral Agent is given a single Action in its lifetime.
 #[derive(Eq, PartialEq, Debug)]
 pub(crate) struct Agent {
     id: u32,
     hostname: Hostname,
     score: u64,
+    cpu_avail: u32,
+    memory_avail: u64,
+    cordoned: bool,
+    running_actions: Vec<RunningAction>,
+    last_seen: Instant,
+    idle_since: Instant, // When the Agent last finished running its Actions
+    breaker: Breaker,
+    failures: u32, // Consecutive failed dispatches
+    labels: Vec<String>,
+    capabilities: proto::Capabilities,
+    pull: bool,
+    ephemeral: bool,
+    spent: bool, // An ephemeral Agent is spent once it has been given its Action
 }
 
 impl Agent {
     /// Constructor
     pub(crate) fn new(id: u32, hostname: Hostname, score: u64) -> Self {
         Self {
-            id: id,
-            hostname: hostname,
-            score: score,
+            id,
+            hostname,
+            score,
+            cpu_avail: 0,
+            memory_avail: 0,
+            cordoned: false,
+            running_actions: Vec::new(),
+            last_seen: Instant::now(),
+            idle_since: Instant::now(),
+            breaker: Breaker::Closed,
+            failures: 0,
+            labels: Vec::new(),
+            capabilities: proto::Capabilities::default(),
+            pull: false,
+            ephemeral: false,
+            spent: false,
         }
     }
     /// ID getter
@@ -43,6 +152,168 @@ impl Agent {
         self.score = score;
     }
 
+    /// Health getter, as (CPU available, memory available)
+    pub(crate) fn get_health(&self) -> (u32, u64) {
+        (self.cpu_avail, self.memory_avail)
+    }
+
+    /// Health setter. Setting the health counts as a sign of life from the Agent.
+    pub(crate) fn set_health(&mut self, cpu_avail: u32, memory_avail: u64) {
+        self.cpu_avail = cpu_avail;
+        self.memory_avail = memory_avail;
+        self.last_seen = Instant::now();
+    }
+
+    /// Hostname getter
+    pub(crate) fn get_hostname(&self) -> &Hostname {
+        &self.hostname
+    }
+
+    /// Labels getter
+    pub(crate) fn get_labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    /// Labels setter
+    pub(crate) fn set_labels(&mut self, labels: Vec<String>) {
+        self.labels = labels;
+    }
+
+    /// Capabilities getter
+    pub(crate) fn get_capabilities(&self) -> &proto::Capabilities {
+        &self.capabilities
+    }
+
+    /// Capabilities setter
+    pub(crate) fn set_capabilities(&mut self, capabilities: proto::Capabilities) {
+        self.capabilities = capabilities;
+    }
+
+    /// Pull mode getter
+    pub(crate) fn is_pull(&self) -> bool {
+        self.pull
+    }
+
+    /// Pull mode setter
+    pub(crate) fn set_pull(&mut self, pull: bool) {
+        self.pull = pull;
+    }
+
+    /// Ephemeral getter
+    pub(crate) fn is_ephemeral(&self) -> bool {
+        self.ephemeral
+    }
+
+    /// Ephemeral setter
+    pub(crate) fn set_ephemeral(&mut self, ephemeral: bool) {
+        self.ephemeral = ephemeral;
+    }
+
+    /// Check if the Agent matches the given selector: each of its entries must be one of the Agent's labels, its architecture, its OS or one of its runner types.
+    pub(crate) fn matches(&self, runs_on: &[String]) -> bool {
+        runs_on.iter().all(|selector| {
+            self.labels.contains(selector)
+                || self.capabilities.arch == *selector
+                || self.capabilities.os == *selector
+                || self.capabilities.runner_types.contains(selector)
+        })
+    }
+
+    /// Cordoned getter
+    pub(crate) fn is_cordoned(&self) -> bool {
+        self.cordoned
+    }
+
+    /// Cordoned setter
+    pub(crate) fn set_cordoned(&mut self, cordoned: bool) {
+        self.cordoned = cordoned;
+    }
+
+    /// Check if the Agent is quarantined by its circuit breaker, or still probing whether it recovered.
+    pub(crate) fn is_quarantined(&self) -> bool {
+        match self.breaker {
+            Breaker::Closed => false,
+            Breaker::Open(until) => Instant::now() < until,
+            Breaker::HalfOpen => true,
+        }
+    }
+
+    /// Check if the Agent can be given a new Action: it is neither cordoned, quarantined nor running `capacity` Actions, nor an ephemeral Agent already given its Action.
+    /// An Agent whose quarantine is over can be given a single probing Action.
+    fn accepts_actions(&self, capacity: usize) -> bool {
+        let breaker_closed = match self.breaker {
+            Breaker::Closed => true,
+            Breaker::Open(until) => Instant::now() >= until,
+            Breaker::HalfOpen => false,
+        };
+        breaker_closed && !self.cordoned && !self.spent && self.running_actions.len() < capacity
+    }
+
+    /// Running Actions getter
+    pub(crate) fn get_running_actions(&self) -> &[RunningAction] {
+        &self.running_actions
+    }
+
+    /// Record that the Agent started running the given Action.
+    /// When the Agent's quarantine is over, the Action probes whether the Agent recovered.
+    pub(crate) fn start_action(&mut self, action_id: u32) {
+        if let Breaker::Open(_) = self.breaker {
+            self.breaker = Breaker::HalfOpen;
+        }
+        self.spent = self.ephemeral;
+        self.running_actions.push(RunningAction {
+            action_id,
+            since: SystemTime::now(),
+        });
+    }
+
+    /// Forget the given Action once the Agent is done with it. Returns whether the Action was running on this Agent.
+    pub(crate) fn finish_action(&mut self, action_id: u32) -> bool {
+        let count = self.running_actions.len();
+        self.running_actions.retain(|running| running.action_id != action_id);
+        if self.running_actions.is_empty() && count > 0 {
+            self.idle_since = Instant::now();
+        }
+        self.running_actions.len() != count
+    }
+
+    /// Forget an Action the Agent never got, e.g. because it could not be dispatched to it. An ephemeral Agent can then be given another one.
+    /// Returns whether the Action was assigned to this Agent.
+    pub(crate) fn unassign_action(&mut self, action_id: u32) -> bool {
+        let unassigned = self.finish_action(action_id);
+        if unassigned {
+            self.spent = false;
+        }
+        unassigned
+    }
+
+    /// Return how long the Agent has not been running any Action, or None if it is running some.
+    pub(crate) fn idle_for(&self) -> Option<Duration> {
+        if self.running_actions.is_empty() {
+            Some(self.idle_since.elapsed())
+        } else {
+            None
+        }
+    }
+
+    /// Record that an Action was dispatched to the Agent, which closes its circuit breaker.
+    pub(crate) fn record_success(&mut self) {
+        self.failures = 0;
+        self.breaker = Breaker::Closed;
+    }
+
+    /// Record that an Action could not be dispatched to the Agent.
+    /// The Agent is quarantined for `cooldown` after `threshold` consecutive failures, or when its probing Action failed.
+    /// Returns whether the Agent was quarantined.
+    pub(crate) fn record_failure(&mut self, threshold: u32, cooldown: Duration) -> bool {
+        self.failures += 1;
+        if self.breaker == Breaker::HalfOpen || self.failures >= threshold {
+            self.breaker = Breaker::Open(Instant::now() + cooldown);
+            return true;
+        }
+        false
+    }
+
     /// Returns the Agent's IP address in the format "host:port"
     /// If the hostname is empty, returns "unknown:unknown"
     pub(crate) fn get_ip_address(&self) -> String {
@@ -101,20 +372,145 @@ impl Hostname {
     }
 }
 
+/// Whether an Agent started an Action, or why it could not.
+type Launch = Result<(), String>;
+
+/// The Work stream a pull-mode Agent opened to the Scheduler.
+/// Actions and cancellations are sent down the stream, and whether each Action was started, then its responses, come back up it.
+#[derive(Debug, Clone)]
+pub(crate) struct WorkStream {
+    work_tx: mpsc::UnboundedSender<proto::WorkResponse>,
+    launches: Arc<Mutex<HashMap<u32, oneshot::Sender<Launch>>>>, // Who waits for each Action sent down the stream to be started
+    responses: Arc<Mutex<HashMap<u32, mpsc::UnboundedSender<actions::ActionResponseStream>>>>, // Where the responses of each running Action go
+}
+
+impl WorkStream {
+    /// Constructor, from the sending half of the stream
+    pub(crate) fn new(work_tx: mpsc::UnboundedSender<proto::WorkResponse>) -> Self {
+        Self {
+            work_tx,
+            launches: Arc::new(Mutex::new(HashMap::new())),
+            responses: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Send an Action down the stream and wait for the Agent to start it, then return the receiver of its responses.
+    /// Fails with the reason the Agent gives if it could not start the Action, or if the stream is closed.
+    pub(crate) async fn start(&self, request: actions::ActionRequest) -> Result<mpsc::UnboundedReceiver<actions::ActionResponseStream>, String> {
+        let action_id = request.action_id;
+        let (launch_tx, launch_rx) = oneshot::channel();
+        let (response_tx, response_rx) = mpsc::unbounded_channel();
+        self.launches.lock().unwrap().insert(action_id, launch_tx);
+        self.responses.lock().unwrap().insert(action_id, response_tx);
+        let work = proto::WorkResponse {
+            work: Some(proto::work_response::Work::Action(request)),
+        };
+        let launched = match self.work_tx.send(work) {
+            Ok(()) => launch_rx.await.unwrap_or_else(|_| Err(String::from("The Work stream of the Agent is closed."))),
+            Err(_) => Err(String::from("The Work stream of the Agent is closed.")),
+        };
+        if launched.is_err() {
+            self.launches.lock().unwrap().remove(&action_id);
+            self.responses.lock().unwrap().remove(&action_id);
+        }
+        launched.map(|()| response_rx)
+    }
+
+    /// Pass on whether the Agent started an Action, to whoever waits for it.
+    pub(crate) fn launched(&self, result: proto::LaunchResult) {
+        if let Some(launch_tx) = self.launches.lock().unwrap().remove(&result.action_id) {
+            let _ = launch_tx.send(result.error.map_or(Ok(()), Err));
+        }
+    }
+
+    /// Ask the Agent to stop an Action. Its responses end right away. Returns whether the stream is still open.
+    pub(crate) fn cancel(&self, action_id: u32) -> bool {
+        self.responses.lock().unwrap().remove(&action_id);
+        let work = proto::WorkResponse {
+            work: Some(proto::work_response::Work::Cancel(actions::CancelActionRequest { action_id })),
+        };
+        self.work_tx.send(work).is_ok()
+    }
+
+    /// Pass a response coming up the stream on to its Action.
+    pub(crate) fn respond(&self, response: actions::ActionResponseStream) {
+        if let Some(response_tx) = self.responses.lock().unwrap().get(&response.action_id) {
+            let _ = response_tx.send(response);
+        }
+    }
+
+    /// End the responses of an Action, once the Agent is done with it.
+    pub(crate) fn finish(&self, action_id: u32) {
+        self.responses.lock().unwrap().remove(&action_id);
+    }
+
+    /// End the responses of every Action, and fail the ones not started yet, once the stream is closed.
+    pub(crate) fn close(&self) {
+        self.launches.lock().unwrap().clear();
+        self.responses.lock().unwrap().clear();
+    }
+
+    /// Check whether both are the same stream.
+    pub(crate) fn same_stream(&self, other: &WorkStream) -> bool {
+        self.work_tx.same_channel(&other.work_tx)
+    }
+}
+
+/// How the Scheduler reaches an Agent to give it an Action.
+#[derive(Debug, Clone)]
+pub(crate) enum AgentConnection {
+    /// The Scheduler dials the Agent at its address, "host:port".
+    Push(String),
+    /// The Agent opened a Work stream to the Scheduler.
+    Pull(WorkStream),
+}
+
 /// AgentPool is a collection of Agents stored in a vector.
 /// The vector is sorted whenever necessary to maintain order.
+/// The Work streams of the pull-mode Agents are kept by Agent ID: such an Agent is only given Actions while its stream is open.
 pub struct AgentPool {
     agents: Vec<Agent>,
+    work_streams: HashMap<u32, WorkStream>,
+    last_id: u32,
+    strategy: Strategy,
+    agent_capacity: usize,
+    breaker_threshold: u32,
+    breaker_cooldown: Duration,
 }
 
 impl AgentPool {
     /// Constructor
     pub fn new() -> Self {
+        Self::with_strategy(Strategy::default())
+    }
+
+    /// Constructor, picking Agents with the given strategy
+    pub fn with_strategy(strategy: Strategy) -> Self {
         Self {
             agents: Vec::new(),
+            work_streams: HashMap::new(),
+            last_id: 0,
+            strategy,
+            agent_capacity: usize::MAX,
+            breaker_threshold: 3,
+            breaker_cooldown: Duration::from_secs(30),
         }
     }
 
+    /// Let each Agent run at most `agent_capacity` Actions at the same time. Agents have no such limit by default.
+    pub fn with_agent_capacity(mut self, agent_capacity: usize) -> Self {
+        self.agent_capacity = agent_capacity;
+        self
+    }
+
+    /// Quarantine Agents for `cooldown` once `threshold` Actions in a row could not be dispatched to them.
+    /// By default, Agents are quarantined for 30 seconds after 3 failures.
+    pub fn with_circuit_breaker(mut self, threshold: u32, cooldown: Duration) -> Self {
+        self.breaker_threshold = threshold;
+        self.breaker_cooldown = cooldown;
+        self
+    }
+
     /// Insert an Agent into the Agent Pool and sort the Pool by score.
     pub(crate) fn push(&mut self, item: Agent) {
         self.agents.push(item);
@@ -130,6 +526,11 @@ impl AgentPool {
         }
     }
 
+    /// Agent capacity getter
+    pub(crate) fn get_agent_capacity(&self) -> usize {
+        self.agent_capacity
+    }
+
     /// Peek at the Agent with the lowest score without removing it, or return None if the Pool is empty.
     pub(crate) fn peek(&self) -> Option<&Agent> {
         if self.agents.is_empty() {
@@ -139,6 +540,107 @@ impl AgentPool {
         }
     }
 
+    /// Peek at the Agent picked by the Pool's strategy among the ones that are neither cordoned, quarantined nor running at full capacity,
+    /// that match the `runs_on` selector and whose IDs are not `excluded`, or return None if there is no such Agent.
+    pub(crate) fn peek_available_for(&self, runs_on: &[String], excluded: &[u32]) -> Option<&Agent> {
+        let mut available = self.agents.iter().filter(|agent| {
+            agent.accepts_actions(self.agent_capacity)
+                && agent.matches(runs_on)
+                && !excluded.contains(&agent.id)
+                && (!agent.pull || self.work_streams.contains_key(&agent.id))
+        });
+        // The Pool is sorted, so the first available Agent has the lowest score and the last one the highest.
+        match self.strategy {
+            Strategy::LowestScore => available.next(),
+            Strategy::HighestScore => available.next_back(),
+            Strategy::LeastLoaded => available.min_by_key(|agent| agent.running_actions.len()),
+        }
+    }
+
+    /// Return how to reach the given Agent: through its Work stream if it is a pull-mode Agent, or at its address otherwise.
+    /// Return None for a pull-mode Agent without any Work stream open.
+    pub(crate) fn connection(&self, agent: &Agent) -> Option<AgentConnection> {
+        if agent.pull {
+            return self.work_streams.get(&agent.id).cloned().map(AgentConnection::Pull);
+        }
+        Some(AgentConnection::Push(agent.get_ip_address()))
+    }
+
+    /// Keep the Work stream a pull-mode Agent opened, replacing any former one.
+    pub(crate) fn open_work_stream(&mut self, id: u32, work_stream: WorkStream) {
+        if let Some(former) = self.work_streams.insert(id, work_stream) {
+            former.close();
+        }
+    }
+
+    /// Forget the Work stream of an Agent once it is closed, unless the Agent opened another one since.
+    pub(crate) fn close_work_stream(&mut self, id: u32, work_stream: &WorkStream) {
+        work_stream.close();
+        if self.work_streams.get(&id).is_some_and(|current| current.same_stream(work_stream)) {
+            self.work_streams.remove(&id);
+        }
+    }
+
+    /// Check whether the Agent of the given ID has a Work stream open.
+    pub(crate) fn has_work_stream(&self, id: u32) -> bool {
+        self.work_streams.contains_key(&id)
+    }
+
+    /// Iterate over the Agents of the Pool, by ascending score.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Agent> {
+        self.agents.iter()
+    }
+
+    /// Return a reference to the Agent of the given ID, or None if the Agent is not found.
+    pub(crate) fn find_agent(&self, id: u32) -> Option<&Agent> {
+        self.agents.iter().find(|agent| agent.id == id)
+    }
+
+    /// Return the Agent running the given Action, along with the Action's running details, or None if no Agent is running it.
+    pub(crate) fn find_running_action(&self, action_id: u32) -> Option<(&Agent, &RunningAction)> {
+        self.agents.iter().find_map(|agent| {
+            agent
+                .running_actions
+                .iter()
+                .find(|running| running.action_id == action_id)
+                .map(|running| (agent, running))
+        })
+    }
+
+    /// Record that an Action was dispatched to the Agent of the given ID.
+    pub(crate) fn record_success(&mut self, id: u32) {
+        if let Some(agent) = self.find_agent_mut(id) {
+            agent.record_success();
+        }
+    }
+
+    /// Record that an Action could not be dispatched to the Agent of the given ID. Returns whether the Agent was quarantined.
+    pub(crate) fn record_failure(&mut self, id: u32) -> bool {
+        let (threshold, cooldown) = (self.breaker_threshold, self.breaker_cooldown);
+        self.find_agent_mut(id)
+            .is_some_and(|agent| agent.record_failure(threshold, cooldown))
+    }
+
+    /// Remove and return the Agent of the given ID, or return None if the Agent is not found.
+    pub(crate) fn remove(&mut self, id: u32) -> Option<Agent> {
+        let index = self.agents.iter().position(|agent| agent.id == id)?;
+        self.work_streams.remove(&id);  // The responses of its running Actions still come up its Work stream
+        Some(self.agents.remove(index))  // Removing keeps the remaining Agents sorted
+    }
+
+    /// Remove the Agents that have not shown any sign of life for longer than the given timeout, and return their IDs.
+    pub fn remove_stale(&mut self, timeout: Duration) -> Vec<u32> {
+        let (stale, alive): (Vec<Agent>, Vec<Agent>) = self
+            .agents
+            .drain(..)
+            .partition(|agent| agent.last_seen.elapsed() > timeout);
+        self.agents = alive;  // Partitioning keeps the remaining Agents sorted
+        for agent in &stale {
+            self.work_streams.remove(&agent.id);
+        }
+        stale.iter().map(|agent| agent.id).collect()
+    }
+
     /// Return the number of Agents in the Pool
     pub(crate) fn len(&self) -> usize {
         self.agents.len()
@@ -175,8 +677,10 @@ impl AgentPool {
         return false;  // Agent is in correct order
     }
 
-    /// Generate a unique ID by finding the maximum existing ID and incrementing it by 1. This ensures that the new ID is *always* unique among the Agent Pool.
-    pub(crate) fn generate_unique_id(&self) -> u32 {
-        self.agents.iter().map(|agent| agent.id).max().unwrap_or(0) + 1  // unwrap_or(0) is used to handle the case when the Agent Pool is empty
+    /// Generate a unique ID by incrementing the highest ID ever given out by 1. This ensures that the new ID is *always* unique among the Agent Pool.
+    /// IDs of evicted Agents are never handed out again, so a stale Agent cannot report health in place of a new one.
+    pub(crate) fn generate_unique_id(&mut self) -> u32 {
+        self.last_id += 1;
+        self.last_id
     }
 }
@@ -0,0 +1,160 @@
+use crate::logic::action_queue_logic::ActionsQueue;
+use crate::logic::agent_pool_logic::AgentPool;
+
+use serde::Serialize;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Whether more or fewer Agents are wanted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScaleDirection {
+    Up,
+    Down,
+}
+
+/// The threshold that was crossed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScaleReason {
+    /// Too many Actions are waiting in the Queue.
+    QueueDepth,
+    /// An Action has been waiting in the Queue for too long.
+    WaitTime,
+    /// Agents have not been running any Action for a while.
+    IdleAgents,
+}
+
+/// A struct describing the scale-up or scale-down the Autoscaler asks for, sent to the scaling hook as JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScaleRequest {
+    pub direction: ScaleDirection,
+    pub reason: ScaleReason,
+    pub agents: usize, // Agents in the Pool
+    pub desired_agents: usize,
+    pub queued_actions: usize,
+    pub oldest_wait: u64, // In seconds, 0 when the Queue is empty
+    pub idle_agents: Vec<u32>, // IDs of the Agents idle for longer than the idle time
+}
+
+/// The Autoscaler watches the Action Queue and the Agent Pool, and asks for more Agents when the Queue crosses its thresholds, or for fewer when Agents sit idle.
+/// After asking, it waits for a cooldown before asking again, so that it does not flap while Agents come and go.
+#[derive(Debug, Clone)]
+pub struct Autoscaler {
+    queue_depth: Option<usize>,
+    wait_time: Option<Duration>,
+    idle_time: Option<Duration>,
+    scale_up_cooldown: Duration,
+    scale_down_cooldown: Duration,
+    last_scaled: Option<Instant>,
+}
+
+impl Autoscaler {
+    /// Constructor, without any threshold: the Autoscaler never asks for anything until one is set.
+    /// The cooldowns are 1 minute after asking for more Agents, and 5 minutes after asking for fewer.
+    pub fn new() -> Self {
+        Self {
+            queue_depth: None,
+            wait_time: None,
+            idle_time: None,
+            scale_up_cooldown: Duration::from_secs(60),
+            scale_down_cooldown: Duration::from_secs(300),
+            last_scaled: None,
+        }
+    }
+
+    /// Ask for more Agents when at least `queue_depth` Actions are waiting in the Queue.
+    pub fn with_queue_depth(mut self, queue_depth: Option<usize>) -> Self {
+        self.queue_depth = queue_depth;
+        self
+    }
+
+    /// Ask for more Agents when an Action has been waiting in the Queue for at least `wait_time`.
+    pub fn with_wait_time(mut self, wait_time: Option<Duration>) -> Self {
+        self.wait_time = wait_time;
+        self
+    }
+
+    /// Ask for fewer Agents when some have not been running any Action for at least `idle_time`, and the Queue is empty.
+    pub fn with_idle_time(mut self, idle_time: Option<Duration>) -> Self {
+        self.idle_time = idle_time;
+        self
+    }
+
+    /// Wait for `scale_up` after asking for anything before asking for more Agents, and for `scale_down` before asking for fewer.
+    pub fn with_cooldowns(mut self, scale_up: Duration, scale_down: Duration) -> Self {
+        self.scale_up_cooldown = scale_up;
+        self.scale_down_cooldown = scale_down;
+        self
+    }
+
+    /// Check the thresholds against the Queue and the Pool, and return what to ask for, if anything.
+    /// Nothing is asked for while in cooldown. The cooldown only starts once `record_scaled` is called.
+    pub(crate) fn evaluate(&self, pool: &AgentPool, queue: &ActionsQueue) -> Option<ScaleRequest> {
+        let queued_actions = queue.len();
+        let oldest_wait = queue
+            .iter()
+            .map(|queued| queued.get_enqueued_at())
+            .min()
+            .and_then(|enqueued_at| SystemTime::now().duration_since(enqueued_at).ok())
+            .unwrap_or_default();
+        let idle_agents: Vec<u32> = match self.idle_time {
+            Some(idle_time) => pool
+                .iter()
+                .filter(|agent| agent.idle_for().is_some_and(|idle_for| idle_for >= idle_time))
+                .map(|agent| agent.get_id())
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let up_reason = if self.queue_depth.is_some_and(|depth| queued_actions >= depth) {
+            Some(ScaleReason::QueueDepth)
+        } else if queued_actions > 0 && self.wait_time.is_some_and(|wait_time| oldest_wait >= wait_time) {
+            Some(ScaleReason::WaitTime)
+        } else {
+            None
+        };
+
+        let agents = pool.len();
+        let (direction, reason, desired_agents) = match up_reason {
+            Some(reason) => {
+                // Enough new Agents to run every waiting Action at once.
+                let capacity = pool.get_agent_capacity().max(1);
+                let missing = queued_actions.div_ceil(capacity);
+                (ScaleDirection::Up, reason, agents + missing)
+            }
+            None if queued_actions == 0 && !idle_agents.is_empty() => {
+                (ScaleDirection::Down, ScaleReason::IdleAgents, agents - idle_agents.len())
+            }
+            None => return None,
+        };
+
+        let cooldown = match direction {
+            ScaleDirection::Up => self.scale_up_cooldown,
+            ScaleDirection::Down => self.scale_down_cooldown,
+        };
+        if self.last_scaled.is_some_and(|last_scaled| last_scaled.elapsed() < cooldown) {
+            return None;
+        }
+
+        Some(ScaleRequest {
+            direction,
+            reason,
+            agents,
+            desired_agents,
+            queued_actions,
+            oldest_wait: oldest_wait.as_secs(),
+            idle_agents,
+        })
+    }
+
+    /// Record that the scaling hook was called, which starts the cooldown.
+    pub(crate) fn record_scaled(&mut self) {
+        self.last_scaled = Some(Instant::now());
+    }
+}
+
+impl Default for Autoscaler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
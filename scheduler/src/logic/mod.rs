@@ -1,2 +1,5 @@
 pub mod agent_pool_logic;
-pub mod action_queue_logic;
\ No newline at end of file
+pub mod action_queue_logic;
+pub mod action_log_logic;
+pub mod autoscaler_logic;
+pub mod action_status_logic;
@@ -1,10 +1,11 @@
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
 
-use env_logger;
-use log::info;
+use clap::Parser;
+use log::{error, info, warn};
 use logic::agent_pool_logic::AgentPool;
-// use logic::action_queue_logic::ActionsQueue;
+use logic::action_queue_logic::ActionsQueue;
 use tonic::transport::Server;
 
 mod proto;
@@ -12,36 +13,96 @@ mod proto;
 //use proto::controller::controller_server::ControllerServer;
 use proto::scheduler::agent_server::AgentServer;
 use proto::scheduler::controller_server::ControllerServer;
+use proto::scheduler::admin_server::AdminServer;
 
 mod interfaces;
 use interfaces::server::agent_interface::AgentService;
 use interfaces::server::controller_interface::ControllerService;
+use interfaces::server::admin_interface::AdminService;
+use interfaces::client::scale_hook::autoscale;
 
 mod logic;
 
+mod config;
+use config::{Args, Config};
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-	env_logger::init();
+	// Settings are validated before anything starts.
+	let config = Config::load(Args::parse()).unwrap_or_else(|e| {
+		eprintln!("{}", e);
+		std::process::exit(1);
+	});
+
+	env_logger::Builder::new()
+		.parse_filters(&config.log_level)
+		.parse_default_env()
+		.init();
 
-	let addr = "[::0]:50051".parse()?;
+	let addr = config.listen_address;
 
 	// Initializes the Agent Pool and Action queue. They are lost when the Scheduler dies.
-	let agent_pool = Arc::new(Mutex::new(AgentPool::new()));
-	//let action_queue = Arc::new(Mutex::new(ActionsQueue::new()));
+	let agent_pool = Arc::new(Mutex::new(
+		AgentPool::with_strategy(config.strategy)
+			.with_agent_capacity(config.agent_capacity)
+			.with_circuit_breaker(config.breaker_threshold, Duration::from_secs(config.breaker_cooldown)),
+	));
+	let action_queue = Arc::new(Mutex::new(
+		ActionsQueue::with_max_length(config.max_queued_actions).with_tenant_weights(config.tenant_weights.clone()),
+	));
+
+	// Pass the shared Agent Pool and Action queue to Agent, Controller and Admin services.
+	let agent = AgentService::new(agent_pool.clone(), action_queue.clone());
+	let controller = ControllerService::new(agent_pool.clone(), action_queue.clone())
+		.with_dispatch_attempts(config.dispatch_attempts)
+		.with_log_buffer_size(config.log_buffer_size)
+		.with_reattach_grace_period(Duration::from_secs(config.reattach_grace_period));
+	let admin = AdminService::new(agent_pool.clone(), action_queue.clone());
 
-	// Pass the shared Agent Pool to Agent and Controller services.
-	let agent = AgentService::new(agent_pool.clone());
-	let controller = ControllerService::new(agent_pool.clone());
+	// Agents that stop reporting their health are removed from the Pool.
+	let heartbeat_timeout = Duration::from_secs(config.heartbeat_timeout);
+	let reaped_pool = agent_pool.clone();
+	tokio::spawn(async move {
+		let mut interval = tokio::time::interval(heartbeat_timeout / 2);
+		loop {
+			interval.tick().await;
+			for agent_id in reaped_pool.lock().await.remove_stale(heartbeat_timeout) {
+				warn!("Agent {} missed its heartbeat, removed from the Pool", agent_id);
+			}
+		}
+	});
+
+	// The scaling hook is called when the Queue or the idle Agents cross their thresholds.
+	if let Some(autoscaling) = &config.autoscaling {
+		info!("Autoscaling enabled, checking thresholds every {} seconds", autoscaling.interval);
+		tokio::spawn(autoscale(
+			autoscaling.autoscaler(),
+			autoscaling.hook(),
+			agent_pool.clone(),
+			action_queue.clone(),
+			Duration::from_secs(autoscaling.interval),
+		));
+	}
 
 	let service = tonic_reflection::server::Builder::configure()
 		.register_encoded_file_descriptor_set(proto::FILE_DESCRIPTOR_SET)
 		.build()?;
 
-  info!("Starting gRPC server at {}", addr);
-	Server::builder()
+	let mut server = Server::builder();
+	if let Some(tls_config) = config.tls_config()? {
+		info!("TLS enabled");
+		server = server.tls_config(tls_config).map_err(|e| {
+			error!("Invalid TLS configuration: {}", e);
+			e
+		})?;
+	}
+
+	info!("Starting gRPC server at {} (scheduling strategy: {})", addr, config.strategy);
+	server
 		.add_service(service)
 		.add_service(AgentServer::new(agent))
 		.add_service(ControllerServer::new(controller))
+		.add_service(AdminServer::new(admin))
 		.serve(addr)
 		.await?;
 
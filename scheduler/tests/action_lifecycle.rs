@@ -0,0 +1,40 @@
+use scheduler::proto::actions;
+use scheduler::proto::scheduler::ActionStatus::*;
+
+use scheduler::logic::action_status_logic::{reported_status, ActionLifecycle};
+
+#[test]
+fn test_action_lifecycle() {
+    assert!(Pending.can_transition_to(Scheduled));
+    assert!(Scheduled.can_transition_to(Running));
+    assert!(Scheduled.can_transition_to(Pending));
+    assert!(Running.can_transition_to(Completed));
+    assert!(Pending.can_transition_to(Cancelled));
+    assert!(Pending.can_transition_to(Skipped));
+    assert!(Running.can_transition_to(TimedOut));
+    assert!(Completed.can_transition_to(Completed));
+
+    assert!(!Running.can_transition_to(Pending));
+    assert!(!Running.can_transition_to(Scheduled));
+    assert!(!Completed.can_transition_to(Error));
+    assert!(!Cancelled.can_transition_to(Running));
+
+    let mut lifecycle = ActionLifecycle::new(1);
+    assert_eq!(lifecycle.advance(Scheduled), Scheduled);
+    assert_eq!(lifecycle.advance(Running), Running);
+    assert_eq!(lifecycle.advance(Cancelled), Cancelled);
+    // A late result does not bring the Action back.
+    assert_eq!(lifecycle.advance(Completed), Cancelled);
+
+    // The exit code, when there is one, tells whether the Action completed.
+    let result = |completion: actions::ActionStatus, exit_code| actions::ActionResult {
+        completion: completion.into(),
+        exit_code,
+    };
+    assert_eq!(reported_status(&result(actions::ActionStatus::Running, None)), Running);
+    assert_eq!(reported_status(&result(actions::ActionStatus::Running, Some(0))), Completed);
+    assert_eq!(reported_status(&result(actions::ActionStatus::Completed, Some(2))), Error);
+    assert_eq!(reported_status(&result(actions::ActionStatus::TimedOut, None)), TimedOut);
+    let unknown = actions::ActionResult { completion: 42, exit_code: None };
+    assert_eq!(reported_status(&unknown), Error);
+}
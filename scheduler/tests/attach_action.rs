@@ -0,0 +1,100 @@
+mod common;
+use common::{action_request, RecordingAgent};
+
+use scheduler::proto::scheduler as agent;
+use agent::agent_client::AgentClient;
+use agent::agent_server::AgentServer;
+use agent::{Health, RegisterAgentRequest};
+
+use scheduler::proto::scheduler as controller;
+use controller::controller_server::ControllerServer;
+use controller::{controller_client::ControllerClient, ActionResponse, ActionStatus, AttachActionRequest};
+
+use scheduler::interfaces::server as server;
+use server::agent_interface::AgentService;
+use server::controller_interface::ControllerService;
+
+use scheduler::logic as logic;
+use logic::agent_pool_logic::AgentPool;
+use logic::action_queue_logic::ActionsQueue;
+
+use tonic::transport::Server;
+use tonic::transport::Channel;
+use tonic::{Code, Request};
+use std::error::Error;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::Duration;
+
+async fn attach(channel: Channel, action_id: u32, from_seq: u64) -> Result<Vec<ActionResponse>, Box<dyn Error>> {
+    let mut stream = ControllerClient::new(channel)
+        .attach_action(Request::new(AttachActionRequest { action_id, from_seq }))
+        .await?
+        .into_inner();
+    let mut responses = Vec::new();
+    while let Some(response) = stream.message().await? {
+        responses.push(response);
+    }
+    Ok(responses)
+}
+
+#[tokio::test]
+async fn test_attach_action() -> Result<(), Box<dyn Error>> {
+    let recording_agent = RecordingAgent::serve(50052, Duration::from_secs(1));
+
+    tokio::spawn(async {
+        let addr = "[::1]:50051".parse().unwrap();
+        let agent_pool = Arc::new(Mutex::new(AgentPool::new()));
+        let action_queue = Arc::new(Mutex::new(ActionsQueue::new()));
+        let agent = AgentService::new(agent_pool.clone(), action_queue.clone());
+        let controller = ControllerService::new(agent_pool.clone(), action_queue.clone())
+            .with_reattach_grace_period(Duration::from_secs(5));
+
+        Server::builder()
+            .add_service(AgentServer::new(agent))
+            .add_service(ControllerServer::new(controller))
+            .serve(addr)
+            .await
+            .unwrap();
+    });
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let channel = Channel::from_static("http://[::1]:50051").connect().await?;
+    AgentClient::new(channel.clone())
+        .register_agent(Request::new(RegisterAgentRequest {
+            health: Some(Health { cpu_avail: 42, memory_avail: 2048 }),
+            hostname: Some(agent::Hostname { host: String::from("http://[::1]"), port: 50052 }),
+            labels: vec![],
+            capabilities: None,
+            pull: false,
+            ephemeral: false,
+        }))
+        .await?;
+
+    // The Controller's connection blips while the Action runs.
+    let blipping_channel = Channel::from_static("http://[::1]:50051").connect().await?;
+    let stream = ControllerClient::new(blipping_channel.clone())
+        .schedule_action(Request::new(action_request(1, &[])))
+        .await?;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    drop(stream);
+    drop(blipping_channel);
+
+    // It attaches again within the grace period, and gets every response: the Action was not cancelled.
+    let responses = attach(channel.clone(), 1, 0).await?;
+    assert_eq!(responses.len(), 1);
+    assert_eq!(responses[0].seq, 0);
+    assert_eq!(responses[0].log, "done");
+    assert_eq!(responses[0].result.as_ref().unwrap().completion(), ActionStatus::Completed);
+    assert!(recording_agent.cancelled.lock().await.is_empty());
+
+    // The responses of a finished Action can still be read, from any point.
+    assert_eq!(attach(channel.clone(), 1, 0).await?.len(), 1);
+    assert!(attach(channel.clone(), 1, 1).await?.is_empty());
+
+    let error = attach(channel, 42, 0).await.unwrap_err();
+    assert_eq!(error.downcast_ref::<tonic::Status>().map(|status| status.code()), Some(Code::NotFound));
+
+    Ok(())
+}
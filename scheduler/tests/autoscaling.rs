@@ -0,0 +1,147 @@
+mod common;
+use common::action_request;
+
+use scheduler::proto::scheduler as agent;
+use agent::agent_client::AgentClient;
+use agent::agent_server::AgentServer;
+use agent::{Health, RegisterAgentRequest};
+
+use scheduler::proto::scheduler as controller;
+use controller::controller_server::ControllerServer;
+use controller::controller_client::ControllerClient;
+
+use scheduler::interfaces::server as server;
+use server::agent_interface::AgentService;
+use server::controller_interface::ControllerService;
+
+use scheduler::interfaces::client::scale_hook::{autoscale, ScaleHook};
+
+use scheduler::logic as logic;
+use logic::agent_pool_logic::AgentPool;
+use logic::action_queue_logic::ActionsQueue;
+use logic::autoscaler_logic::Autoscaler;
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::Body;
+use serde_json::Value;
+use tonic::transport::Server;
+use tonic::transport::Channel;
+use tonic::Request;
+use std::convert::Infallible;
+use std::error::Error;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+use tokio::time::Duration;
+
+/// Serve the scaling URL, passing on each scale request it is POSTed.
+fn serve_scaling_url(port: u16) -> mpsc::UnboundedReceiver<Value> {
+    let (request_tx, request_rx) = mpsc::unbounded_channel();
+    let make_service = make_service_fn(move |_| {
+        let request_tx = request_tx.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: hyper::Request<Body>| {
+                let request_tx = request_tx.clone();
+                async move {
+                    let body = hyper::body::to_bytes(request.into_body()).await?;
+                    request_tx.send(serde_json::from_slice::<Value>(&body).unwrap()).unwrap();
+                    Ok::<_, hyper::Error>(hyper::Response::new(Body::empty()))
+                }
+            }))
+        }
+    });
+    let addr = ([127, 0, 0, 1], port).into();
+    tokio::spawn(hyper::Server::bind(&addr).serve(make_service));
+    request_rx
+}
+
+#[tokio::test]
+async fn test_autoscaling() -> Result<(), Box<dyn Error>> {
+    let mut scale_requests = serve_scaling_url(50054);
+    let scale_down_file = std::env::temp_dir().join(format!("sealci-scheduler-scale-down-{}.json", std::process::id()));
+
+    let agent_pool = Arc::new(Mutex::new(AgentPool::new()));
+    let action_queue = Arc::new(Mutex::new(ActionsQueue::new()));
+
+    let (server_pool, server_queue) = (agent_pool.clone(), action_queue.clone());
+    tokio::spawn(async move {
+        let addr = "[::1]:50051".parse().unwrap();
+        let agent = AgentService::new(server_pool.clone(), server_queue.clone());
+        // Actions are cancelled as soon as their Controller goes away.
+        let controller = ControllerService::new(server_pool, server_queue).with_reattach_grace_period(Duration::ZERO);
+
+        Server::builder()
+            .add_service(AgentServer::new(agent))
+            .add_service(ControllerServer::new(controller))
+            .serve(addr)
+            .await
+            .unwrap();
+    });
+
+    // More Agents are POSTed for from 2 queued Actions, then not again before a minute.
+    let scale_up = Autoscaler::new()
+        .with_queue_depth(Some(2))
+        .with_cooldowns(Duration::from_secs(60), Duration::from_secs(60));
+    let hook = ScaleHook::Url(String::from("http://127.0.0.1:50054/scale"));
+    tokio::spawn(autoscale(scale_up, hook, agent_pool.clone(), action_queue.clone(), Duration::from_millis(100)));
+
+    // Fewer Agents are asked for by a script once an Agent has been idle for a second.
+    let scale_down = Autoscaler::new().with_idle_time(Some(Duration::from_secs(1)));
+    let hook = ScaleHook::Command(format!("cat > {}", scale_down_file.display()));
+    tokio::spawn(autoscale(scale_down, hook, agent_pool.clone(), action_queue.clone(), Duration::from_millis(100)));
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let channel = Channel::from_static("http://[::1]:50051").connect().await?;
+    let mut agent_client = AgentClient::new(channel.clone());
+    let mut controller_client = ControllerClient::new(channel);
+
+    // This Agent has no GPU, so both Actions wait in the Queue.
+    let request = Request::new(RegisterAgentRequest {
+        health: Some(Health { cpu_avail: 42, memory_avail: 2048 }),
+        hostname: Some(agent::Hostname { host: String::from("http://[::1]"), port: 50053 }),
+        labels: vec![],
+        capabilities: None,
+        pull: false,
+        ephemeral: false,
+    });
+    let id = agent_client.register_agent(request).await?.get_ref().id;
+
+    let first = controller_client.schedule_action(Request::new(action_request(1, &["gpu"]))).await?.into_inner();
+    let second = controller_client.schedule_action(Request::new(action_request(2, &["gpu"]))).await?.into_inner();
+
+    let request = tokio::time::timeout(Duration::from_secs(5), scale_requests.recv()).await?.unwrap();
+    assert_eq!(request["direction"], "up");
+    assert_eq!(request["reason"], "queue_depth");
+    assert_eq!(request["agents"], 1);
+    assert_eq!(request["desired_agents"], 2);
+    assert_eq!(request["queued_actions"], 2);
+
+    // The cooldown keeps the Autoscaler from asking again.
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert!(scale_requests.try_recv().is_err());
+
+    // Once the Queue is empty, the Agent that has had nothing to run is given up on.
+    assert!(!scale_down_file.exists());
+    drop(first);
+    drop(second);
+
+    let mut scale_down_request = None;
+    for _ in 0..50 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        if let Ok(contents) = std::fs::read_to_string(&scale_down_file) {
+            if let Ok(request) = serde_json::from_str::<Value>(&contents) {
+                scale_down_request = Some(request);
+                break;
+            }
+        }
+    }
+    let _ = std::fs::remove_file(&scale_down_file);
+    let request = scale_down_request.expect("The scaling command was not run");
+    assert_eq!(request["direction"], "down");
+    assert_eq!(request["reason"], "idle_agents");
+    assert_eq!(request["agents"], 1);
+    assert_eq!(request["desired_agents"], 0);
+    assert_eq!(request["idle_agents"], serde_json::json!([id]));
+
+    Ok(())
+}
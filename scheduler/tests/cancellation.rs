@@ -0,0 +1,118 @@
+mod common;
+use common::{action_request, RecordingAgent};
+
+use scheduler::proto::scheduler as agent;
+use agent::agent_client::AgentClient;
+use agent::agent_server::AgentServer;
+use agent::{Health, RegisterAgentRequest};
+
+use scheduler::proto::scheduler as admin;
+use admin::admin_client::AdminClient;
+use admin::admin_server::AdminServer;
+use admin::{AdminActionState, GetActionRequest, ListAgentsRequest};
+
+use scheduler::proto::scheduler as controller;
+use controller::controller_server::ControllerServer;
+use controller::{controller_client::ControllerClient, ActionStatus};
+
+use scheduler::interfaces::server as server;
+use server::admin_interface::AdminService;
+use server::agent_interface::AgentService;
+use server::controller_interface::ControllerService;
+
+use scheduler::logic as logic;
+use logic::agent_pool_logic::AgentPool;
+use logic::action_queue_logic::ActionsQueue;
+
+use tonic::transport::Server;
+use tonic::transport::Channel;
+use tonic::Request;
+use std::error::Error;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::Duration;
+
+#[tokio::test]
+async fn test_cancellation() -> Result<(), Box<dyn Error>> {
+    // Actions never complete on their own.
+    let recording_agent = RecordingAgent::serve(50052, Duration::from_secs(3600));
+
+    tokio::spawn(async {
+        let addr = "[::1]:50051".parse().unwrap();
+        let agent_pool = Arc::new(Mutex::new(AgentPool::new().with_agent_capacity(1)));
+        let action_queue = Arc::new(Mutex::new(ActionsQueue::new()));
+        let agent = AgentService::new(agent_pool.clone(), action_queue.clone());
+        // Actions are cancelled as soon as their Controller goes away.
+        let controller =
+            ControllerService::new(agent_pool.clone(), action_queue.clone()).with_reattach_grace_period(Duration::ZERO);
+        let admin = AdminService::new(agent_pool.clone(), action_queue.clone());
+
+        Server::builder()
+            .add_service(AgentServer::new(agent))
+            .add_service(ControllerServer::new(controller))
+            .add_service(AdminServer::new(admin))
+            .serve(addr)
+            .await
+            .unwrap();
+    });
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let channel = Channel::from_static("http://[::1]:50051").connect().await?;
+    let mut agent_client = AgentClient::new(channel.clone());
+    let mut admin_client = AdminClient::new(channel.clone());
+
+    let agent_id = agent_client
+        .register_agent(Request::new(RegisterAgentRequest {
+            health: Some(Health { cpu_avail: 42, memory_avail: 2048 }),
+            hostname: Some(agent::Hostname { host: String::from("http://[::1]"), port: 50052 }),
+            labels: vec![],
+            capabilities: None,
+            pull: false,
+            ephemeral: false,
+        }))
+        .await?
+        .into_inner()
+        .id;
+
+    // Each Controller gets its own connection, so that it can go away on its own.
+    let running_channel = Channel::from_static("http://[::1]:50051").connect().await?;
+    let running_stream = ControllerClient::new(running_channel.clone())
+        .schedule_action(Request::new(action_request(1, &[])))
+        .await?;
+    let queued_channel = Channel::from_static("http://[::1]:50051").connect().await?;
+    let queued_stream = ControllerClient::new(queued_channel.clone())
+        .schedule_action(Request::new(action_request(2, &[])))
+        .await?;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(*recording_agent.executed.lock().await, vec![1]);
+
+    // The queued Action's Controller goes away: the Action leaves the Queue.
+    drop(queued_stream);
+    drop(queued_channel);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let action = admin_client.get_action(Request::new(GetActionRequest { action_id: 2 })).await?.into_inner();
+    assert_eq!(action.state(), AdminActionState::Cancelled);
+    assert_eq!(action.status(), ActionStatus::Cancelled);
+    assert_eq!(action.agent_id, None);
+
+    // The running Action's Controller goes away: the Action is cancelled on the Agent, which is freed.
+    drop(running_stream);
+    drop(running_channel);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(*recording_agent.cancelled.lock().await, vec![1]);
+    let action = admin_client.get_action(Request::new(GetActionRequest { action_id: 1 })).await?.into_inner();
+    assert_eq!(action.state(), AdminActionState::Cancelled);
+    assert_eq!(action.agent_id, Some(agent_id));
+    let agents = admin_client.list_agents(Request::new(ListAgentsRequest {})).await?.into_inner().agents;
+    assert!(agents[0].running_actions.is_empty());
+
+    // The Agent takes the next Action right away.
+    let _stream = ControllerClient::new(channel)
+        .schedule_action(Request::new(action_request(3, &[])))
+        .await?;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(*recording_agent.executed.lock().await, vec![1, 3]);
+
+    Ok(())
+}
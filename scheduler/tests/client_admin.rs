@@ -0,0 +1,120 @@
+use scheduler::proto::scheduler as agent;
+use agent::agent_client::AgentClient;
+use agent::agent_server::AgentServer;
+use agent::{Health, RegisterAgentRequest};
+
+use scheduler::proto::scheduler as controller;
+use controller::controller_server::ControllerServer;
+
+use scheduler::proto::scheduler as admin;
+use admin::admin_client::AdminClient;
+use admin::admin_server::AdminServer;
+use admin::{AgentRequest, GetActionRequest, ListAgentsRequest, ListQueueRequest};
+
+use scheduler::interfaces::server as server;
+use server::admin_interface::AdminService;
+use server::agent_interface::AgentService;
+use server::controller_interface::ControllerService;
+
+use scheduler::logic as logic;
+use logic::agent_pool_logic::AgentPool;
+use logic::action_queue_logic::ActionsQueue;
+
+use tonic::transport::Server;
+use tonic::transport::Channel;
+use tonic::{Code, Request};
+use tonic_reflection::pb::server_reflection_client::ServerReflectionClient;
+use tonic_reflection::pb::server_reflection_request::MessageRequest;
+use tonic_reflection::pb::server_reflection_response::MessageResponse;
+use tonic_reflection::pb::ServerReflectionRequest;
+use std::error::Error;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::Duration;
+
+#[tokio::test]
+async fn test_admin() -> Result<(), Box<dyn Error>> {
+    tokio::spawn(async {
+        let addr = "[::1]:50051".parse().unwrap();
+        let agent_pool = Arc::new(Mutex::new(AgentPool::new()));
+        let action_queue = Arc::new(Mutex::new(ActionsQueue::new()));
+        let agent = AgentService::new(agent_pool.clone(), action_queue.clone());
+        let controller = ControllerService::new(agent_pool.clone(), action_queue.clone());
+        let admin = AdminService::new(agent_pool.clone(), action_queue.clone());
+        let service = tonic_reflection::server::Builder::configure()
+            .register_encoded_file_descriptor_set(scheduler::proto::FILE_DESCRIPTOR_SET)
+            .build()
+            .unwrap();
+
+        Server::builder()
+            .add_service(service)
+            .add_service(AgentServer::new(agent))
+            .add_service(ControllerServer::new(controller))
+            .add_service(AdminServer::new(admin))
+            .serve(addr)
+            .await
+            .unwrap();
+    });
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let channel = Channel::from_static("http://[::1]:50051").connect().await?;
+    let mut agent_client = AgentClient::new(channel.clone());
+    let mut client = AdminClient::new(channel.clone());
+
+    // The Admin service is discoverable through reflection.
+    let mut reflection_client = ServerReflectionClient::new(channel);
+    let reflection_request = ServerReflectionRequest {
+        host: String::new(),
+        message_request: Some(MessageRequest::ListServices(String::new())),
+    };
+    let mut reflection_stream = reflection_client
+        .server_reflection_info(tokio_stream::iter(vec![reflection_request]))
+        .await?
+        .into_inner();
+    let services = match reflection_stream.message().await?.and_then(|response| response.message_response) {
+        Some(MessageResponse::ListServicesResponse(services)) => services.service,
+        _ => vec![],
+    };
+    assert!(services.iter().any(|service| service.name == "scheduler.Admin"));
+
+    let request = Request::new(RegisterAgentRequest {
+        health: Some(Health { cpu_avail: 42, memory_avail: 2048 }),
+        hostname: Some(agent::Hostname { host: "localhost".to_string(), port: 1234 }),
+        labels: vec![],
+        capabilities: None,
+        pull: false,
+        ephemeral: false,
+    });
+    let id = agent_client.register_agent(request).await?.get_ref().id;
+
+    let agents = client.list_agents(Request::new(ListAgentsRequest {})).await?.into_inner().agents;
+    assert_eq!(agents.len(), 1);
+    assert_eq!(agents[0].id, id);
+    assert_eq!(agents[0].health, Some(Health { cpu_avail: 42, memory_avail: 2048 }));
+    assert!(agents[0].running_actions.is_empty());
+    assert!(!agents[0].cordoned);
+
+    let cordoned = client.cordon_agent(Request::new(AgentRequest { agent_id: id })).await?.into_inner();
+    assert!(cordoned.cordoned);
+
+    let uncordoned = client.uncordon_agent(Request::new(AgentRequest { agent_id: id })).await?.into_inner();
+    assert!(!uncordoned.cordoned);
+
+    let queue = client.list_queue(Request::new(ListQueueRequest {})).await?.into_inner().actions;
+    assert!(queue.is_empty());
+
+    let status = client.get_action(Request::new(GetActionRequest { action_id: 69420 })).await.unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+
+    let evicted = client.evict_agent(Request::new(AgentRequest { agent_id: id })).await?.into_inner();
+    assert_eq!(evicted.id, id);
+
+    let agents = client.list_agents(Request::new(ListAgentsRequest {})).await?.into_inner().agents;
+    assert!(agents.is_empty());
+
+    let status = client.cordon_agent(Request::new(AgentRequest { agent_id: id })).await.unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+
+    Ok(())
+}
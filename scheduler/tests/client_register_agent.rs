@@ -14,7 +14,7 @@ use server::controller_interface::ControllerService;
 
 use scheduler::logic as logic;
 use logic::agent_pool_logic::AgentPool;
-// use logic::action_queue_logic::ActionsQueue;
+use logic::action_queue_logic::ActionsQueue;
 
 use tonic::transport::Server;
 use tonic::Request;
@@ -28,9 +28,9 @@ async fn test_register_agent() -> Result<(), Box<dyn Error>> {
     tokio::spawn(async {
         let addr = "[::1]:50051".parse().unwrap();
         let agent_pool = Arc::new(Mutex::new(AgentPool::new()));
-        // let action_queue = Arc::new(Mutex::new(ActionsQueue::new()));
-        let agent = AgentService::new(agent_pool.clone());
-        let controller = ControllerService::new(agent_pool.clone());
+        let action_queue = Arc::new(Mutex::new(ActionsQueue::new()));
+        let agent = AgentService::new(agent_pool.clone(), action_queue.clone());
+        let controller = ControllerService::new(agent_pool.clone(), action_queue.clone());
         let service = tonic_reflection::server::Builder::configure()
             .register_encoded_file_descriptor_set(scheduler::proto::FILE_DESCRIPTOR_SET)
             .build()
@@ -50,7 +50,18 @@ async fn test_register_agent() -> Result<(), Box<dyn Error>> {
     let mut client = AgentClient::connect("http://[::1]:50051").await?;
 
     let req = Health { cpu_avail: 123, memory_avail: 321 };
-    let request = Request::new(RegisterAgentRequest { health: Some(req), hostname: Some(agent::Hostname { host: "localhost".to_string(), port: 1234 }) });
+    let request = Request::new(RegisterAgentRequest {
+        health: Some(req),
+        hostname: Some(agent::Hostname { host: "localhost".to_string(), port: 1234 }),
+        labels: vec![String::from("gpu")],
+        capabilities: Some(agent::Capabilities {
+            arch: String::from("x86_64"),
+            os: String::from("linux"),
+            runner_types: vec![String::from("docker")],
+        }),
+        pull: false,
+        ephemeral: false,
+    });
 
     let response = client.register_agent(request).await?;
 
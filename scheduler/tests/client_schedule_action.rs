@@ -13,7 +13,7 @@ use server::controller_interface::ControllerService;
 
 use scheduler::logic as logic;
 use logic::agent_pool_logic::AgentPool;
-// use logic::action_queue_logic::ActionsQueue;
+use logic::action_queue_logic::ActionsQueue;
 
 use tonic::transport::Server;
 use tonic::transport::Channel;
@@ -28,9 +28,9 @@ async fn test_schedule_action() -> Result<(), Box<dyn Error>> {
     tokio::spawn(async {
         let addr = "[::1]:50051".parse().unwrap();
         let agent_pool = Arc::new(Mutex::new(AgentPool::new()));
-        // let action_queue = Arc::new(Mutex::new(ActionsQueue::new()));
-        let agent = AgentService::new(agent_pool.clone());
-        let controller = ControllerService::new(agent_pool.clone());
+        let action_queue = Arc::new(Mutex::new(ActionsQueue::new()));
+        let agent = AgentService::new(agent_pool.clone(), action_queue.clone());
+        let controller = ControllerService::new(agent_pool.clone(), action_queue.clone());
         let service = tonic_reflection::server::Builder::configure()
             .register_encoded_file_descriptor_set(scheduler::proto::FILE_DESCRIPTOR_SET)
             .build()
@@ -58,6 +58,10 @@ async fn test_schedule_action() -> Result<(), Box<dyn Error>> {
         }),
         commands: vec![String::from("echo 'Salut les zagennntss!!!'"), String::from("shutdown now")],
         repo_url: String::from("sealci-repo-url"),
+        priority: 0,
+        tenant: String::new(),
+        runs_on: vec![],
+        env: vec![],
     });
 
     let mut response_stream = client.schedule_action(request).await?.into_inner();
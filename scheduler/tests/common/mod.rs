@@ -0,0 +1,100 @@
+use scheduler::proto::actions as actions;
+use actions::action_service_server::{ActionService, ActionServiceServer};
+
+use scheduler::proto::scheduler::{ActionRequest, ExecutionContext, RunnerType};
+
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+use tokio::time::Duration;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+/// A container Action for the given `runs-on` selectors, as the Controller sends it.
+#[allow(dead_code)]
+pub fn action_request(action_id: u32, runs_on: &[&str]) -> ActionRequest {
+    ActionRequest {
+        action_id,
+        context: Some(ExecutionContext {
+            r#type: RunnerType::Docker.into(),
+            container_image: Some(String::from("test_image")),
+        }),
+        commands: vec![String::from("cargo build")],
+        repo_url: String::from("sealci-repo-url"),
+        priority: 0,
+        tenant: String::new(),
+        runs_on: runs_on.iter().map(|selector| selector.to_string()).collect(),
+        env: vec![],
+    }
+}
+
+/// Agent stub recording the Actions it executes and cancels, in order.
+/// Each Action completes after the given duration, unless it is cancelled first.
+#[allow(dead_code)]
+#[derive(Clone)]
+pub struct RecordingAgent {
+    duration: Duration,
+    pub executed: Arc<Mutex<Vec<u32>>>,
+    pub cancelled: Arc<Mutex<Vec<u32>>>,
+}
+
+#[allow(dead_code)]
+impl RecordingAgent {
+    /// Start the Agent stub on the given port of [::1].
+    pub fn serve(port: u16, duration: Duration) -> Self {
+        let agent = Self {
+            duration,
+            executed: Arc::new(Mutex::new(Vec::new())),
+            cancelled: Arc::new(Mutex::new(Vec::new())),
+        };
+        let service = ActionServiceServer::new(agent.clone());
+        tokio::spawn(async move {
+            Server::builder()
+                .add_service(service)
+                .serve(format!("[::1]:{}", port).parse().unwrap())
+                .await
+                .unwrap();
+        });
+        agent
+    }
+}
+
+#[tonic::async_trait]
+impl ActionService for RecordingAgent {
+    type ExecutionActionStream = UnboundedReceiverStream<Result<actions::ActionResponseStream, Status>>;
+
+    async fn execution_action(
+        &self,
+        request: Request<actions::ActionRequest>,
+    ) -> Result<Response<Self::ExecutionActionStream>, Status> {
+        let action_id = request.into_inner().action_id;
+        self.executed.lock().await.push(action_id);
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let duration = self.duration;
+        let cancelled = self.cancelled.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(duration).await;
+            if cancelled.lock().await.contains(&action_id) {
+                return;
+            }
+            let _ = tx.send(Ok(actions::ActionResponseStream {
+                action_id,
+                log: String::from("done"),
+                result: Some(actions::ActionResult {
+                    completion: actions::ActionStatus::Completed.into(),
+                    exit_code: Some(0),
+                }),
+            }));
+        });
+        Ok(Response::new(UnboundedReceiverStream::new(rx)))
+    }
+
+    async fn cancel_action(
+        &self,
+        request: Request<actions::CancelActionRequest>,
+    ) -> Result<Response<actions::CancelActionResponse>, Status> {
+        self.cancelled.lock().await.push(request.into_inner().action_id);
+        Ok(Response::new(actions::CancelActionResponse {}))
+    }
+}
@@ -0,0 +1,156 @@
+use scheduler::proto::scheduler as agent;
+use agent::agent_client::AgentClient;
+use agent::agent_server::AgentServer;
+use agent::{Health, RegisterAgentRequest};
+
+use scheduler::proto::scheduler as controller;
+use controller::controller_server::ControllerServer;
+
+use scheduler::interfaces::server as server;
+use server::agent_interface::AgentService;
+use server::controller_interface::ControllerService;
+
+use scheduler::logic as logic;
+use logic::agent_pool_logic::{AgentPool, Strategy};
+use logic::action_queue_logic::ActionsQueue;
+
+use scheduler::config::{Args, Config, ConfigError};
+
+use tonic::transport::Server;
+use tonic::Request;
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::Duration;
+
+fn write_config_file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("sealci-scheduler-{}-{}.yaml", name, std::process::id()));
+    std::fs::write(&path, contents).expect("Failed to write config file");
+    path
+}
+
+#[test]
+fn test_default_config_is_valid() {
+    let config = Config::default();
+
+    assert!(config.validate().is_ok());
+    assert_eq!(config.listen_address, "[::0]:50051".parse().unwrap());
+    assert_eq!(config.strategy, Strategy::LowestScore);
+    assert_eq!(config.tls, None);
+}
+
+#[test]
+fn test_config_file_is_overridden_by_args() {
+    let path = write_config_file(
+        "override",
+        "listen_address: \"[::1]:6000\"\nheartbeat_timeout: 5\nstrategy: highest-score\nlog_level: debug\ntenant_weights:\n  https://github.com/dev-sys-do/sealci.git: 3\n",
+    );
+
+    let config = Config::load(Args {
+        config: Some(path.clone()),
+        strategy: Some(Strategy::LeastLoaded),
+        max_queued_actions: Some(3),
+        ..Args::default()
+    })
+    .unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(config.listen_address, "[::1]:6000".parse().unwrap());
+    assert_eq!(config.heartbeat_timeout, 5);
+    assert_eq!(config.max_queued_actions, 3);
+    assert_eq!(config.strategy, Strategy::LeastLoaded);
+    assert_eq!(config.log_level, "debug");
+    assert_eq!(config.tenant_weights.get("https://github.com/dev-sys-do/sealci.git"), Some(&3));
+    assert_eq!(config.agent_capacity, 1);
+}
+
+#[test]
+fn test_invalid_config_is_rejected() {
+    let path = write_config_file("unknown", "listen_adress: \"[::1]:6000\"\n");
+    let result = Config::load(Args { config: Some(path.clone()), ..Args::default() });
+    std::fs::remove_file(path).unwrap();
+    assert!(matches!(result, Err(ConfigError::ParseError(_, _))));
+
+    let result = Config::load(Args { heartbeat_timeout: Some(0), ..Args::default() });
+    assert!(matches!(result, Err(ConfigError::InvalidValue(_))));
+
+    let result = Config::load(Args { agent_capacity: Some(0), ..Args::default() });
+    assert!(matches!(result, Err(ConfigError::InvalidValue(_))));
+
+    let result = Config::load(Args { log_buffer_size: Some(0), ..Args::default() });
+    assert!(matches!(result, Err(ConfigError::InvalidValue(_))));
+
+    let path = write_config_file("weight", "tenant_weights:\n  https://github.com/dev-sys-do/sealci.git: 0\n");
+    let result = Config::load(Args { config: Some(path.clone()), ..Args::default() });
+    std::fs::remove_file(path).unwrap();
+    assert!(matches!(result, Err(ConfigError::InvalidValue(_))));
+
+    let path = write_config_file(
+        "autoscaling",
+        "autoscaling:\n  command: scale.sh\n  url: http://localhost:8080/scale\n  queue_depth: 10\n",
+    );
+    let result = Config::load(Args { config: Some(path.clone()), ..Args::default() });
+    std::fs::remove_file(path).unwrap();
+    assert!(matches!(result, Err(ConfigError::InvalidValue(_))));
+
+    let path = write_config_file("thresholds", "autoscaling:\n  command: scale.sh\n");
+    let result = Config::load(Args { config: Some(path.clone()), ..Args::default() });
+    std::fs::remove_file(path).unwrap();
+    assert!(matches!(result, Err(ConfigError::InvalidValue(_))));
+
+    let result = Config::load(Args { log_level: Some(String::from("loud")), ..Args::default() });
+    assert!(matches!(result, Err(ConfigError::InvalidValue(_))));
+
+    let result = Config::load(Args { tls_cert: Some(PathBuf::from("server.pem")), ..Args::default() });
+    assert!(matches!(result, Err(ConfigError::InvalidValue(_))));
+
+    let result = Config::load(Args {
+        tls_cert: Some(PathBuf::from("/nonexistent/server.pem")),
+        tls_key: Some(PathBuf::from("/nonexistent/server.key")),
+        ..Args::default()
+    });
+    assert!(matches!(result, Err(ConfigError::InvalidValue(_))));
+}
+
+#[tokio::test]
+async fn test_server_from_config() -> Result<(), Box<dyn Error>> {
+    let config = Config {
+        listen_address: "[::1]:50051".parse()?,
+        ..Config::default()
+    };
+    config.validate()?;
+
+    let server_config = config.clone();
+    tokio::spawn(async move {
+        let agent_pool = Arc::new(Mutex::new(AgentPool::with_strategy(server_config.strategy)));
+        let action_queue = Arc::new(Mutex::new(ActionsQueue::with_max_length(server_config.max_queued_actions)));
+        let agent = AgentService::new(agent_pool.clone(), action_queue.clone());
+        let controller = ControllerService::new(agent_pool.clone(), action_queue.clone());
+
+        Server::builder()
+            .add_service(AgentServer::new(agent))
+            .add_service(ControllerServer::new(controller))
+            .serve(server_config.listen_address)
+            .await
+            .unwrap();
+    });
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let mut client = AgentClient::connect(format!("http://{}", config.listen_address)).await?;
+
+    let request = Request::new(RegisterAgentRequest {
+        health: Some(Health { cpu_avail: 123, memory_avail: 321 }),
+        hostname: Some(agent::Hostname { host: "localhost".to_string(), port: 1234 }),
+        labels: vec![],
+        capabilities: None,
+        pull: false,
+        ephemeral: false,
+    });
+    let response = client.register_agent(request).await?;
+
+    assert_eq!(response.get_ref().id, 1);
+
+    Ok(())
+}
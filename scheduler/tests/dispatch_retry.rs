@@ -0,0 +1,120 @@
+mod common;
+use common::{action_request, RecordingAgent};
+
+use scheduler::proto::scheduler as agent;
+use agent::agent_client::AgentClient;
+use agent::agent_server::AgentServer;
+use agent::{Health, RegisterAgentRequest};
+
+use scheduler::proto::scheduler as admin;
+use admin::admin_client::AdminClient;
+use admin::admin_server::AdminServer;
+use admin::ListAgentsRequest;
+
+use scheduler::proto::scheduler as controller;
+use controller::controller_server::ControllerServer;
+use controller::{controller_client::ControllerClient, ActionStatus, ResponseSource};
+
+use scheduler::interfaces::server as server;
+use server::admin_interface::AdminService;
+use server::agent_interface::AgentService;
+use server::controller_interface::ControllerService;
+
+use scheduler::logic as logic;
+use logic::agent_pool_logic::AgentPool;
+use logic::action_queue_logic::ActionsQueue;
+
+use tonic::transport::Server;
+use tonic::transport::Channel;
+use tonic::Request;
+use std::error::Error;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::Duration;
+
+#[tokio::test]
+async fn test_dispatch_retry() -> Result<(), Box<dyn Error>> {
+    let recording_agent = RecordingAgent::serve(50052, Duration::ZERO);
+    let executed = recording_agent.executed.clone();
+
+    tokio::spawn(async {
+        let addr = "[::1]:50051".parse().unwrap();
+        // A single failed dispatch quarantines an Agent.
+        let agent_pool = Arc::new(Mutex::new(AgentPool::new().with_circuit_breaker(1, Duration::from_secs(60))));
+        let action_queue = Arc::new(Mutex::new(ActionsQueue::new()));
+        let agent = AgentService::new(agent_pool.clone(), action_queue.clone());
+        let controller = ControllerService::new(agent_pool.clone(), action_queue.clone()).with_dispatch_attempts(2);
+        let admin = AdminService::new(agent_pool.clone(), action_queue.clone());
+
+        Server::builder()
+            .add_service(AgentServer::new(agent))
+            .add_service(ControllerServer::new(controller))
+            .add_service(AdminServer::new(admin))
+            .serve(addr)
+            .await
+            .unwrap();
+    });
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let channel = Channel::from_static("http://[::1]:50051").connect().await?;
+    let mut agent_client = AgentClient::new(channel.clone());
+    let mut controller_client = ControllerClient::new(channel.clone());
+    let mut admin_client = AdminClient::new(channel);
+
+    // The dead Agent has the lowest score, so it is picked first.
+    let dead_id = agent_client
+        .register_agent(Request::new(RegisterAgentRequest {
+            health: Some(Health { cpu_avail: 1, memory_avail: 1 }),
+            hostname: Some(agent::Hostname { host: String::from("http://[::1]"), port: 50053 }),
+            labels: vec![],
+            capabilities: None,
+            pull: false,
+            ephemeral: false,
+        }))
+        .await?
+        .into_inner()
+        .id;
+    agent_client
+        .register_agent(Request::new(RegisterAgentRequest {
+            health: Some(Health { cpu_avail: 42, memory_avail: 2048 }),
+            hostname: Some(agent::Hostname { host: String::from("http://[::1]"), port: 50052 }),
+            labels: vec![],
+            capabilities: None,
+            pull: false,
+            ephemeral: false,
+        }))
+        .await?;
+
+    let mut stream = controller_client.schedule_action(Request::new(action_request(1, &[]))).await?.into_inner();
+    let mut responses = Vec::new();
+    while let Some(response) = stream.message().await? {
+        responses.push(response);
+    }
+
+    // The failed dispatch is reported to the Controller, then the Action runs on the other Agent.
+    assert_eq!(responses.len(), 2);
+    assert!(responses[0].log.contains("Retrying on another Agent"));
+    assert_eq!(responses[0].result.as_ref().unwrap().completion(), ActionStatus::Scheduled);
+    assert_eq!(responses[0].source(), ResponseSource::Scheduler);
+    assert_eq!(responses[1].log, "done");
+    assert_eq!(responses[1].source(), ResponseSource::Agent);
+    assert_eq!(responses.iter().map(|response| response.seq).collect::<Vec<_>>(), vec![0, 1]);
+    assert_eq!(*executed.lock().await, vec![1]);
+
+    let agents = admin_client.list_agents(Request::new(ListAgentsRequest {})).await?.into_inner().agents;
+    let dead = agents.iter().find(|agent| agent.id == dead_id).unwrap();
+    assert!(dead.quarantined);
+
+    // The quarantined Agent is skipped altogether.
+    let mut stream = controller_client.schedule_action(Request::new(action_request(2, &[]))).await?.into_inner();
+    let mut responses = Vec::new();
+    while let Some(response) = stream.message().await? {
+        responses.push(response);
+    }
+    assert_eq!(responses.len(), 1);
+    assert_eq!(responses[0].log, "done");
+    assert_eq!(*executed.lock().await, vec![1, 2]);
+
+    Ok(())
+}
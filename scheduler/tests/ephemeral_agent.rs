@@ -0,0 +1,160 @@
+mod common;
+use common::{action_request, RecordingAgent};
+
+use scheduler::proto::scheduler as agent;
+use agent::agent_client::AgentClient;
+use agent::agent_server::AgentServer;
+use agent::{Health, RegisterAgentRequest, UnregisterAgentRequest};
+
+use scheduler::proto::scheduler as admin;
+use admin::admin_client::AdminClient;
+use admin::admin_server::AdminServer;
+use admin::{ListAgentsRequest, ListQueueRequest};
+
+use scheduler::proto::scheduler as controller;
+use controller::controller_server::ControllerServer;
+use controller::controller_client::ControllerClient;
+
+use scheduler::interfaces::server as server;
+use server::admin_interface::AdminService;
+use server::agent_interface::AgentService;
+use server::controller_interface::ControllerService;
+
+use scheduler::logic as logic;
+use logic::agent_pool_logic::AgentPool;
+use logic::action_queue_logic::ActionsQueue;
+
+use tonic::transport::Server;
+use tonic::transport::Channel;
+use tonic::{Code, Request};
+use std::error::Error;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::Duration;
+
+#[tokio::test]
+async fn test_ephemeral_agent() -> Result<(), Box<dyn Error>> {
+    let recording_agent = RecordingAgent::serve(50052, Duration::from_millis(100));
+    let executed = recording_agent.executed.clone();
+
+    tokio::spawn(async {
+        let addr = "[::1]:50051".parse().unwrap();
+        let agent_pool = Arc::new(Mutex::new(AgentPool::new()));
+        let action_queue = Arc::new(Mutex::new(ActionsQueue::new()));
+        let agent = AgentService::new(agent_pool.clone(), action_queue.clone());
+        let controller = ControllerService::new(agent_pool.clone(), action_queue.clone());
+        let admin = AdminService::new(agent_pool.clone(), action_queue.clone());
+
+        Server::builder()
+            .add_service(AgentServer::new(agent))
+            .add_service(ControllerServer::new(controller))
+            .add_service(AdminServer::new(admin))
+            .serve(addr)
+            .await
+            .unwrap();
+    });
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let channel = Channel::from_static("http://[::1]:50051").connect().await?;
+    let mut agent_client = AgentClient::new(channel.clone());
+    let mut controller_client = ControllerClient::new(channel.clone());
+    let mut admin_client = AdminClient::new(channel);
+
+    let request = Request::new(RegisterAgentRequest {
+        health: Some(Health { cpu_avail: 42, memory_avail: 2048 }),
+        hostname: Some(agent::Hostname { host: String::from("http://[::1]"), port: 50052 }),
+        labels: vec![],
+        capabilities: None,
+        pull: false,
+        ephemeral: true,
+    });
+    let id = agent_client.register_agent(request).await?.get_ref().id;
+
+    let agents = admin_client.list_agents(Request::new(ListAgentsRequest {})).await?.into_inner().agents;
+    assert!(agents[0].ephemeral);
+
+    let mut first = controller_client.schedule_action(Request::new(action_request(1, &[]))).await?.into_inner();
+    let mut second = controller_client.schedule_action(Request::new(action_request(2, &[]))).await?.into_inner();
+
+    while first.message().await?.is_some() {}
+    assert_eq!(*executed.lock().await, vec![1]);
+
+    // The Agent is done with its Action, and is never given the second one.
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(*executed.lock().await, vec![1]);
+    let queue = admin_client.list_queue(Request::new(ListQueueRequest {})).await?.into_inner().actions;
+    assert_eq!(queue.len(), 1);
+    assert_eq!(queue[0].action_id, 2);
+
+    agent_client.unregister_agent(Request::new(UnregisterAgentRequest { id })).await?;
+    let agents = admin_client.list_agents(Request::new(ListAgentsRequest {})).await?.into_inner().agents;
+    assert!(agents.is_empty());
+
+    let status = agent_client.unregister_agent(Request::new(UnregisterAgentRequest { id })).await.unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+
+    // A new Agent gets the second Action.
+    let request = Request::new(RegisterAgentRequest {
+        health: Some(Health { cpu_avail: 42, memory_avail: 2048 }),
+        hostname: Some(agent::Hostname { host: String::from("http://[::1]"), port: 50052 }),
+        labels: vec![],
+        capabilities: None,
+        pull: false,
+        ephemeral: true,
+    });
+    agent_client.register_agent(request).await?;
+    while second.message().await?.is_some() {}
+    assert_eq!(*executed.lock().await, vec![1, 2]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ephemeral_agent_after_failed_dispatch() -> Result<(), Box<dyn Error>> {
+    tokio::spawn(async {
+        let addr = "[::1]:50055".parse().unwrap();
+        let agent_pool = Arc::new(Mutex::new(AgentPool::new()));
+        let action_queue = Arc::new(Mutex::new(ActionsQueue::new()));
+        let agent = AgentService::new(agent_pool.clone(), action_queue.clone());
+        let controller = ControllerService::new(agent_pool.clone(), action_queue.clone());
+
+        Server::builder()
+            .add_service(AgentServer::new(agent))
+            .add_service(ControllerServer::new(controller))
+            .serve(addr)
+            .await
+            .unwrap();
+    });
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let channel = Channel::from_static("http://[::1]:50055").connect().await?;
+    let mut agent_client = AgentClient::new(channel.clone());
+    let mut controller_client = ControllerClient::new(channel);
+
+    // Nothing listens on the Agent's port yet: its first Action cannot be dispatched to it.
+    let request = Request::new(RegisterAgentRequest {
+        health: Some(Health { cpu_avail: 42, memory_avail: 2048 }),
+        hostname: Some(agent::Hostname { host: String::from("http://[::1]"), port: 50056 }),
+        labels: vec![],
+        capabilities: None,
+        pull: false,
+        ephemeral: true,
+    });
+    agent_client.register_agent(request).await?;
+    let mut first = controller_client.schedule_action(Request::new(action_request(1, &[]))).await?.into_inner();
+    while first.message().await?.is_some() {}
+
+    // The Agent never got that Action, so it is not spent: it gets the next one once it is up.
+    let recording_agent = RecordingAgent::serve(50056, Duration::from_millis(100));
+    let mut second = controller_client.schedule_action(Request::new(action_request(2, &[]))).await?.into_inner();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while second.message().await?.is_some() {}
+        Ok::<(), tonic::Status>(())
+    })
+    .await??;
+    assert_eq!(*recording_agent.executed.lock().await, vec![2]);
+
+    Ok(())
+}
@@ -0,0 +1,86 @@
+mod common;
+use common::{action_request, RecordingAgent};
+
+use scheduler::proto::scheduler as agent;
+use agent::agent_client::AgentClient;
+use agent::agent_server::AgentServer;
+use agent::{Health, RegisterAgentRequest};
+
+use scheduler::proto::scheduler as controller;
+use controller::controller_server::ControllerServer;
+use controller::{controller_client::ControllerClient, ActionRequest};
+
+use scheduler::interfaces::server as server;
+use server::agent_interface::AgentService;
+use server::controller_interface::ControllerService;
+
+use scheduler::logic as logic;
+use logic::agent_pool_logic::AgentPool;
+use logic::action_queue_logic::ActionsQueue;
+
+use tonic::transport::Server;
+use tonic::transport::Channel;
+use tonic::Request;
+use std::error::Error;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::Duration;
+
+#[tokio::test]
+async fn test_fair_queue() -> Result<(), Box<dyn Error>> {
+    let recording_agent = RecordingAgent::serve(50052, Duration::from_millis(300));
+    let executed = recording_agent.executed.clone();
+
+    tokio::spawn(async {
+        let addr = "[::1]:50051".parse().unwrap();
+        // A single Agent running one Action at a time, so that every other Action has to wait in the Queue.
+        let agent_pool = Arc::new(Mutex::new(AgentPool::new().with_agent_capacity(1)));
+        let action_queue = Arc::new(Mutex::new(ActionsQueue::new()));
+        let agent = AgentService::new(agent_pool.clone(), action_queue.clone());
+        let controller = ControllerService::new(agent_pool.clone(), action_queue.clone());
+
+        Server::builder()
+            .add_service(AgentServer::new(agent))
+            .add_service(ControllerServer::new(controller))
+            .serve(addr)
+            .await
+            .unwrap();
+    });
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let channel = Channel::from_static("http://[::1]:50051").connect().await?;
+    let mut agent_client = AgentClient::new(channel.clone());
+    agent_client
+        .register_agent(Request::new(RegisterAgentRequest {
+            health: Some(Health { cpu_avail: 42, memory_avail: 2048 }),
+            hostname: Some(agent::Hostname { host: String::from("http://[::1]"), port: 50052 }),
+            labels: vec![],
+            capabilities: None,
+            pull: false,
+            ephemeral: false,
+        }))
+        .await?;
+
+    // Repository "a" keeps the Agent busy and queues three Actions, then repository "b" queues one.
+    let requests = [(1, "a", 0), (2, "a", 0), (3, "a", 0), (4, "a", 5), (5, "b", 0)].map(|(action_id, tenant, priority)| {
+        ActionRequest {
+            repo_url: format!("https://example.com/{}.git", tenant),
+            priority,
+            ..action_request(action_id, &[])
+        }
+    });
+    let mut streams = Vec::new();
+    for request in requests {
+        let mut client = ControllerClient::new(channel.clone());
+        streams.push(client.schedule_action(Request::new(request)).await?.into_inner());
+    }
+    for mut stream in streams {
+        while stream.message().await?.is_some() {}
+    }
+
+    // "b" gets its share before "a" runs again, and "a" runs its higher priority Action first.
+    assert_eq!(*executed.lock().await, vec![1, 5, 4, 2, 3]);
+
+    Ok(())
+}
@@ -0,0 +1,247 @@
+mod common;
+use common::{action_request, RecordingAgent};
+
+use scheduler::proto::actions;
+
+use scheduler::proto::scheduler as agent;
+use agent::agent_client::AgentClient;
+use agent::agent_server::AgentServer;
+use agent::work_request::Message;
+use agent::work_response::Work;
+use agent::{Health, LaunchResult, RegisterAgentRequest, WorkRequest};
+
+use scheduler::proto::scheduler as admin;
+use admin::admin_client::AdminClient;
+use admin::admin_server::AdminServer;
+use admin::ListAgentsRequest;
+
+use scheduler::proto::scheduler as controller;
+use controller::controller_server::ControllerServer;
+use controller::{controller_client::ControllerClient, ActionStatus};
+
+use scheduler::interfaces::server as server;
+use server::admin_interface::AdminService;
+use server::agent_interface::AgentService;
+use server::controller_interface::ControllerService;
+
+use scheduler::logic as logic;
+use logic::agent_pool_logic::AgentPool;
+use logic::action_queue_logic::ActionsQueue;
+
+use tonic::transport::Server;
+use tonic::transport::Channel;
+use tonic::Request;
+use std::error::Error;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+use tokio::time::Duration;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+#[tokio::test]
+async fn test_pull_agent() -> Result<(), Box<dyn Error>> {
+    let recording_agent = RecordingAgent::serve(50052, Duration::from_millis(100));
+
+    tokio::spawn(async {
+        let addr = "[::1]:50051".parse().unwrap();
+        let agent_pool = Arc::new(Mutex::new(AgentPool::new()));
+        let action_queue = Arc::new(Mutex::new(ActionsQueue::new()));
+        let agent = AgentService::new(agent_pool.clone(), action_queue.clone());
+        let controller = ControllerService::new(agent_pool.clone(), action_queue.clone());
+        let admin = AdminService::new(agent_pool.clone(), action_queue.clone());
+
+        Server::builder()
+            .add_service(AgentServer::new(agent))
+            .add_service(ControllerServer::new(controller))
+            .add_service(AdminServer::new(admin))
+            .serve(addr)
+            .await
+            .unwrap();
+    });
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let channel = Channel::from_static("http://[::1]:50051").connect().await?;
+    let mut agent_client = AgentClient::new(channel.clone());
+    let mut admin_client = AdminClient::new(channel.clone());
+
+    // An Agent the Scheduler dials, and one behind a NAT, that is not reachable at all.
+    agent_client
+        .register_agent(Request::new(RegisterAgentRequest {
+            health: Some(Health { cpu_avail: 42, memory_avail: 2048 }),
+            hostname: Some(agent::Hostname { host: String::from("http://[::1]"), port: 50052 }),
+            labels: vec![],
+            capabilities: None,
+            pull: false,
+            ephemeral: false,
+        }))
+        .await?;
+    let pull_id = agent_client
+        .register_agent(Request::new(RegisterAgentRequest {
+            health: Some(Health { cpu_avail: 42, memory_avail: 2048 }),
+            hostname: None,
+            labels: vec![String::from("behind-nat")],
+            capabilities: None,
+            pull: true,
+            ephemeral: false,
+        }))
+        .await?
+        .into_inner()
+        .id;
+
+    // Until it opens its Work stream, the pull-mode Agent gets no Action.
+    let agents = admin_client.list_agents(Request::new(ListAgentsRequest {})).await?.into_inner().agents;
+    let pull_agent = agents.iter().find(|agent| agent.id == pull_id).unwrap();
+    assert!(pull_agent.pull && !pull_agent.connected);
+
+    let (request_tx, request_rx) = mpsc::unbounded_channel();
+    request_tx.send(WorkRequest { message: Some(Message::AgentId(pull_id)) })?;
+    let mut work = agent_client.work(Request::new(UnboundedReceiverStream::new(request_rx))).await?.into_inner();
+
+    // Actions come down the Work stream, and their responses go back up it.
+    let stub = tokio::spawn(async move {
+        let mut pulled = Vec::new();
+        while let Ok(Some(work)) = work.message().await {
+            let Some(Work::Action(action)) = work.work else {
+                continue;
+            };
+            pulled.push(action.action_id);
+            let launched = LaunchResult { action_id: action.action_id, error: None };
+            request_tx.send(WorkRequest { message: Some(Message::Launched(launched)) }).unwrap();
+            let response = actions::ActionResponseStream {
+                action_id: action.action_id,
+                log: String::from("pulled"),
+                result: Some(actions::ActionResult {
+                    completion: actions::ActionStatus::Completed.into(),
+                    exit_code: Some(0),
+                }),
+            };
+            request_tx.send(WorkRequest { message: Some(Message::Response(response)) }).unwrap();
+            request_tx.send(WorkRequest { message: Some(Message::FinishedActionId(action.action_id)) }).unwrap();
+            if pulled.len() == 1 {
+                // Close the Work stream once done.
+                break;
+            }
+        }
+        pulled
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let agents = admin_client.list_agents(Request::new(ListAgentsRequest {})).await?.into_inner().agents;
+    assert!(agents.iter().find(|agent| agent.id == pull_id).unwrap().connected);
+
+    // Both modes coexist: each Action goes to the only Agent it can run on.
+    let mut controller_client = ControllerClient::new(channel.clone());
+    let mut stream = controller_client.schedule_action(Request::new(action_request(1, &["behind-nat"]))).await?.into_inner();
+    let mut responses = Vec::new();
+    while let Some(response) = stream.message().await? {
+        responses.push(response);
+    }
+    assert_eq!(responses.len(), 1);
+    assert_eq!(responses[0].log, "pulled");
+    assert_eq!(stub.await?, vec![1]);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // The closed Work stream leaves only the other Agent to run Actions.
+    let agents = admin_client.list_agents(Request::new(ListAgentsRequest {})).await?.into_inner().agents;
+    let pull_agent = agents.iter().find(|agent| agent.id == pull_id).unwrap();
+    assert!(!pull_agent.connected);
+    assert!(pull_agent.running_actions.is_empty());
+
+    let mut stream = controller_client.schedule_action(Request::new(action_request(2, &[]))).await?.into_inner();
+    while let Some(response) = stream.message().await? {
+        assert_eq!(response.log, "done");
+    }
+    assert_eq!(*recording_agent.executed.lock().await, vec![2]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_pull_agent_launch_failure() -> Result<(), Box<dyn Error>> {
+    let recording_agent = RecordingAgent::serve(50058, Duration::ZERO);
+
+    tokio::spawn(async {
+        let addr = "[::1]:50057".parse().unwrap();
+        // A single failed dispatch quarantines an Agent.
+        let agent_pool = Arc::new(Mutex::new(AgentPool::new().with_circuit_breaker(1, Duration::from_secs(60))));
+        let action_queue = Arc::new(Mutex::new(ActionsQueue::new()));
+        let agent = AgentService::new(agent_pool.clone(), action_queue.clone());
+        let controller = ControllerService::new(agent_pool.clone(), action_queue.clone()).with_dispatch_attempts(2);
+        let admin = AdminService::new(agent_pool.clone(), action_queue.clone());
+
+        Server::builder()
+            .add_service(AgentServer::new(agent))
+            .add_service(ControllerServer::new(controller))
+            .add_service(AdminServer::new(admin))
+            .serve(addr)
+            .await
+            .unwrap();
+    });
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let channel = Channel::from_static("http://[::1]:50057").connect().await?;
+    let mut agent_client = AgentClient::new(channel.clone());
+    let mut admin_client = AdminClient::new(channel.clone());
+
+    // The pull-mode Agent has the lowest score, so it is picked first.
+    let pull_id = agent_client
+        .register_agent(Request::new(RegisterAgentRequest {
+            health: Some(Health { cpu_avail: 1, memory_avail: 1 }),
+            hostname: None,
+            labels: vec![],
+            capabilities: None,
+            pull: true,
+            ephemeral: false,
+        }))
+        .await?
+        .into_inner()
+        .id;
+    agent_client
+        .register_agent(Request::new(RegisterAgentRequest {
+            health: Some(Health { cpu_avail: 42, memory_avail: 2048 }),
+            hostname: Some(agent::Hostname { host: String::from("http://[::1]"), port: 50058 }),
+            labels: vec![],
+            capabilities: None,
+            pull: false,
+            ephemeral: false,
+        }))
+        .await?;
+
+    // It cannot start any Action.
+    let (request_tx, request_rx) = mpsc::unbounded_channel();
+    request_tx.send(WorkRequest { message: Some(Message::AgentId(pull_id)) })?;
+    let mut work = agent_client.work(Request::new(UnboundedReceiverStream::new(request_rx))).await?.into_inner();
+    tokio::spawn(async move {
+        while let Ok(Some(work)) = work.message().await {
+            let Some(Work::Action(action)) = work.work else {
+                continue;
+            };
+            let launched = LaunchResult { action_id: action.action_id, error: Some(String::from("image not found")) };
+            request_tx.send(WorkRequest { message: Some(Message::Launched(launched)) }).unwrap();
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut controller_client = ControllerClient::new(channel.clone());
+    let mut stream = controller_client.schedule_action(Request::new(action_request(1, &[]))).await?.into_inner();
+    let mut responses = Vec::new();
+    while let Some(response) = stream.message().await? {
+        responses.push(response);
+    }
+
+    // The failed launch counts as a failed dispatch: the Action runs on the other Agent.
+    assert_eq!(responses.len(), 2);
+    assert!(responses[0].log.contains("image not found"));
+    assert!(responses[0].log.contains("Retrying on another Agent"));
+    assert_eq!(responses[0].result.as_ref().unwrap().completion(), ActionStatus::Scheduled);
+    assert_eq!(responses[1].log, "done");
+    assert_eq!(*recording_agent.executed.lock().await, vec![1]);
+
+    let agents = admin_client.list_agents(Request::new(ListAgentsRequest {})).await?.into_inner().agents;
+    let pull_agent = agents.iter().find(|agent| agent.id == pull_id).unwrap();
+    assert!(pull_agent.quarantined);
+    assert!(pull_agent.running_actions.is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,103 @@
+mod common;
+use common::{action_request, RecordingAgent};
+
+use scheduler::proto::scheduler as agent;
+use agent::agent_client::AgentClient;
+use agent::agent_server::AgentServer;
+use agent::{Capabilities, Health, RegisterAgentRequest};
+
+use scheduler::proto::scheduler as admin;
+use admin::admin_client::AdminClient;
+use admin::admin_server::AdminServer;
+use admin::ListQueueRequest;
+
+use scheduler::proto::scheduler as controller;
+use controller::controller_server::ControllerServer;
+use controller::controller_client::ControllerClient;
+
+use scheduler::interfaces::server as server;
+use server::admin_interface::AdminService;
+use server::agent_interface::AgentService;
+use server::controller_interface::ControllerService;
+
+use scheduler::logic as logic;
+use logic::agent_pool_logic::AgentPool;
+use logic::action_queue_logic::ActionsQueue;
+
+use tonic::transport::Server;
+use tonic::transport::Channel;
+use tonic::Request;
+use std::error::Error;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::Duration;
+
+fn register_request(port: u32, labels: Vec<String>) -> RegisterAgentRequest {
+    RegisterAgentRequest {
+        health: Some(Health { cpu_avail: 42, memory_avail: 2048 }),
+        hostname: Some(agent::Hostname { host: String::from("http://[::1]"), port }),
+        labels,
+        capabilities: Some(Capabilities {
+            arch: String::from("x86_64"),
+            os: String::from("linux"),
+            runner_types: vec![String::from("docker")],
+        }),
+        pull: false,
+        ephemeral: false,
+    }
+}
+
+#[tokio::test]
+async fn test_runs_on() -> Result<(), Box<dyn Error>> {
+    let recording_agent = RecordingAgent::serve(50052, Duration::ZERO);
+    let executed = recording_agent.executed.clone();
+
+    tokio::spawn(async {
+        let addr = "[::1]:50051".parse().unwrap();
+        let agent_pool = Arc::new(Mutex::new(AgentPool::new()));
+        let action_queue = Arc::new(Mutex::new(ActionsQueue::new()));
+        let agent = AgentService::new(agent_pool.clone(), action_queue.clone());
+        // Dispatching to the wrong Agent fails the Action right away.
+        let controller = ControllerService::new(agent_pool.clone(), action_queue.clone()).with_dispatch_attempts(1);
+        let admin = AdminService::new(agent_pool.clone(), action_queue.clone());
+
+        Server::builder()
+            .add_service(AgentServer::new(agent))
+            .add_service(ControllerServer::new(controller))
+            .add_service(AdminServer::new(admin))
+            .serve(addr)
+            .await
+            .unwrap();
+    });
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let channel = Channel::from_static("http://[::1]:50051").connect().await?;
+    let mut agent_client = AgentClient::new(channel.clone());
+    let mut controller_client = ControllerClient::new(channel.clone());
+    let mut admin_client = AdminClient::new(channel);
+
+    // This Agent does not listen, and has no GPU.
+    agent_client.register_agent(Request::new(register_request(50053, vec![]))).await?;
+
+    let request = action_request(1, &["linux", "gpu"]);
+    let mut stream = controller_client.schedule_action(Request::new(request)).await?.into_inner();
+
+    // No Agent matches, so the Action waits in the Queue.
+    let queue = admin_client.list_queue(Request::new(ListQueueRequest {})).await?.into_inner().actions;
+    assert_eq!(queue.len(), 1);
+    assert_eq!(queue[0].runs_on, vec![String::from("linux"), String::from("gpu")]);
+
+    // A matching Agent registers, and gets the Action.
+    agent_client.register_agent(Request::new(register_request(50052, vec![String::from("gpu")]))).await?;
+
+    let mut responses = Vec::new();
+    while let Some(response) = stream.message().await? {
+        responses.push(response);
+    }
+    assert_eq!(responses.len(), 1);
+    assert_eq!(responses[0].log, "done");
+    assert_eq!(*executed.lock().await, vec![1]);
+
+    Ok(())
+}
@@ -50,7 +50,8 @@ export default function PipelinePage() {
                   </span>
                   {action.status === "ACTION_STATUS_COMPLETED" ? (
                     <span className="bg-success w-3 h-3 rounded-full"></span>
-                  ) : action.status === "ACTION_STATUS_ERROR" ? (
+                  ) : action.status === "ACTION_STATUS_ERROR" ||
+                    action.status === "ACTION_STATUS_TIMED_OUT" ? (
                     <span className="bg-error w-3 h-3 rounded-full"></span>
                   ) : (
                     <span className="bg-warning w-3 h-3 rounded-full"></span>
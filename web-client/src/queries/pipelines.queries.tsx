@@ -1,4 +1,4 @@
-import { Pipeline } from "@/types";
+import { Pipeline, PipelinePage } from "@/types";
 import { useQuery } from "@tanstack/react-query";
 import ky from "ky";
 
@@ -12,9 +12,9 @@ const fetchPipelines = async ({
     : "/pipeline?verbose=false";
   const json = await ky
     .get(import.meta.env.VITE_CONTROLLER_ENDPOINT + endpoint)
-    .json<Pipeline[]>();
+    .json<PipelinePage>();
 
-  return json;
+  return json.pipelines;
 };
 
 const fetchPipeline = async ({
@@ -3,6 +3,18 @@ export interface Pipeline {
   repository_url: string;
   name: string;
   actions: Action[];
+  started_at: string | null;
+  finished_at: string | null;
+  duration: number | null; // In milliseconds
+  timeline?: ActionEvent[];
+  created_at: string;
+  manifest_hash: string | null; // Sha256 of the manifest, in hex
+  manifest?: string; // Only when getting a single pipeline
+  commit_sha: string | null;
+  ref: string | null;
+  event: string | null;
+  trigger: string | null;
+  requester: string | null;
 }
 
 export interface Action {
@@ -14,11 +26,79 @@ export interface Action {
   type: string;
   status: string;
   logs?: string[];
+  needs?: string[];
+  env?: string[]; // As KEY=VALUE
+  condition?: string;
+  retry: RetryPolicy;
+  priority: number; // Higher ones of a repository are dispatched first
+  attempt: number;
+  attempts?: ActionAttempt[];
+  started_at: string | null;
+  finished_at: string | null;
+  duration: number | null; // In milliseconds
 }
 
+export interface RetryPolicy {
+  max_attempts: number;
+  backoff: number; // In seconds
+  on: number[];
+}
+
+export interface ActionAttempt {
+  attempt: number;
+  logs: string[];
+}
+
+export interface ActionEvent {
+  action_id: number;
+  action_name: string;
+  status: PipelineStatus;
+  source: "controller" | "scheduler" | "agent";
+  attempt: number;
+  created_at: string;
+}
+
+export interface Diagnostic {
+  severity: "error" | "warning";
+  code: string;
+  message: string;
+  path: string; // Yaml path, e.g. actions.build.commands
+  line: number | null;
+  column: number | null;
+  suggestion: string | null;
+}
+
+export interface ValidationReport {
+  valid: boolean;
+  diagnostics: Diagnostic[];
+}
+
+export interface PipelinePage {
+  pipelines: Pipeline[];
+  next_cursor: string | null; // To get the next page, null on the last one
+}
+
+export interface ActionLog {
+  offset: number;
+  attempt: number;
+  message: string;
+}
+
+export interface ActionLogsPage {
+  logs: ActionLog[];
+  next_offset: number | null;
+}
+
+export type LogStreamEvent =
+  | { event: "log"; offset: number; attempt: number; message: string }
+  | { event: "end"; status: PipelineStatus };
+
 export type PipelineStatus =
   | "ACTION_STATUS_PENDING"
   | "ACTION_STATUS_SCHEDULED"
   | "ACTION_STATUS_RUNNING"
   | "ACTION_STATUS_COMPLETED"
-  | "ACTION_STATUS_ERROR";
+  | "ACTION_STATUS_ERROR"
+  | "ACTION_STATUS_CANCELLED"
+  | "ACTION_STATUS_TIMED_OUT"
+  | "ACTION_STATUS_SKIPPED";
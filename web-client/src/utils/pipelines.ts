@@ -2,8 +2,13 @@ import { Pipeline, PipelineStatus } from "../types";
 
 export function getPipelineStatus(pipeline: Pipeline): PipelineStatus {
   const statuses = pipeline.actions.map((action) => action.status);
-  if (statuses.includes("ACTION_STATUS_ERROR")) {
+  if (
+    statuses.includes("ACTION_STATUS_ERROR") ||
+    statuses.includes("ACTION_STATUS_TIMED_OUT")
+  ) {
     return "ACTION_STATUS_ERROR";
+  } else if (statuses.includes("ACTION_STATUS_CANCELLED")) {
+    return "ACTION_STATUS_CANCELLED";
   } else if (statuses.includes("ACTION_STATUS_RUNNING")) {
     return "ACTION_STATUS_RUNNING";
   } else if (statuses.includes("ACTION_STATUS_SCHEDULED")) {